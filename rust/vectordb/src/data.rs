@@ -14,5 +14,9 @@
 
 //! Data types, schema coercion, and data cleaning and etc.
 
+#[cfg(feature = "datafusion")]
+mod expression;
 pub mod inspect;
+pub mod migration;
 pub mod sanitize;
+pub mod transform;
@@ -0,0 +1,150 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Evaluate SQL scalar expressions over batches, with DataFusion.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{ArrowError, DataType, Schema, SchemaRef};
+use datafusion::common::{config::ConfigOptions, plan_err, DFSchema, DataFusionError};
+use datafusion::logical_expr::{AggregateUDF, ScalarUDF, TableSource, WindowUDF};
+use datafusion::optimizer::simplify_expressions::{ExprSimplifier, SimplifyContext};
+use datafusion::physical_expr::{
+    create_physical_expr, execution_props::ExecutionProps, PhysicalExpr,
+};
+use datafusion::sql::planner::{ContextProvider, PlannerContext, SqlToRel};
+use datafusion::sql::sqlparser::{dialect::GenericDialect, parser::Parser};
+use datafusion::sql::TableReference;
+
+use crate::error::{Error, Result};
+
+/// The planner context of a standalone expression: no tables, and no user-defined
+/// functions, so only the built-in functions can be called.
+#[derive(Default)]
+struct ExpressionContext {
+    options: ConfigOptions,
+}
+
+impl ContextProvider for ExpressionContext {
+    fn get_table_source(
+        &self,
+        name: TableReference,
+    ) -> datafusion::common::Result<Arc<dyn TableSource>> {
+        plan_err!(
+            "Table {} can not be referenced in a column expression",
+            name
+        )
+    }
+
+    fn get_function_meta(&self, _name: &str) -> Option<Arc<ScalarUDF>> {
+        None
+    }
+
+    fn get_aggregate_meta(&self, _name: &str) -> Option<Arc<AggregateUDF>> {
+        None
+    }
+
+    fn get_window_meta(&self, _name: &str) -> Option<Arc<WindowUDF>> {
+        None
+    }
+
+    fn get_variable_type(&self, _variable_names: &[String]) -> Option<DataType> {
+        None
+    }
+
+    fn options(&self) -> &ConfigOptions {
+        &self.options
+    }
+}
+
+fn to_arrow_error(err: impl Into<DataFusionError>) -> ArrowError {
+    ArrowError::ExternalError(Box::new(err.into()))
+}
+
+/// Plan the SQL scalar expression `sql` over batches of the `schema`, e.g.,
+/// `lower(trim(name))`.
+pub(crate) fn plan_expression(
+    sql: &str,
+    schema: &Schema,
+) -> std::result::Result<Arc<dyn PhysicalExpr>, ArrowError> {
+    let ast = Parser::new(&GenericDialect {})
+        .try_with_sql(sql)
+        .and_then(|mut parser| parser.parse_expr())
+        .map_err(to_arrow_error)?;
+    let df_schema = Arc::new(DFSchema::try_from(schema.clone()).map_err(to_arrow_error)?);
+
+    let context = ExpressionContext::default();
+    let expr = SqlToRel::new(&context)
+        .sql_to_expr(ast, &df_schema, &mut PlannerContext::new())
+        .map_err(to_arrow_error)?;
+    // The expression needs its types coerced before it can be planned.
+    let props = ExecutionProps::new();
+    let simplifier =
+        ExprSimplifier::new(SimplifyContext::new(&props).with_schema(df_schema.clone()));
+    let expr = simplifier
+        .coerce(expr, df_schema.clone())
+        .map_err(to_arrow_error)?;
+
+    create_physical_expr(&expr, &df_schema, schema, &props).map_err(to_arrow_error)
+}
+
+/// Evaluate an expression planned by [plan_expression] over the batch.
+pub(crate) fn evaluate_expression(
+    batch: &RecordBatch,
+    expression: &Arc<dyn PhysicalExpr>,
+) -> std::result::Result<ArrayRef, ArrowError> {
+    expression
+        .evaluate(batch)
+        .and_then(|value| value.into_array(batch.num_rows()))
+        .map_err(to_arrow_error)
+}
+
+/// The expressions of the computed columns, planned once over the schema of the input.
+#[derive(Debug)]
+pub(crate) struct PlannedExpressions {
+    schema: SchemaRef,
+    expressions: HashMap<String, Arc<dyn PhysicalExpr>>,
+}
+
+impl PlannedExpressions {
+    /// Plan the expression of each column over batches of the `schema`.
+    pub(crate) fn try_new(
+        expressions: &HashMap<String, String>,
+        schema: SchemaRef,
+    ) -> Result<Self> {
+        let expressions = expressions
+            .iter()
+            .map(|(column, sql)| {
+                let planned = plan_expression(sql, &schema).map_err(|e| Error::Schema {
+                    message: format!("Invalid expression {} for column {}: {}", sql, column, e),
+                })?;
+                Ok((column.clone(), planned))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            schema,
+            expressions,
+        })
+    }
+
+    /// The planned expression of the `column`, if the batches have the planned schema.
+    pub(crate) fn get(&self, schema: &Schema, column: &str) -> Option<&Arc<dyn PhysicalExpr>> {
+        if self.schema.as_ref() != schema {
+            return None;
+        }
+        self.expressions.get(column)
+    }
+}
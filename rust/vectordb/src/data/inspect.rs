@@ -12,18 +12,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
+use arrow::array::ArrayData;
 use arrow::compute::kernels::{aggregate::bool_and, length::length};
 use arrow_array::{
     cast::AsArray,
-    types::{ArrowPrimitiveType, Int32Type, Int64Type},
-    Array, GenericListArray, OffsetSizeTrait, PrimitiveArray, RecordBatchReader,
+    types::{ArrowPrimitiveType, Float64Type, Int32Type, Int64Type},
+    Array, GenericListArray, OffsetSizeTrait, PrimitiveArray, RecordBatch, RecordBatchReader,
 };
+use arrow_cast::cast::cast;
 use arrow_ord::cmp::eq;
-use arrow_schema::DataType;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use half::f16;
 use num_traits::{ToPrimitive, Zero};
 
+use super::transform::{vector_norms, VectorNorm};
 use crate::error::{Error, Result};
 
 pub(crate) fn infer_dimension<T: ArrowPrimitiveType>(
@@ -46,6 +53,19 @@ where
     }
 }
 
+/// Infer the common length of the lists in `col`, if they all have the same length.
+fn list_dimension(col: &dyn Array, col_name: &str) -> Result<Option<i64>> {
+    match *col.data_type() {
+        DataType::List(_) => {
+            Ok(infer_dimension::<Int32Type>(col.as_list::<i32>())?.map(|d| d as i64))
+        }
+        DataType::LargeList(_) => infer_dimension::<Int64Type>(col.as_list::<i64>()),
+        _ => Err(Error::Schema {
+            message: format!("Column {} is not a list", col_name),
+        }),
+    }
+}
+
 /// Infer the vector columns from a dataset.
 ///
 /// Parameters
@@ -81,17 +101,7 @@ pub fn infer_vector_columns(
             let col = batch.column_by_name(&col_name).ok_or(Error::Schema {
                 message: format!("Column {} not found", col_name),
             })?;
-            if let Some(dim) = match *col.data_type() {
-                DataType::List(_) => {
-                    infer_dimension::<Int32Type>(col.as_list::<i32>())?.map(|d| d as i64)
-                }
-                DataType::LargeList(_) => infer_dimension::<Int64Type>(col.as_list::<i64>())?,
-                _ => {
-                    return Err(Error::Schema {
-                        message: format!("Column {} is not a list", col_name),
-                    })
-                }
-            } {
+            if let Some(dim) = list_dimension(col, &col_name)? {
                 if let Some(Some(prev_dim)) = columns_to_infer.get(&col_name) {
                     if prev_dim != &dim {
                         columns_to_infer.remove(&col_name);
@@ -108,6 +118,486 @@ pub fn infer_vector_columns(
     Ok(columns)
 }
 
+/// Infer the schema of a dataset from at most `max_batches` of its batches.
+///
+/// List of float columns whose lists have the same length across the sampled batches
+/// are inferred as `FixedSizeList` vector columns.
+///
+/// Returns the inferred schema, and whether the reader had more batches than were
+/// sampled, in which case the inference may be incomplete.
+pub fn infer_schema_sample(
+    mut reader: impl RecordBatchReader + Send,
+    max_batches: usize,
+) -> Result<(SchemaRef, bool)> {
+    let schema = reader.schema();
+    let mut dimensions: HashMap<String, Option<i64>> = schema
+        .fields()
+        .iter()
+        .filter(|f| match f.data_type() {
+            DataType::List(sub_field) | DataType::LargeList(sub_field) => {
+                sub_field.data_type().is_floating()
+            }
+            _ => false,
+        })
+        .map(|f| (f.name().to_string(), None))
+        .collect();
+
+    for batch in reader.by_ref().take(max_batches) {
+        let batch = batch?;
+        // Empty batches do not tell anything about the dimension.
+        if batch.num_rows() == 0 {
+            continue;
+        }
+        let col_names = dimensions.keys().cloned().collect::<Vec<_>>();
+        for col_name in col_names {
+            let col = batch.column_by_name(&col_name).ok_or(Error::Schema {
+                message: format!("Column {} not found", col_name),
+            })?;
+            match (list_dimension(col, &col_name)?, dimensions[&col_name]) {
+                (Some(dim), None) => {
+                    dimensions.insert(col_name, Some(dim));
+                }
+                (Some(dim), Some(prev_dim)) if dim == prev_dim => {}
+                _ => {
+                    dimensions.remove(&col_name);
+                }
+            }
+        }
+    }
+    let incomplete = reader.next().is_some();
+
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|f| match (f.data_type(), dimensions.get(f.name())) {
+            (DataType::List(sub_field) | DataType::LargeList(sub_field), Some(Some(dim))) => {
+                Arc::new(
+                    Field::new(
+                        f.name(),
+                        DataType::FixedSizeList(sub_field.clone(), *dim as i32),
+                        f.is_nullable(),
+                    )
+                    .with_metadata(f.metadata().clone()),
+                )
+            }
+            _ => f.clone(),
+        })
+        .collect::<Vec<_>>();
+    Ok((
+        Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone())),
+        incomplete,
+    ))
+}
+
+/// A histogram of values, with `counts[i]` values in `[edges[i], edges[i + 1])`.
+///
+/// The last bucket also includes its upper edge.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// The `buckets + 1` bucket edges, in ascending order.
+    pub edges: Vec<f32>,
+    /// The number of values in each bucket.
+    pub counts: Vec<usize>,
+    /// The number of NaN or infinite values, which are not in any bucket.
+    pub non_finite: usize,
+}
+
+/// The number of fine buckets the norms are counted in by [vector_magnitude_histogram].
+const FINE_BUCKETS: usize = 4096;
+
+/// Counts of non-negative values in [FINE_BUCKETS] buckets of equal width starting at
+/// zero, whose width doubles whenever a value does not fit.
+#[derive(Debug)]
+struct NormSketch {
+    width: f32,
+    counts: Vec<usize>,
+    min: f32,
+    max: f32,
+    non_finite: usize,
+}
+
+impl NormSketch {
+    fn new() -> Self {
+        Self {
+            width: 0.0,
+            counts: vec![0; FINE_BUCKETS],
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+            non_finite: 0,
+        }
+    }
+
+    fn add(&mut self, norm: f32) {
+        if !norm.is_finite() {
+            self.non_finite += 1;
+            return;
+        }
+        self.min = self.min.min(norm);
+        self.max = self.max.max(norm);
+        if self.width == 0.0 && norm > 0.0 {
+            self.width = 2.0 * norm / FINE_BUCKETS as f32;
+        }
+        while norm >= self.width * FINE_BUCKETS as f32 && self.width > 0.0 {
+            for i in 0..FINE_BUCKETS / 2 {
+                self.counts[i] = self.counts[2 * i] + self.counts[2 * i + 1];
+            }
+            self.counts[FINE_BUCKETS / 2..].fill(0);
+            self.width *= 2.0;
+        }
+        let bucket = if self.width > 0.0 {
+            (norm / self.width) as usize
+        } else {
+            0
+        };
+        self.counts[bucket.min(FINE_BUCKETS - 1)] += 1;
+    }
+
+    /// Redistribute the fine buckets to `buckets` buckets evenly splitting `[min, max]`,
+    /// by the midpoints of the fine buckets.
+    fn histogram(&self, buckets: usize) -> Histogram {
+        if self.min > self.max {
+            return Histogram {
+                edges: vec![0.0; buckets + 1],
+                counts: vec![0; buckets],
+                non_finite: self.non_finite,
+            };
+        }
+        let width = (self.max - self.min) / buckets as f32;
+        let edges = (0..=buckets)
+            .map(|i| {
+                if i == buckets {
+                    self.max
+                } else {
+                    self.min + width * i as f32
+                }
+            })
+            .collect();
+        let mut counts = vec![0; buckets];
+        for (i, count) in self.counts.iter().enumerate().filter(|(_, c)| **c > 0) {
+            let midpoint = (i as f32 + 0.5) * self.width;
+            let bucket = if width > 0.0 {
+                ((midpoint - self.min).max(0.0) / width) as usize
+            } else {
+                0
+            };
+            counts[bucket.min(buckets - 1)] += count;
+        }
+        Histogram {
+            edges,
+            counts,
+            non_finite: self.non_finite,
+        }
+    }
+}
+
+/// Compute a histogram of the L2 norms of a vector column, e.g., to tune an index.
+///
+/// The norms are counted as the batches are read, in a fixed number of fine buckets, so
+/// the memory does not grow with the number of rows. The buckets evenly split the range
+/// between the smallest and the largest norm; a norm within a fine bucket of an edge may
+/// be counted in the adjacent bucket. Null vectors are skipped, and NaN or infinite
+/// norms are only counted in [Histogram::non_finite].
+pub fn vector_magnitude_histogram(
+    reader: impl RecordBatchReader + Send,
+    column: &str,
+    buckets: usize,
+) -> Result<Histogram> {
+    if buckets == 0 {
+        return Err(Error::Schema {
+            message: "A histogram needs at least one bucket".to_string(),
+        });
+    }
+    match reader.schema().field_with_name(column) {
+        Ok(field) if matches!(field.data_type(), DataType::FixedSizeList(_, _)) => {}
+        _ => {
+            return Err(Error::Schema {
+                message: format!("Column {} is not a vector column", column),
+            })
+        }
+    }
+
+    let mut sketch = NormSketch::new();
+    for batch in reader {
+        let batch = batch?;
+        let vectors = batch.column_by_name(column).ok_or(Error::Schema {
+            message: format!("Column {} not found", column),
+        })?;
+        let norms = vector_norms(vectors.as_fixed_size_list(), VectorNorm::L2)?;
+        norms.iter().flatten().for_each(|norm| sketch.add(norm));
+    }
+    Ok(sketch.histogram(buckets))
+}
+
+/// The maximum number of example rows kept for each length by [check_list_dimension_uniformity].
+const MAX_EXAMPLE_ROWS: usize = 5;
+
+/// The lists of one length, in a [DimensionReport].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListLength {
+    /// The number of lists with this length.
+    pub count: usize,
+    /// The indices of the first few rows with this length.
+    pub example_rows: Vec<usize>,
+}
+
+/// The distinct lengths of the lists of a column.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DimensionReport {
+    /// The lists of each distinct length.
+    pub lengths: BTreeMap<usize, ListLength>,
+}
+
+impl DimensionReport {
+    /// Whether all the lists have the same length, i.e., the column can be a vector column.
+    pub fn is_uniform(&self) -> bool {
+        self.lengths.len() <= 1
+    }
+
+    /// The most common length, the smallest one on ties.
+    pub fn dominant_length(&self) -> Option<usize> {
+        // `max_by_key` returns the last maximum, so iterate from the largest length.
+        self.lengths
+            .iter()
+            .rev()
+            .max_by_key(|(_, l)| l.count)
+            .map(|(len, _)| *len)
+    }
+
+    /// The lengths other than the dominant one.
+    pub fn outliers(&self) -> Vec<usize> {
+        let dominant = self.dominant_length();
+        self.lengths
+            .keys()
+            .copied()
+            .filter(|len| Some(*len) != dominant)
+            .collect()
+    }
+}
+
+/// Scan the lengths of the lists of a column, e.g., before coercing it to a vector column.
+///
+/// This reports all the mixed dimensions upfront, instead of failing on the first batch
+/// that can not be coerced. Null lists are skipped.
+pub fn check_list_dimension_uniformity(
+    reader: impl RecordBatchReader + Send,
+    column: &str,
+) -> Result<DimensionReport> {
+    match reader.schema().field_with_name(column) {
+        Ok(field)
+            if matches!(
+                field.data_type(),
+                DataType::List(_) | DataType::LargeList(_)
+            ) => {}
+        _ => {
+            return Err(Error::Schema {
+                message: format!("Column {} is not a list", column),
+            })
+        }
+    }
+
+    let mut report = DimensionReport::default();
+    let mut offset = 0;
+    for batch in reader {
+        let batch = batch?;
+        let col = batch.column_by_name(column).ok_or(Error::Schema {
+            message: format!("Column {} not found", column),
+        })?;
+        let lengths = length(col)?;
+        let lengths = match lengths.data_type() {
+            DataType::Int32 => lengths
+                .as_primitive::<Int32Type>()
+                .iter()
+                .map(|l| l.map(|l| l as usize))
+                .collect::<Vec<_>>(),
+            _ => lengths
+                .as_primitive::<Int64Type>()
+                .iter()
+                .map(|l| l.map(|l| l as usize))
+                .collect(),
+        };
+        for (row, len) in lengths.into_iter().enumerate() {
+            let Some(len) = len else {
+                continue;
+            };
+            let entry = report.lengths.entry(len).or_default();
+            entry.count += 1;
+            if entry.example_rows.len() < MAX_EXAMPLE_ROWS {
+                entry.example_rows.push(offset + row);
+            }
+        }
+        offset += batch.num_rows();
+    }
+    Ok(report)
+}
+
+/// Check that the named vector columns all have the same dimension, e.g., text and image
+/// embeddings that are fused late, and return that dimension.
+///
+/// Only the schema is checked, so the reader can still be read afterwards.
+pub fn assert_vector_dims_equal(
+    reader: &impl RecordBatchReader,
+    columns: &[String],
+) -> Result<i32> {
+    let schema = reader.schema();
+    let mut dims = Vec::with_capacity(columns.len());
+    for name in columns {
+        match schema.field_with_name(name).map(|f| f.data_type()) {
+            Ok(DataType::FixedSizeList(_, dim)) => dims.push((name.as_str(), *dim)),
+            _ => {
+                return Err(Error::Schema {
+                    message: format!("Column {} is not a vector column", name),
+                })
+            }
+        }
+    }
+    let Some((_, dim)) = dims.first().copied() else {
+        return Err(Error::Schema {
+            message: "No vector columns to compare".to_string(),
+        });
+    };
+    if dims.iter().any(|(_, d)| *d != dim) {
+        return Err(Error::Schema {
+            message: format!(
+                "Vector columns have different dimensions: {}",
+                dims.iter()
+                    .map(|(name, d)| format!("{} ({})", name, d))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        });
+    }
+    Ok(dim)
+}
+
+/// Collect the memory ranges of the non-empty buffers of the array and its children.
+fn buffer_ranges(data: &ArrayData, ranges: &mut Vec<(usize, usize)>) {
+    let nulls = data.nulls().map(|n| n.buffer());
+    for buffer in data.buffers().iter().chain(nulls) {
+        if !buffer.is_empty() {
+            let start = buffer.as_ptr() as usize;
+            ranges.push((start, start + buffer.len()));
+        }
+    }
+    for child in data.child_data() {
+        buffer_ranges(child, ranges);
+    }
+}
+
+/// Check that no two columns of the batch share memory, e.g., after a coercion that
+/// reuses buffers without copying them, so that mutating a column in place can not
+/// corrupt another one.
+///
+/// Columns may share buffers by design, e.g., a computed column that copies another
+/// one, so this is a check to run on demand rather than on every coerced batch.
+pub fn validate_no_aliasing(batch: &RecordBatch) -> Result<()> {
+    let schema = batch.schema();
+    let mut columns: Vec<(&String, Vec<(usize, usize)>)> = vec![];
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        let mut ranges = vec![];
+        buffer_ranges(&column.to_data(), &mut ranges);
+        for (other, other_ranges) in &columns {
+            let overlaps = ranges.iter().any(|(start, end)| {
+                other_ranges
+                    .iter()
+                    .any(|(other_start, other_end)| start < other_end && other_start < end)
+            });
+            if overlaps {
+                return Err(Error::Schema {
+                    message: format!("Columns {} and {} share a buffer", other, field.name()),
+                });
+            }
+        }
+        columns.push((field.name(), ranges));
+    }
+    Ok(())
+}
+
+/// The magnitudes of the values of a vector column, in a [PrecisionReport].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrecisionReport {
+    /// The number of non-null values.
+    pub values: usize,
+    /// The largest absolute value, if any.
+    pub max_abs: Option<f64>,
+    /// The smallest nonzero absolute value, if any.
+    pub min_abs_nonzero: Option<f64>,
+    /// The number of values larger than the largest finite f16.
+    pub f16_overflows: usize,
+    /// The number of nonzero values smaller than the smallest normal f16, which lose
+    /// precision as subnormals, or flush to zero.
+    pub f16_subnormals: usize,
+}
+
+impl PrecisionReport {
+    /// Whether all the values can be stored as f16 without overflow or subnormal loss.
+    pub fn f16_is_safe(&self) -> bool {
+        self.f16_overflows == 0 && self.f16_subnormals == 0
+    }
+
+    /// The suggested value type of the vectors, f16 if it is safe, f32 otherwise.
+    pub fn suggested_type(&self) -> DataType {
+        if self.f16_is_safe() {
+            DataType::Float16
+        } else {
+            DataType::Float32
+        }
+    }
+}
+
+/// Scan the values of a float vector column, to suggest whether it can be stored as f16.
+///
+/// f16 halves the storage of f32 vectors, but only represents magnitudes up to 65504, and
+/// loses precision below 6.1e-5. Null vectors and null values are skipped.
+pub fn suggest_vector_precision(
+    reader: impl RecordBatchReader + Send,
+    column: &str,
+) -> Result<PrecisionReport> {
+    match reader.schema().field_with_name(column) {
+        Ok(field) if matches!(field.data_type(), DataType::FixedSizeList(f, _) if f.data_type().is_floating()) =>
+            {}
+        _ => {
+            return Err(Error::Schema {
+                message: format!("Column {} is not a float vector column", column),
+            })
+        }
+    }
+
+    let max_f16 = f16::MAX.to_f64();
+    let min_f16 = f16::MIN_POSITIVE.to_f64();
+    let mut report = PrecisionReport::default();
+    for batch in reader {
+        let batch = batch?;
+        let vectors = batch.column_by_name(column).ok_or(Error::Schema {
+            message: format!("Column {} not found", column),
+        })?;
+        let vectors = vectors.as_fixed_size_list();
+        let dim = vectors.value_length() as usize;
+        let values = cast(vectors.values(), &DataType::Float64)?;
+        let values = values.as_primitive::<Float64Type>();
+        for row in (0..vectors.len()).filter(|r| vectors.is_valid(*r)) {
+            let start = vectors.value_offset(row) as usize;
+            for i in start..start + dim {
+                if values.is_null(i) {
+                    continue;
+                }
+                let abs = values.value(i).abs();
+                report.values += 1;
+                report.max_abs = Some(report.max_abs.map_or(abs, |m| m.max(abs)));
+                if abs > 0.0 {
+                    report.min_abs_nonzero =
+                        Some(report.min_abs_nonzero.map_or(abs, |m| m.min(abs)));
+                }
+                if abs > max_f16 {
+                    report.f16_overflows += 1;
+                } else if abs > 0.0 && abs < min_f16 {
+                    report.f16_subnormals += 1;
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -116,8 +606,7 @@ mod tests {
         types::{Float32Type, Float64Type},
         FixedSizeListArray, Float32Array, ListArray, RecordBatch, RecordBatchIterator, StringArray,
     };
-    use arrow_schema::{DataType, Field, Schema};
-    use std::{sync::Arc, vec};
+    use std::vec;
 
     #[test]
     fn test_infer_vector_columns() {
@@ -178,4 +667,223 @@ mod tests {
         let cols = infer_vector_columns(reader, true).unwrap();
         assert_eq!(cols, vec!["fl"]);
     }
+
+    #[test]
+    fn test_infer_schema_sample() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vec",
+            DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+            true,
+        )]));
+        // The last batches have a different length, which the sample does not see.
+        let batches = (0..5)
+            .map(|i| {
+                let dim = if i < 2 { 2 } else { 3 };
+                let vectors =
+                    ListArray::from_iter_primitive::<Float32Type, _, _>(vec![Some(vec![
+                        Some(1.0);
+                        dim
+                    ])]);
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let reader = RecordBatchIterator::new(batches.clone().into_iter().map(Ok), schema.clone());
+        let (inferred, incomplete) = infer_schema_sample(reader, 2).unwrap();
+        assert!(incomplete);
+        assert_eq!(
+            inferred.field(0).data_type(),
+            &DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2)
+        );
+
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+        let (inferred, incomplete) = infer_schema_sample(reader, 5).unwrap();
+        assert!(!incomplete);
+        assert_eq!(inferred, schema);
+    }
+
+    #[test]
+    fn test_check_list_dimension_uniformity() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vec",
+            DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+            true,
+        )]));
+        let lists = |lengths: Vec<Option<usize>>| {
+            let array = ListArray::from_iter_primitive::<Float32Type, _, _>(
+                lengths.into_iter().map(|l| l.map(|l| vec![Some(1.0); l])),
+            );
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(array)]).unwrap()
+        };
+        let batches = vec![lists(vec![Some(3), None]), lists(vec![Some(3), Some(5)])];
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+
+        let report = check_list_dimension_uniformity(reader, "vec").unwrap();
+        assert!(!report.is_uniform());
+        assert_eq!(
+            report.lengths.keys().copied().collect::<Vec<_>>(),
+            vec![3, 5]
+        );
+        assert_eq!(report.lengths[&3].count, 2);
+        assert_eq!(report.lengths[&3].example_rows, vec![0, 2]);
+        assert_eq!(report.lengths[&5].example_rows, vec![3]);
+        assert_eq!(report.dominant_length(), Some(3));
+        assert_eq!(report.outliers(), vec![5]);
+
+        let reader = RecordBatchIterator::new(vec![], schema);
+        assert!(check_list_dimension_uniformity(reader, "missing").is_err());
+    }
+
+    #[test]
+    fn test_vector_magnitude_histogram() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vec",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+            true,
+        )]));
+        // Norms 0, 5, 10 (twice), 2.5 and a null vector.
+        let vectors = |rows: Vec<Option<[f32; 2]>>| {
+            let array = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+                rows.into_iter()
+                    .map(|r| r.map(|v| v.into_iter().map(Some).collect::<Vec<_>>())),
+                2,
+            );
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(array)]).unwrap()
+        };
+        let batches = vec![
+            vectors(vec![Some([0.0, 0.0]), Some([3.0, 4.0]), None]),
+            vectors(vec![Some([6.0, 8.0]), Some([-6.0, 8.0]), Some([1.5, 2.0])]),
+        ];
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+
+        let histogram = vector_magnitude_histogram(reader, "vec", 4).unwrap();
+        assert_eq!(histogram.edges, vec![0.0, 2.5, 5.0, 7.5, 10.0]);
+        assert_eq!(histogram.counts, vec![1, 1, 1, 2]);
+        assert_eq!(histogram.non_finite, 0);
+
+        let batches = vec![
+            vectors(vec![Some([1.0, 0.0]), Some([f32::NAN, 1.0])]),
+            vectors(vec![Some([f32::INFINITY, 0.0]), Some([2.0, 0.0])]),
+            vectors(vec![Some([1000.0, 0.0]), Some([4.0, 0.0])]),
+        ];
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+        let histogram = vector_magnitude_histogram(reader, "vec", 3).unwrap();
+        assert_eq!(histogram.edges, vec![1.0, 334.0, 667.0, 1000.0]);
+        assert_eq!(histogram.counts, vec![3, 0, 1]);
+        assert_eq!(histogram.non_finite, 2);
+
+        let reader = RecordBatchIterator::new(vec![], schema);
+        assert!(vector_magnitude_histogram(reader, "missing", 4).is_err());
+    }
+
+    #[test]
+    fn test_assert_vector_dims_equal() {
+        let vector_field = |name, dim| {
+            Field::new(
+                name,
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
+                true,
+            )
+        };
+        let schema = Arc::new(Schema::new(vec![
+            vector_field("text", 4),
+            vector_field("image", 4),
+            vector_field("audio", 3),
+            Field::new("id", DataType::Int32, false),
+        ]));
+        let reader = RecordBatchIterator::new(vec![], schema);
+        let columns = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            assert_vector_dims_equal(&reader, &columns(&["text", "image"])).unwrap(),
+            4
+        );
+        let err = assert_vector_dims_equal(&reader, &columns(&["text", "audio"])).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("different dimensions: text (4), audio (3)"));
+        assert!(assert_vector_dims_equal(&reader, &columns(&["text", "id"])).is_err());
+        assert!(assert_vector_dims_equal(&reader, &[]).is_err());
+    }
+
+    #[test]
+    fn test_suggest_vector_precision() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vec",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+            true,
+        )]));
+        let vectors = |rows: Vec<Option<[f32; 2]>>| {
+            let array = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+                rows.into_iter()
+                    .map(|r| r.map(|v| v.into_iter().map(Some).collect::<Vec<_>>())),
+                2,
+            );
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(array)]).unwrap()
+        };
+
+        let batches = vec![vectors(vec![Some([0.5, -2.0]), None, Some([0.0, 100.0])])];
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+        let report = suggest_vector_precision(reader, "vec").unwrap();
+        assert_eq!(report.values, 4);
+        assert_eq!(report.max_abs, Some(100.0));
+        assert_eq!(report.min_abs_nonzero, Some(0.5));
+        assert!(report.f16_is_safe());
+        assert_eq!(report.suggested_type(), DataType::Float16);
+
+        let batches = vec![
+            vectors(vec![Some([0.5, 1e-6])]),
+            vectors(vec![Some([-3e-7, 1e5])]),
+        ];
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+        let report = suggest_vector_precision(reader, "vec").unwrap();
+        assert_eq!(report.min_abs_nonzero, Some(3e-7_f32 as f64));
+        assert_eq!(report.f16_subnormals, 2);
+        assert_eq!(report.f16_overflows, 1);
+        assert!(!report.f16_is_safe());
+        assert_eq!(report.suggested_type(), DataType::Float32);
+
+        let reader = RecordBatchIterator::new(vec![], schema);
+        assert!(suggest_vector_precision(reader, "missing").is_err());
+    }
+
+    #[test]
+    fn test_validate_no_aliasing() {
+        let vector_type = |nullable| {
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, nullable)), 2)
+        };
+        let input = Arc::new(Schema::new(vec![
+            Field::new("a", vector_type(false), true),
+            Field::new("b", vector_type(false), true),
+        ]));
+        let vectors = |values: Vec<f32>| {
+            Arc::new(FixedSizeListArray::new(
+                Arc::new(Field::new("item", DataType::Float32, false)),
+                2,
+                Arc::new(Float32Array::from(values)),
+                None,
+            )) as Arc<dyn Array>
+        };
+        let shared = vectors(vec![1.0, 2.0]);
+        let aliased = RecordBatch::try_new(input.clone(), vec![shared.clone(), shared]).unwrap();
+        let err = validate_no_aliasing(&aliased).unwrap_err();
+        assert!(err.to_string().contains("Columns a and b share a buffer"));
+
+        // Only the item field differs, so the coercion reuses the values of each column.
+        let batch = RecordBatch::try_new(
+            input.clone(),
+            vec![vectors(vec![1.0, 2.0]), vectors(vec![3.0, 4.0])],
+        )
+        .unwrap();
+        let target = Arc::new(Schema::new(vec![
+            Field::new("a", vector_type(true), true),
+            Field::new("b", vector_type(true), true),
+        ]));
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], input);
+        let coerced = crate::data::sanitize::coerce_schema(reader, target)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(validate_no_aliasing(&coerced[0]).is_ok());
+    }
 }
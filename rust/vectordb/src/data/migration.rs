@@ -0,0 +1,181 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Migrate data through successive schema versions.
+
+use arrow_array::RecordBatchReader;
+use arrow_schema::SchemaRef;
+
+use super::sanitize::{coerce_schema_with_options, CoercionOptions};
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone)]
+struct MigrationStep {
+    from_version: u64,
+    to_version: u64,
+    options: CoercionOptions,
+    schema: SchemaRef,
+}
+
+/// An ordered registry of schema versions, and the coercions between them.
+///
+/// Each step coerces the data of one version to the target schema of the next version,
+/// so that old data can be migrated through successive versions.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaMigration {
+    steps: Vec<MigrationStep>,
+}
+
+impl SchemaMigration {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Register the migration from one version to another.
+    ///
+    /// # Arguments
+    ///
+    /// * `from_version` - The version of the input data.
+    /// * `to_version` - The version of the output data.
+    /// * `options` - The [CoercionOptions] used by this step.
+    /// * `schema` - The target schema of `to_version`.
+    pub fn step(
+        mut self,
+        from_version: u64,
+        to_version: u64,
+        options: CoercionOptions,
+        schema: SchemaRef,
+    ) -> Self {
+        self.steps.push(MigrationStep {
+            from_version,
+            to_version,
+            options,
+            schema,
+        });
+        self
+    }
+
+    /// Migrate the reader from `from_version` to `to_version`, composing the coercions
+    /// of every step in between.
+    pub fn apply_migrations(
+        &self,
+        reader: impl RecordBatchReader + Send + 'static,
+        from_version: u64,
+        to_version: u64,
+    ) -> Result<Box<dyn RecordBatchReader + Send>> {
+        let mut reader: Box<dyn RecordBatchReader + Send> = Box::new(reader);
+        let mut version = from_version;
+        // Each step can only be taken once, which also guards against cycles.
+        for _ in 0..self.steps.len() {
+            if version == to_version {
+                break;
+            }
+            let step = self
+                .steps
+                .iter()
+                .find(|s| s.from_version == version)
+                .ok_or_else(|| Error::Schema {
+                    message: format!(
+                        "No migration from schema version {} towards {}",
+                        version, to_version
+                    ),
+                })?;
+            reader = coerce_schema_with_options(reader, step.schema.clone(), step.options.clone())?;
+            version = step.to_version;
+        }
+        if version != to_version {
+            return Err(Error::Schema {
+                message: format!(
+                    "Unable to migrate from schema version {} to {}",
+                    from_version, to_version
+                ),
+            });
+        }
+        Ok(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow_array::{
+        cast::AsArray,
+        types::{Float16Type, Float32Type, Int64Type},
+        Int32Array, ListArray, RecordBatch, RecordBatchIterator, StringArray,
+    };
+    use arrow_schema::{DataType, Field, Schema};
+    use half::f16;
+
+    #[test]
+    fn test_apply_migrations() {
+        let v1 = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new(
+                "vec",
+                DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+                true,
+            ),
+            Field::new("legacy", DataType::Utf8, true),
+        ]));
+        let vector_type = |dt| DataType::FixedSizeList(Arc::new(Field::new("item", dt, true)), 2);
+        let v2 = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("vec", vector_type(DataType::Float32), true),
+        ]));
+        let v3 = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("vec", vector_type(DataType::Float16), true),
+        ]));
+        let migration = SchemaMigration::new()
+            .step(1, 2, CoercionOptions::default(), v2)
+            .step(2, 3, CoercionOptions::default(), v3.clone());
+
+        let batch = RecordBatch::try_new(
+            v1.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(ListArray::from_iter_primitive::<Float32Type, _, _>(vec![
+                    Some(vec![Some(1.0), Some(2.0)]),
+                    Some(vec![Some(3.0), Some(4.0)]),
+                ])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap();
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], v1.clone());
+        let stream = migration.apply_migrations(reader, 1, 3).unwrap();
+        assert_eq!(stream.schema(), v3);
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            batches[0].column(0).as_primitive::<Int64Type>().values(),
+            &[1, 2]
+        );
+        assert_eq!(
+            batches[0]
+                .column(1)
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float16Type>()
+                .values(),
+            &[1.0, 2.0, 3.0, 4.0].map(f16::from_f32)
+        );
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], v1);
+        assert!(migration.apply_migrations(reader, 1, 4).is_err());
+    }
+}
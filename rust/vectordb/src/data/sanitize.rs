@@ -12,23 +12,53 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{iter::repeat_with, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
+use arrow::buffer::OffsetBuffer;
+use arrow::compute::kernels::length::length;
+use arrow::compute::{concat, interleave, partition, sort_to_indices, take, SortOptions};
+use arrow::json::{writer::array_to_json_array, ReaderBuilder};
+use arrow::row::{RowConverter, SortField};
+use arrow::util::display::array_value_to_string;
 use arrow_array::{
     cast::AsArray,
-    types::{Float16Type, Float32Type, Float64Type, Int32Type, Int64Type},
-    Array, ArrowNumericType, FixedSizeListArray, PrimitiveArray, RecordBatch, RecordBatchIterator,
-    RecordBatchReader,
+    make_array, new_empty_array, new_null_array,
+    types::{
+        Date32Type, Date64Type, Decimal128Type, DecimalType, Float16Type, Float32Type, Float64Type,
+        Int16Type, Int32Type, Int64Type, Int8Type, TimestampMicrosecondType,
+        TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType, UInt16Type,
+        UInt32Type, UInt64Type, UInt8Type,
+    },
+    Array, ArrowNumericType, ArrowPrimitiveType, BooleanArray, FixedSizeBinaryArray,
+    FixedSizeListArray, Float32Array, Float64Array, GenericBinaryArray, GenericListArray,
+    GenericStringArray, Int64Array, LargeListArray, ListArray, MapArray, OffsetSizeTrait,
+    PrimitiveArray, RecordBatch, RecordBatchIterator, RecordBatchOptions, RecordBatchReader,
+    RunArray, StringArray, StructArray, UInt32Array, UInt8Array,
 };
-use arrow_cast::{can_cast_types, cast};
-use arrow_schema::{ArrowError, DataType, Field, Schema};
-use half::f16;
+use arrow_array::{temporal_conversions::as_datetime_with_timezone, timezone::Tz};
+use arrow_cast::{can_cast_types, cast, cast_with_options, parse::string_to_datetime, CastOptions};
+use arrow_schema::{ArrowError, DataType, Field, FieldRef, Schema, SchemaRef, TimeUnit};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{Offset, TimeZone};
+use half::{bf16, f16};
 use lance::arrow::{DataTypeExt, FixedSizeListArrayExt};
-use log::warn;
-use num_traits::cast::AsPrimitive;
+use lance::dataset::Dataset;
+use log::{info, warn};
+use num_traits::{cast::AsPrimitive, Float, Zero};
+use sha2::{Digest, Sha256};
 
 use super::inspect::infer_dimension;
-use crate::error::Result;
+use super::transform::{byteswap_array, vector_norms, VectorNorm};
+use crate::error::{Error, Result};
+#[cfg(feature = "parquet")]
+use crate::io::parquet::int96_to_timestamp;
 
 fn cast_array<I: ArrowNumericType, O: ArrowNumericType>(
     arr: &PrimitiveArray<I>,
@@ -36,9 +66,7 @@ fn cast_array<I: ArrowNumericType, O: ArrowNumericType>(
 where
     I::Native: AsPrimitive<O::Native>,
 {
-    Arc::new(PrimitiveArray::<O>::from_iter_values(
-        arr.values().iter().map(|v| (*v).as_()),
-    ))
+    Arc::new(arr.unary::<_, O>(|v| v.as_()))
 }
 
 fn cast_float_array<I: ArrowNumericType>(
@@ -60,225 +88,8493 @@ where
     }
 }
 
-fn coerce_array(
+/// Whether the conversion from `from` to `to` is a numeric conversion involving `Float16`.
+///
+/// These conversions always go through [cast_half_float_array] rather than the Arrow cast
+/// kernels, so the rounding behavior does not depend on the Arrow version. `Float16` to
+/// integer conversions are widened to `Float32` first, then narrowed like any float.
+fn is_half_float_cast(from: &DataType, to: &DataType) -> bool {
+    let numeric = |dt: &DataType| dt.is_floating() || dt.is_integer();
+    from != to
+        && ((from == &DataType::Float16 && numeric(to))
+            || (to == &DataType::Float16 && numeric(from)))
+}
+
+/// Convert a numeric array from or to `Float16`, rounding to the nearest representable value.
+fn cast_half_float_array(
+    array: &Arc<dyn Array>,
+    dt: &DataType,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    match (array.data_type(), dt) {
+        (DataType::Float16, dt) => cast_float_array(array.as_primitive::<Float16Type>(), dt),
+        (DataType::Float32, dt) => cast_float_array(array.as_primitive::<Float32Type>(), dt),
+        (DataType::Float64, dt) => cast_float_array(array.as_primitive::<Float64Type>(), dt),
+        (DataType::Int8, dt) => cast_float_array(array.as_primitive::<Int8Type>(), dt),
+        (DataType::Int16, dt) => cast_float_array(array.as_primitive::<Int16Type>(), dt),
+        (DataType::Int32, dt) => cast_float_array(array.as_primitive::<Int32Type>(), dt),
+        (DataType::Int64, dt) => cast_float_array(array.as_primitive::<Int64Type>(), dt),
+        (DataType::UInt8, dt) => cast_float_array(array.as_primitive::<UInt8Type>(), dt),
+        (DataType::UInt16, dt) => cast_float_array(array.as_primitive::<UInt16Type>(), dt),
+        (DataType::UInt32, dt) => cast_float_array(array.as_primitive::<UInt32Type>(), dt),
+        (DataType::UInt64, dt) => cast_float_array(array.as_primitive::<UInt64Type>(), dt),
+        (adt, dt) => Err(ArrowError::SchemaError(format!(
+            "Incompatible change field: unable to coerce {:?} to {:?}",
+            adt, dt
+        ))),
+    }
+}
+
+/// The adjacent `Float16` value towards positive (`up`) or negative infinity.
+fn next_half_float(h: f16, up: bool) -> f16 {
+    let bits = h.to_bits();
+    if bits & 0x7fff == 0 {
+        // The smallest subnormal of either sign.
+        return f16::from_bits(if up { 0x0001 } else { 0x8001 });
+    }
+    let positive = bits & 0x8000 == 0;
+    f16::from_bits(if positive == up { bits + 1 } else { bits - 1 })
+}
+
+/// A small, seedable generator of uniform values in `[0, 1)` (SplitMix64).
+struct UniformGenerator {
+    state: u64,
+}
+
+impl UniformGenerator {
+    fn next(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+/// Round `x` to one of its two adjacent `Float16` values, with a probability proportional
+/// to its proximity, so that the rounding is unbiased on average.
+fn stochastic_round(x: f64, generator: &mut UniformGenerator) -> f16 {
+    let r = generator.next();
+    let nearest = f16::from_f64(x);
+    if !x.is_finite() || nearest.is_infinite() || nearest.to_f64() == x {
+        return nearest;
+    }
+    let (lo, hi) = if nearest.to_f64() < x {
+        (nearest, next_half_float(nearest, true))
+    } else {
+        (next_half_float(nearest, false), nearest)
+    };
+    if r < (x - lo.to_f64()) / (hi.to_f64() - lo.to_f64()) {
+        hi
+    } else {
+        lo
+    }
+}
+
+/// Narrow a float array to `Float16` with stochastic rounding.
+///
+/// The values are rounded in order with a generator seeded by `seed`, so the same input
+/// and seed always produce the same output.
+fn stochastic_half_float_array<I: ArrowPrimitiveType>(
+    arr: &PrimitiveArray<I>,
+    seed: u64,
+) -> Arc<dyn Array>
+where
+    I::Native: AsPrimitive<f64>,
+{
+    let mut generator = UniformGenerator { state: seed };
+    let values = arr
+        .values()
+        .iter()
+        .map(|v| stochastic_round(v.as_(), &mut generator))
+        .collect::<Vec<_>>();
+    Arc::new(PrimitiveArray::<Float16Type>::new(
+        values.into(),
+        arr.nulls().cloned(),
+    ))
+}
+
+const SECONDS_PER_DAY: i64 = 86_400;
+const MILLISECONDS_PER_DAY: i64 = SECONDS_PER_DAY * 1_000;
+
+fn units_per_second(unit: &TimeUnit) -> i64 {
+    match unit {
+        TimeUnit::Second => 1,
+        TimeUnit::Millisecond => 1_000,
+        TimeUnit::Microsecond => 1_000_000,
+        TimeUnit::Nanosecond => 1_000_000_000,
+    }
+}
+
+/// Truncate timestamps to the day they fall on.
+fn timestamp_to_date(
     array: &Arc<dyn Array>,
+    unit: &TimeUnit,
+    tz: Option<&str>,
     field: &Field,
+    day_boundary: DayBoundary,
 ) -> std::result::Result<Arc<dyn Array>, ArrowError> {
-    if array.data_type() == field.data_type() {
-        return Ok(array.clone());
+    let dt = field.data_type();
+    let per_second = units_per_second(unit);
+    let tz = match (day_boundary, tz) {
+        (DayBoundary::SourceTimezone, Some(tz)) => Some(tz.parse::<Tz>()?),
+        _ => None,
+    };
+    let values = cast(array, &DataType::Int64)?;
+    let days = values
+        .as_primitive::<Int64Type>()
+        .try_unary::<_, Int64Type, ArrowError>(|v| {
+            let mut seconds = v.div_euclid(per_second);
+            if let Some(tz) = tz {
+                let local = as_datetime_with_timezone::<TimestampSecondType>(seconds, tz)
+                    .ok_or_else(|| {
+                        ArrowError::CastError(format!("Timestamp {} is out of range", v))
+                    })?;
+                seconds += local.offset().fix().local_minus_utc() as i64;
+            }
+            Ok(seconds.div_euclid(SECONDS_PER_DAY))
+        })?;
+    match dt {
+        DataType::Date32 => Ok(Arc::new(checked_temporal_array::<Date32Type>(
+            &days,
+            field,
+            |d| i32::try_from(d).ok(),
+        )?)),
+        DataType::Date64 => Ok(Arc::new(checked_temporal_array::<Date64Type>(
+            &days,
+            field,
+            |d| d.checked_mul(MILLISECONDS_PER_DAY),
+        )?)),
+        _ => Err(ArrowError::SchemaError(format!(
+            "Incompatible change field: unable to coerce {:?} to {:?}",
+            array.data_type(),
+            dt
+        ))),
     }
-    match (array.data_type(), field.data_type()) {
-        // Normal cast-able types.
-        (adt, dt) if can_cast_types(adt, dt) => cast(&array, dt),
-        // Casting between f16/f32/f64 can be lossy.
-        (adt, dt) if (adt.is_floating() || dt.is_floating()) => {
-            if adt.byte_width() > dt.byte_width() {
+}
+
+/// Expand dates to timestamps at UTC midnight.
+fn date_to_timestamp(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    unit: &TimeUnit,
+    tz: Option<Arc<str>>,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let per_day = SECONDS_PER_DAY * units_per_second(unit);
+    let days = match array.data_type() {
+        DataType::Date32 => array
+            .as_primitive::<Date32Type>()
+            .unary::<_, Int64Type>(|d| d as i64),
+        _ => array
+            .as_primitive::<Date64Type>()
+            .unary::<_, Int64Type>(|ms| ms.div_euclid(MILLISECONDS_PER_DAY)),
+    };
+    let values = checked_temporal_array::<Int64Type>(&days, field, |d| d.checked_mul(per_day))?;
+    Ok(timestamp_array(values, unit, tz))
+}
+
+/// Convert the days or timestamps of `values`, reporting the row of the first value that
+/// is out of range of the type of `field`.
+fn checked_temporal_array<T: ArrowPrimitiveType>(
+    values: &PrimitiveArray<Int64Type>,
+    field: &Field,
+    convert: impl Fn(i64) -> Option<T::Native>,
+) -> std::result::Result<PrimitiveArray<T>, ArrowError> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(row, value)| {
+            value
+                .map(|v| {
+                    convert(v).ok_or_else(|| {
+                        ArrowError::CastError(format!(
+                            "Coercing field {}: value at row {} is out of range for {:?}",
+                            field.name(),
+                            row,
+                            field.data_type()
+                        ))
+                    })
+                })
+                .transpose()
+        })
+        .collect()
+}
+
+/// Label raw `Int64` values as timestamps of the `unit`.
+fn timestamp_array(
+    values: PrimitiveArray<Int64Type>,
+    unit: &TimeUnit,
+    tz: Option<Arc<str>>,
+) -> Arc<dyn Array> {
+    match unit {
+        TimeUnit::Second => Arc::new(
+            values
+                .reinterpret_cast::<TimestampSecondType>()
+                .with_timezone_opt(tz),
+        ),
+        TimeUnit::Millisecond => Arc::new(
+            values
+                .reinterpret_cast::<TimestampMillisecondType>()
+                .with_timezone_opt(tz),
+        ),
+        TimeUnit::Microsecond => Arc::new(
+            values
+                .reinterpret_cast::<TimestampMicrosecondType>()
+                .with_timezone_opt(tz),
+        ),
+        TimeUnit::Nanosecond => Arc::new(
+            values
+                .reinterpret_cast::<TimestampNanosecondType>()
+                .with_timezone_opt(tz),
+        ),
+    }
+}
+
+/// Parse a timestamp with a strptime-like `format`, in the `timezone` unless the format
+/// has an offset. Date-only formats are parsed as midnight.
+fn parse_timestamp_with_format(
+    value: &str,
+    format: &str,
+    timezone: &Tz,
+) -> Option<chrono::DateTime<Tz>> {
+    if let Ok(datetime) = chrono::DateTime::parse_from_str(value, format) {
+        return Some(datetime.with_timezone(timezone));
+    }
+    let naive = chrono::NaiveDateTime::parse_from_str(value, format)
+        .or_else(|_| {
+            chrono::NaiveDate::parse_from_str(value, format)
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .ok()?;
+    timezone.from_local_datetime(&naive).single()
+}
+
+/// Parse timestamp strings, as ISO-8601 or with a strptime-like `format`.
+///
+/// Strings without an offset are in the timezone of the target field, or UTC.
+fn string_to_timestamp<O: OffsetSizeTrait>(
+    array: &GenericStringArray<O>,
+    field: &Field,
+    unit: &TimeUnit,
+    tz: Option<Arc<str>>,
+    format: Option<&str>,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let timezone: Tz = tz.as_deref().unwrap_or("+00:00").parse()?;
+    let values = array
+        .iter()
+        .enumerate()
+        .map(|(row, value)| {
+            let Some(value) = value else {
+                return Ok(None);
+            };
+            let datetime = match format {
+                None => string_to_datetime(&timezone, value).ok(),
+                Some(format) => parse_timestamp_with_format(value, format, &timezone),
+            }
+            .ok_or_else(|| {
+                ArrowError::CastError(format!(
+                    "Field {} has an unparseable timestamp {:?} at row {}",
+                    field.name(),
+                    value,
+                    row
+                ))
+            })?;
+            let timestamp = match unit {
+                TimeUnit::Second => Some(datetime.timestamp()),
+                TimeUnit::Millisecond => Some(datetime.timestamp_millis()),
+                TimeUnit::Microsecond => Some(datetime.timestamp_micros()),
+                TimeUnit::Nanosecond => datetime.timestamp_nanos_opt(),
+            };
+            timestamp.map(Some).ok_or_else(|| {
+                ArrowError::CastError(format!(
+                    "Field {} has an out of range timestamp {:?} at row {}",
+                    field.name(),
+                    value,
+                    row
+                ))
+            })
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    Ok(timestamp_array(PrimitiveArray::from(values), unit, tz))
+}
+
+/// Whether some values of the numeric type `from` can not be represented by the integer type `to`.
+///
+/// Floats are always narrowed when coerced to integers, e.g., for binary or int8 vectors.
+fn is_integer_narrowing(from: &DataType, to: &DataType) -> bool {
+    if from.is_floating() && to.is_integer() {
+        return true;
+    }
+    if !from.is_integer() || !to.is_integer() || from == to {
+        return false;
+    }
+    match (from.is_signed_integer(), to.is_signed_integer()) {
+        (true, false) => true,
+        (false, true) => to.primitive_width() <= from.primitive_width(),
+        _ => to.primitive_width() < from.primitive_width(),
+    }
+}
+
+fn narrow_integer_array(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    policy: NarrowingPolicy,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let dt = field.data_type();
+    match policy {
+        NarrowingPolicy::Allow => cast(array, dt),
+        NarrowingPolicy::WarnOnOverflow => {
+            let narrowed = cast(array, dt)?;
+            let overflows = narrowed.null_count().saturating_sub(array.null_count());
+            if overflows > 0 {
                 warn!(
-                    "Coercing field {} {:?} to {:?} might lose precision",
+                    "Coercing field {} {:?} to {:?}: {} values overflow",
                     field.name(),
-                    adt,
-                    dt
+                    array.data_type(),
+                    dt,
+                    overflows
                 );
             }
-            match adt {
-                DataType::Float16 => cast_float_array(array.as_primitive::<Float16Type>(), dt),
-                DataType::Float32 => cast_float_array(array.as_primitive::<Float32Type>(), dt),
-                DataType::Float64 => cast_float_array(array.as_primitive::<Float64Type>(), dt),
-                _ => unreachable!(),
-            }
+            Ok(narrowed)
         }
-        (adt, DataType::FixedSizeList(exp_field, exp_dim)) => match adt {
-            // Cast a float fixed size array with same dimension to the expected type.
-            DataType::FixedSizeList(_, dim) if dim == exp_dim => {
-                let actual_sub = array.as_fixed_size_list();
-                let values = coerce_array(actual_sub.values(), exp_field)?;
-                Ok(Arc::new(FixedSizeListArray::try_new_from_values(
-                    values.clone(),
-                    *dim,
-                )?) as Arc<dyn Array>)
-            }
-            DataType::List(_) | DataType::LargeList(_) => {
-                let Some(dim) = (match adt {
-                    DataType::List(_) => infer_dimension::<Int32Type>(array.as_list::<i32>())
-                        .map_err(|e| {
-                            ArrowError::SchemaError(format!(
-                                "failed to infer dimension from list: {}",
-                                e
-                            ))
-                        })?
-                        .map(|d| d as i64),
-                    DataType::LargeList(_) => infer_dimension::<Int64Type>(array.as_list::<i64>())
-                        .map_err(|e| {
-                            ArrowError::SchemaError(format!(
-                                "failed to infer dimension from large list: {}",
-                                e
-                            ))
-                        })?,
-                    _ => unreachable!(),
-                }) else {
-                    return Err(ArrowError::SchemaError(format!(
-                        "Incompatible coerce fixed size list: unable to coerce {:?} from {:?}",
-                        field,
-                        array.data_type()
-                    )));
-                };
+        NarrowingPolicy::Error => Err(ArrowError::SchemaError(format!(
+            "Incompatible change field {}: narrowing {:?} to {:?} is not allowed",
+            field.name(),
+            array.data_type(),
+            dt
+        ))),
+        NarrowingPolicy::CheckValues => cast_with_options(
+            array,
+            dt,
+            &CastOptions {
+                safe: false,
+                ..Default::default()
+            },
+        )
+        .map_err(|e| {
+            ArrowError::CastError(format!(
+                "Coercing field {} {:?} to {:?}: {}",
+                field.name(),
+                array.data_type(),
+                dt,
+                e
+            ))
+        }),
+    }
+}
 
-                if dim != *exp_dim as i64 {
-                    return Err(ArrowError::SchemaError(format!(
-                        "Incompatible coerce fixed size list: expected dimension {} but got {}",
-                        exp_dim, dim
-                    )));
-                }
+/// Make sure the components of the vectors fit the narrower integer type of `exp_field`,
+/// reporting the row and the component of the first one that overflows.
+fn check_vector_overflow(
+    vectors: &FixedSizeListArray,
+    field: &Field,
+    exp_field: &Field,
+) -> std::result::Result<(), ArrowError> {
+    let values = vectors.values();
+    let narrowed = cast(values, exp_field.data_type())?;
+    let dim = vectors.value_length() as usize;
+    let offset = vectors.value_offset(0) as usize;
+    // The components of null vectors are not data, whatever their values.
+    let overflow = (0..vectors.len())
+        .filter(|&row| vectors.is_valid(row))
+        .flat_map(|row| (0..dim).map(move |component| (row, component)))
+        .find(|&(row, component)| {
+            let index = offset + row * dim + component;
+            values.is_valid(index) && narrowed.is_null(index)
+        });
+    let Some((row, component)) = overflow else {
+        return Ok(());
+    };
+    Err(ArrowError::CastError(format!(
+        "Coercing field {} {:?} to {:?}: value {} at row {} component {} overflows",
+        field.name(),
+        values.data_type(),
+        exp_field.data_type(),
+        array_value_to_string(values, offset + row * dim + component)?,
+        row,
+        component
+    )))
+}
 
-                let values = coerce_array(array, exp_field)?;
-                Ok(Arc::new(FixedSizeListArray::try_new_from_values(
-                    values.clone(),
-                    *exp_dim,
-                )?) as Arc<dyn Array>)
-            }
-            _ => Err(ArrowError::SchemaError(format!(
-                "Incompatible coerce fixed size list: unable to coerce {:?} from {:?}",
-                field,
-                array.data_type()
-            )))?,
-        },
-        _ => Err(ArrowError::SchemaError(format!(
-            "Incompatible change field {}: unable to coerce {:?} to {:?}",
+/// Rescale a `Decimal128` array to another precision and scale.
+///
+/// Values that do not fit the target precision are rejected. Reducing the scale rounds
+/// the values half away from zero, and is governed by the narrowing `policy`.
+fn rescale_decimal(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    from_scale: i8,
+    (precision, scale): (u8, i8),
+    policy: NarrowingPolicy,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let downscale = scale < from_scale;
+    if downscale && policy == NarrowingPolicy::Error {
+        return Err(ArrowError::SchemaError(format!(
+            "Incompatible change field {}: reducing the scale of {:?} to {:?} is not allowed",
             field.name(),
             array.data_type(),
             field.data_type()
-        )))?,
+        )));
     }
-}
+    let factor = 10_i128
+        .checked_pow((scale as i32 - from_scale as i32).unsigned_abs())
+        .ok_or_else(|| {
+            ArrowError::CastError(format!(
+                "Coercing field {}: can not rescale {:?} to {:?}",
+                field.name(),
+                array.data_type(),
+                field.data_type()
+            ))
+        })?;
 
-fn coerce_schema_batch(
-    batch: RecordBatch,
-    schema: Arc<Schema>,
-) -> std::result::Result<RecordBatch, ArrowError> {
-    if batch.schema() == schema {
-        return Ok(batch);
-    }
-    let columns = schema
-        .fields()
+    let mut lossy = 0;
+    let rescaled = array
+        .as_primitive::<Decimal128Type>()
         .iter()
-        .map(|field| {
-            batch
-                .column_by_name(field.name())
-                .ok_or_else(|| {
-                    ArrowError::SchemaError(format!("Column {} not found", field.name()))
-                })
-                .and_then(|c| coerce_array(c, field))
+        .enumerate()
+        .map(|(row, value)| {
+            let Some(value) = value else {
+                return Ok(None);
+            };
+            let rescaled = if downscale {
+                let (quotient, remainder) = (value / factor, value % factor);
+                if remainder != 0 {
+                    if policy == NarrowingPolicy::CheckValues {
+                        return Err(ArrowError::CastError(format!(
+                            "Coercing field {}: value at row {} loses digits when rescaled to {:?}",
+                            field.name(),
+                            row,
+                            field.data_type()
+                        )));
+                    }
+                    lossy += 1;
+                }
+                // Round half away from zero, without doubling the remainder, which could
+                // overflow for the largest factors.
+                if remainder.unsigned_abs() >= factor.unsigned_abs() - remainder.unsigned_abs() {
+                    Some(quotient + value.signum())
+                } else {
+                    Some(quotient)
+                }
+            } else {
+                value.checked_mul(factor)
+            };
+            let rescaled = rescaled
+                .ok_or_else(|| ArrowError::ComputeError("i128 overflow".to_string()))
+                .and_then(|r| Decimal128Type::validate_decimal_precision(r, precision).map(|_| r))
+                .map_err(|e| {
+                    ArrowError::CastError(format!(
+                        "Coercing field {}: value at row {} overflows {:?}: {}",
+                        field.name(),
+                        row,
+                        field.data_type(),
+                        e
+                    ))
+                })?;
+            Ok(Some(rescaled))
         })
         .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
-    RecordBatch::try_new(schema, columns)
+    if lossy > 0 && policy == NarrowingPolicy::WarnOnOverflow {
+        warn!(
+            "Coercing field {} {:?} to {:?}: {} values lose digits",
+            field.name(),
+            array.data_type(),
+            field.data_type(),
+            lossy
+        );
+    }
+    Ok(Arc::new(
+        PrimitiveArray::<Decimal128Type>::from(rescaled)
+            .with_precision_and_scale(precision, scale)?,
+    ))
 }
 
-/// Coerce the reader (input data) to match the given [Schema].
+/// Run-length encode a plain column, once its values are coerced to the `values` type.
 ///
-pub fn coerce_schema(
-    reader: impl RecordBatchReader + Send + 'static,
-    schema: Arc<Schema>,
-) -> Result<Box<dyn RecordBatchReader + Send>> {
-    if reader.schema() == schema {
-        return Ok(Box::new(RecordBatchIterator::new(reader, schema)));
+/// Consecutive equal values, including nulls, form a run.
+fn run_end_encode(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    run_ends: &FieldRef,
+    values: &FieldRef,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let coerced = coerce_array(array, values, options)?;
+    let ranges = partition(std::slice::from_ref(&coerced))?.ranges();
+    let ends = Int64Array::from_iter_values(ranges.iter().map(|r| r.end as i64));
+    let starts = UInt32Array::from_iter_values(ranges.iter().map(|r| r.start as u32));
+    let run_values = take(&coerced, &starts, None)?;
+
+    // Fail, rather than wrap around, if the run ends overflow their type.
+    let cast_options = CastOptions {
+        safe: false,
+        ..Default::default()
+    };
+    let ends = cast_with_options(&ends, run_ends.data_type(), &cast_options).map_err(|_| {
+        ArrowError::CastError(format!(
+            "Coercing field {}: {} rows overflow the run ends of {:?}",
+            field.name(),
+            array.len(),
+            run_ends.data_type()
+        ))
+    })?;
+    let encoded: Arc<dyn Array> = match run_ends.data_type() {
+        DataType::Int16 => Arc::new(RunArray::try_new(
+            ends.as_primitive::<Int16Type>(),
+            &run_values,
+        )?),
+        DataType::Int32 => Arc::new(RunArray::try_new(
+            ends.as_primitive::<Int32Type>(),
+            &run_values,
+        )?),
+        DataType::Int64 => Arc::new(RunArray::try_new(
+            ends.as_primitive::<Int64Type>(),
+            &run_values,
+        )?),
+        dt => {
+            return Err(ArrowError::SchemaError(format!(
+                "Invalid run ends type of field {}: {:?}",
+                field.name(),
+                dt
+            )))
+        }
+    };
+    // Use the child fields of the target type, which may be named differently.
+    let data = encoded
+        .into_data()
+        .into_builder()
+        .data_type(field.data_type().clone())
+        .build()?;
+    Ok(make_array(data))
+}
+
+/// Join the elements of each list into a delimited string, skipping null elements.
+fn join_lists(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    delimiter: char,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let item = Arc::new(Field::new("item", DataType::Utf8, true));
+    let lists = cast(array, &DataType::List(item))?;
+    let joined = lists
+        .as_list::<i32>()
+        .iter()
+        .map(|list| {
+            list.map(|list| {
+                list.as_string::<i32>()
+                    .iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(&delimiter.to_string())
+            })
+        })
+        .collect::<StringArray>();
+    cast(&joined, field.data_type())
+}
+
+/// Split each string into a list at the delimiter; an empty string is an empty list.
+fn split_strings<O: OffsetSizeTrait>(
+    array: &Arc<dyn Array>,
+    exp_field: &FieldRef,
+    delimiter: char,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let strings = cast(array, &DataType::Utf8)?;
+    let strings = strings.as_string::<i32>();
+    let mut lengths = Vec::with_capacity(strings.len());
+    let mut values = vec![];
+    for s in strings.iter() {
+        let parts = match s {
+            Some(s) if !s.is_empty() => s.split(delimiter).collect::<Vec<_>>(),
+            _ => vec![],
+        };
+        lengths.push(parts.len());
+        values.extend(parts);
     }
-    let s = schema.clone();
-    let batches = reader
-        .zip(repeat_with(move || s.clone()))
-        .map(|(batch, s)| coerce_schema_batch(batch?, s));
-    Ok(Box::new(RecordBatchIterator::new(batches, schema)))
+    let values: Arc<dyn Array> = Arc::new(StringArray::from(values));
+    Ok(Arc::new(GenericListArray::<O>::try_new(
+        exp_field.clone(),
+        OffsetBuffer::from_lengths(lengths),
+        coerce_array(&values, exp_field, options)?,
+        strings.nulls().cloned(),
+    )?))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Decode a geohash into the `[lat, lon]` center of its cell.
+fn decode_geohash(geohash: &str) -> Option<[f64; 2]> {
+    const ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
 
-    use std::sync::Arc;
+    if geohash.is_empty() {
+        return None;
+    }
+    let (mut lat, mut lon) = ((-90.0, 90.0), (-180.0, 180.0));
+    // The bits alternate between longitude and latitude, starting with longitude.
+    let mut is_lon = true;
+    for c in geohash.bytes() {
+        let value = ALPHABET.iter().position(|&a| a == c.to_ascii_lowercase())?;
+        for bit in (0..5).rev() {
+            let range: &mut (f64, f64) = if is_lon { &mut lon } else { &mut lat };
+            let mid = (range.0 + range.1) / 2.0;
+            if value & (1 << bit) != 0 {
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            is_lon = !is_lon;
+        }
+    }
+    Some([(lat.0 + lat.1) / 2.0, (lon.0 + lon.1) / 2.0])
+}
 
-    use arrow_array::{
-        FixedSizeListArray, Float16Array, Float32Array, Float64Array, Int32Array, Int8Array,
-        RecordBatch, RecordBatchIterator, StringArray,
-    };
-    use arrow_schema::Field;
-    use half::f16;
-    use lance::arrow::FixedSizeListArrayExt;
+/// Decode geohash strings into `[lat, lon]` vectors. A null string is a null vector.
+fn geohash_to_vector<O: OffsetSizeTrait>(
+    strings: &GenericStringArray<O>,
+    field: &Field,
+    exp_field: &FieldRef,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let mut values = Vec::with_capacity(strings.len() * 2);
+    for (row, geohash) in strings.iter().enumerate() {
+        let location = match geohash {
+            Some(geohash) => decode_geohash(geohash).ok_or_else(|| {
+                ArrowError::CastError(format!(
+                    "Coercing field {}: invalid geohash {:?} at row {}",
+                    field.name(),
+                    geohash,
+                    row
+                ))
+            })?,
+            None => [0.0, 0.0],
+        };
+        values.extend(location);
+    }
+    let values: Arc<dyn Array> = Arc::new(Float64Array::from(values));
+    Ok(Arc::new(FixedSizeListArray::try_new(
+        exp_field.clone(),
+        2,
+        coerce_array(&values, exp_field, options)?,
+        strings.nulls().cloned(),
+    )?))
+}
 
-    #[test]
-    fn test_coerce_list_to_fixed_size_list() {
-        let schema = Arc::new(Schema::new(vec![
-            Field::new(
-                "fl",
-                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 64),
-                true,
-            ),
-            Field::new("s", DataType::Utf8, true),
-            Field::new("f", DataType::Float16, true),
-            Field::new("i", DataType::Int32, true),
-        ]));
+/// Decode base64 strings of `f32` bytes into vectors of dimension `dim`.
+/// A null string is a null vector.
+fn base64_to_vector<O: OffsetSizeTrait>(
+    strings: &GenericStringArray<O>,
+    field: &Field,
+    exp_field: &FieldRef,
+    dim: i32,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let byte_len = dim as usize * 4;
+    let mut values = Vec::with_capacity(strings.len() * dim as usize);
+    for (row, encoded) in strings.iter().enumerate() {
+        let Some(encoded) = encoded else {
+            values.resize(values.len() + dim as usize, 0.0);
+            continue;
+        };
+        let bytes = BASE64.decode(encoded).map_err(|e| {
+            ArrowError::CastError(format!(
+                "Coercing field {}: invalid base64 at row {}: {}",
+                field.name(),
+                row,
+                e
+            ))
+        })?;
+        if bytes.len() != byte_len {
+            return Err(ArrowError::CastError(format!(
+                "Coercing field {}: expected {} bytes for dimension {} but got {} at row {}",
+                field.name(),
+                byte_len,
+                dim,
+                bytes.len(),
+                row
+            )));
+        }
+        values.extend(
+            bytes
+                .chunks_exact(4)
+                .map(|b| options.endianness.f32_from_bytes([b[0], b[1], b[2], b[3]])),
+        );
+    }
+    let values: Arc<dyn Array> = Arc::new(Float32Array::from(values));
+    Ok(Arc::new(FixedSizeListArray::try_new(
+        exp_field.clone(),
+        dim,
+        coerce_array(&values, exp_field, options)?,
+        strings.nulls().cloned(),
+    )?))
+}
 
-        let batch = RecordBatch::try_new(
-            schema.clone(),
-            vec![
-                Arc::new(
-                    FixedSizeListArray::try_new_from_values(
-                        Float32Array::from_iter_values((0..256).map(|v| v as f32)),
-                        64,
-                    )
-                    .unwrap(),
-                ),
-                Arc::new(StringArray::from(vec![
+/// Decode 16-bit values as the bits of bfloat16 numbers, keeping nulls.
+fn bf16_bits_to_f32(bits: &PrimitiveArray<UInt16Type>) -> Float32Array {
+    bits.unary(|b| bf16::from_bits(b).to_f32())
+}
+
+/// Decode vectors of bfloat16 values, either as lists of `UInt16` bits or as fixed size
+/// binaries of bytes, into vectors of `exp_field`.
+fn bf16_to_vector(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    exp_field: &FieldRef,
+    dim: i32,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let bits = match array.data_type() {
+        DataType::FixedSizeList(_, _) => array
+            .as_fixed_size_list()
+            .values()
+            .as_primitive::<UInt16Type>()
+            .clone(),
+        DataType::FixedSizeBinary(_) => array
+            .as_fixed_size_binary()
+            .values()
+            .chunks_exact(2)
+            .map(|b| options.endianness.u16_from_bytes([b[0], b[1]]))
+            .collect(),
+        dt => {
+            return Err(ArrowError::CastError(format!(
+                "Coercing field {}: cannot decode {} as bfloat16 vectors",
+                field.name(),
+                dt
+            )))
+        }
+    };
+    let values: Arc<dyn Array> = Arc::new(bf16_bits_to_f32(&bits));
+    Ok(Arc::new(FixedSizeListArray::try_new(
+        exp_field.clone(),
+        dim,
+        coerce_array(&values, exp_field, options)?,
+        array.nulls().cloned(),
+    )?))
+}
+
+/// Wrap booleans into single-element vectors of 0 and 1, e.g., to handle flags as vectors.
+///
+/// A null boolean is a null vector.
+fn boolean_to_vector(
+    array: &Arc<dyn Array>,
+    exp_field: &FieldRef,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let flags = array.as_boolean();
+    let values = UInt8Array::from_iter_values(flags.values().iter().map(u8::from));
+    let values = cast(&values, exp_field.data_type())?;
+    Ok(Arc::new(FixedSizeListArray::try_new(
+        exp_field.clone(),
+        1,
+        values,
+        flags.nulls().cloned(),
+    )?))
+}
+
+/// Unwrap single-element vectors of 0 and 1 into booleans.
+///
+/// A null vector, or a null element, is a null boolean. Other values are rejected with
+/// their row index.
+fn vector_to_boolean(
+    array: &Arc<dyn Array>,
+    field: &Field,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let vectors = array.as_fixed_size_list();
+    let values = cast(vectors.values(), &DataType::Int64)?;
+    let values = values.as_primitive::<Int64Type>();
+    let flags = (0..vectors.len())
+        .map(|row| {
+            let i = vectors.value_offset(row) as usize;
+            if vectors.is_null(row) || values.is_null(i) {
+                return Ok(None);
+            }
+            match values.value(i) {
+                0 => Ok(Some(false)),
+                1 => Ok(Some(true)),
+                v => Err(ArrowError::CastError(format!(
+                    "Coercing field {}: value {} at row {} is not a boolean flag",
+                    field.name(),
+                    v,
+                    row
+                ))),
+            }
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    Ok(Arc::new(BooleanArray::from(flags)))
+}
+
+/// The number of nanoseconds in one `unit`.
+fn unit_nanos(unit: &TimeUnit) -> i64 {
+    match unit {
+        TimeUnit::Second => 1_000_000_000,
+        TimeUnit::Millisecond => 1_000_000,
+        TimeUnit::Microsecond => 1_000,
+        TimeUnit::Nanosecond => 1,
+    }
+}
+
+/// Convert durations, or timestamps as durations since the epoch, to numbers counting
+/// `unit`s, cast to the numeric type of `field`.
+fn time_to_number(
+    array: &Arc<dyn Array>,
+    from: &TimeUnit,
+    unit: &TimeUnit,
+    field: &Field,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let counts = cast(array, &DataType::Int64)?;
+    let counts = counts.as_primitive::<Int64Type>();
+    let (from_nanos, to_nanos) = (unit_nanos(from), unit_nanos(unit));
+
+    let numbers: Arc<dyn Array> = if field.data_type().is_floating() {
+        let scale = from_nanos as f64 / to_nanos as f64;
+        Arc::new(counts.unary::<_, Float64Type>(|v| v as f64 * scale))
+    } else if to_nanos >= from_nanos {
+        let divisor = to_nanos / from_nanos;
+        let truncated = counts.iter().flatten().filter(|v| v % divisor != 0).count();
+        if truncated > 0 {
+            warn!(
+                "Coercing field {} {:?} to {:?} in {:?}: {} values lose their sub-unit remainder",
+                field.name(),
+                array.data_type(),
+                field.data_type(),
+                unit,
+                truncated
+            );
+        }
+        Arc::new(counts.unary::<_, Int64Type>(|v| v / divisor))
+    } else {
+        let factor = from_nanos / to_nanos;
+        Arc::new(counts.try_unary::<_, Int64Type, _>(|v| {
+            v.checked_mul(factor).ok_or_else(|| {
+                ArrowError::CastError(format!(
+                    "Coercing field {}: {} {:?} overflows in {:?}",
+                    field.name(),
+                    v,
+                    from,
+                    unit
+                ))
+            })
+        })?)
+    };
+    cast_to_field(&numbers, field, options)
+}
+
+/// Format a decimal value with exactly `scale` fractional digits, e.g., `-0.05`.
+fn format_decimal(value: i128, scale: i8) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let digits = value.unsigned_abs().to_string();
+    if value == 0 && scale < 0 {
+        return digits;
+    }
+    if scale <= 0 {
+        return format!(
+            "{}{}{}",
+            sign,
+            digits,
+            "0".repeat(scale.unsigned_abs() as usize)
+        );
+    }
+    let scale = scale as usize;
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    format!("{}{}.{}", sign, integer, fraction)
+}
+
+/// Store decimals as their unscaled `Int64` values, rejecting the values that overflow.
+fn decimal_to_scaled_int(
+    array: &Arc<dyn Array>,
+    field: &Field,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let unscaled = array
+        .as_primitive::<Decimal128Type>()
+        .iter()
+        .enumerate()
+        .map(|(row, value)| {
+            value
+                .map(|v| {
+                    i64::try_from(v).map_err(|_| {
+                        ArrowError::CastError(format!(
+                            "Coercing field {}: unscaled value {} at row {} overflows Int64",
+                            field.name(),
+                            v,
+                            row
+                        ))
+                    })
+                })
+                .transpose()
+        })
+        .collect::<std::result::Result<Int64Array, ArrowError>>()?;
+    Ok(Arc::new(unscaled))
+}
+
+/// Reinterpret `Int64` values stored by [CoercionOptions::decimals_as_scaled_int] as
+/// decimals, using the scale recorded in the metadata of their `source` field.
+fn scaled_int_to_decimal(
+    column: Arc<dyn Array>,
+    source: &Field,
+    field: &Field,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let (Some(scale), DataType::Int64, DataType::Decimal128(_, _)) = (
+        source.metadata().get(DECIMAL_SCALE_METADATA_KEY),
+        column.data_type(),
+        field.data_type(),
+    ) else {
+        return Ok(column);
+    };
+    let scale = scale.parse::<i8>().map_err(|_| {
+        ArrowError::SchemaError(format!(
+            "Column {} has an invalid {} metadata: {}",
+            field.name(),
+            DECIMAL_SCALE_METADATA_KEY,
+            scale
+        ))
+    })?;
+    let decimals = column
+        .as_primitive::<Int64Type>()
+        .unary::<_, Decimal128Type>(|v| v as i128)
+        .with_precision_and_scale(Decimal128Type::MAX_PRECISION, scale)?;
+    Ok(Arc::new(decimals))
+}
+
+/// Render decimals as strings that preserve their scale.
+fn decimal_to_string(
+    array: &Arc<dyn Array>,
+    scale: i8,
+    dt: &DataType,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let strings = array
+        .as_primitive::<Decimal128Type>()
+        .iter()
+        .map(|v| v.map(|v| format_decimal(v, scale)))
+        .collect::<StringArray>();
+    cast(&strings, dt)
+}
+
+/// Assemble a vector from the children of a struct, e.g., `Struct{x, y, z}` points.
+///
+/// Structs whose children are exactly `x`, `y` (and `z`) are assembled in that order,
+/// other structs in the order of their children.
+fn struct_to_fixed_size_list(
+    array: &Arc<dyn Array>,
+    exp_field: &Arc<Field>,
+    dim: i32,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let struct_arr = array.as_struct();
+    if struct_arr.num_columns() != dim as usize {
+        return Err(ArrowError::SchemaError(format!(
+            "Incompatible coerce fixed size list: expected dimension {} but struct has {} fields",
+            dim,
+            struct_arr.num_columns()
+        )));
+    }
+    let names = struct_arr.column_names();
+    let mut sorted = names.clone();
+    sorted.sort();
+    let order = match sorted.as_slice() {
+        ["x", "y"] | ["x", "y", "z"] => sorted,
+        _ => names,
+    };
+    let children = order
+        .iter()
+        .map(|name| {
+            let child = struct_arr.column_by_name(name).unwrap();
+            coerce_array(child, exp_field, options)
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    let children = children.iter().map(|c| c.as_ref()).collect::<Vec<_>>();
+    let indices = (0..struct_arr.len())
+        .flat_map(|row| (0..children.len()).map(move |child| (child, row)))
+        .collect::<Vec<_>>();
+    let values = interleave(&children, &indices)?;
+    Ok(Arc::new(FixedSizeListArray::try_new(
+        exp_field.clone(),
+        dim,
+        values,
+        struct_arr.nulls().cloned(),
+    )?))
+}
+
+/// The values referenced by the offsets of a (possibly sliced) list array.
+fn list_values<O: OffsetSizeTrait>(list: &GenericListArray<O>) -> Arc<dyn Array> {
+    let offsets = list.value_offsets();
+    let start = offsets[0].as_usize();
+    let end = offsets[offsets.len() - 1].as_usize();
+    list.values().slice(start, end - start)
+}
+
+/// Clamp the values of a primitive array to `[min, max]`.
+fn clamp_primitive<T: ArrowPrimitiveType>(
+    arr: &PrimitiveArray<T>,
+    min: f64,
+    max: f64,
+) -> Arc<dyn Array>
+where
+    T::Native: AsPrimitive<f64>,
+    f64: AsPrimitive<T::Native>,
+{
+    Arc::new(arr.unary::<_, T>(|v| {
+        let f: f64 = v.as_();
+        if f < min {
+            min.as_()
+        } else if f > max {
+            max.as_()
+        } else {
+            v
+        }
+    }))
+}
+
+/// Clamp the values of a numeric column to the inclusive range `[min, max]`.
+fn clamp_values(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    (min, max): (f64, f64),
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    if min > max {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "Invalid clamp range for column {}: {} > {}",
+            field.name(),
+            min,
+            max
+        )));
+    }
+    match array.data_type() {
+        DataType::Int8 => Ok(clamp_primitive(array.as_primitive::<Int8Type>(), min, max)),
+        DataType::Int16 => Ok(clamp_primitive(array.as_primitive::<Int16Type>(), min, max)),
+        DataType::Int32 => Ok(clamp_primitive(array.as_primitive::<Int32Type>(), min, max)),
+        DataType::Int64 => Ok(clamp_primitive(array.as_primitive::<Int64Type>(), min, max)),
+        DataType::UInt8 => Ok(clamp_primitive(array.as_primitive::<UInt8Type>(), min, max)),
+        DataType::UInt16 => Ok(clamp_primitive(
+            array.as_primitive::<UInt16Type>(),
+            min,
+            max,
+        )),
+        DataType::UInt32 => Ok(clamp_primitive(
+            array.as_primitive::<UInt32Type>(),
+            min,
+            max,
+        )),
+        DataType::UInt64 => Ok(clamp_primitive(
+            array.as_primitive::<UInt64Type>(),
+            min,
+            max,
+        )),
+        DataType::Float16 => Ok(clamp_primitive(
+            array.as_primitive::<Float16Type>(),
+            min,
+            max,
+        )),
+        DataType::Float32 => Ok(clamp_primitive(
+            array.as_primitive::<Float32Type>(),
+            min,
+            max,
+        )),
+        DataType::Float64 => Ok(clamp_primitive(
+            array.as_primitive::<Float64Type>(),
+            min,
+            max,
+        )),
+        dt => Err(ArrowError::SchemaError(format!(
+            "Column {} is not numeric, can not clamp its values: {:?}",
+            field.name(),
+            dt
+        ))),
+    }
+}
+
+fn round_primitive<T: ArrowPrimitiveType>(arr: &PrimitiveArray<T>, decimals: u32) -> Arc<dyn Array>
+where
+    T::Native: AsPrimitive<f64>,
+    f64: AsPrimitive<T::Native>,
+{
+    let scale = 10f64.powi(decimals as i32);
+    Arc::new(arr.unary::<_, T>(|v| {
+        let scaled = v.as_() * scale;
+        // Values too large to scale have no decimals to round anyway.
+        if scaled.is_finite() {
+            (scaled.round_ties_even() / scale).as_()
+        } else {
+            v
+        }
+    }))
+}
+
+/// Round the values of a float column, or of the items of a float list column, to
+/// `decimals` decimal digits, rounding half to even.
+fn round_values(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    decimals: u32,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    match array.data_type() {
+        DataType::Float16 => Ok(round_primitive(
+            array.as_primitive::<Float16Type>(),
+            decimals,
+        )),
+        DataType::Float32 => Ok(round_primitive(
+            array.as_primitive::<Float32Type>(),
+            decimals,
+        )),
+        DataType::Float64 => Ok(round_primitive(
+            array.as_primitive::<Float64Type>(),
+            decimals,
+        )),
+        DataType::FixedSizeList(item, dim) => {
+            let list = array.as_fixed_size_list();
+            let values = round_values(list.values(), field, decimals)?;
+            Ok(Arc::new(FixedSizeListArray::try_new(
+                item.clone(),
+                *dim,
+                values,
+                list.nulls().cloned(),
+            )?))
+        }
+        DataType::List(item) => {
+            let list = array.as_list::<i32>();
+            let values = round_values(list.values(), field, decimals)?;
+            Ok(Arc::new(ListArray::try_new(
+                item.clone(),
+                list.offsets().clone(),
+                values,
+                list.nulls().cloned(),
+            )?))
+        }
+        DataType::LargeList(item) => {
+            let list = array.as_list::<i64>();
+            let values = round_values(list.values(), field, decimals)?;
+            Ok(Arc::new(LargeListArray::try_new(
+                item.clone(),
+                list.offsets().clone(),
+                values,
+                list.nulls().cloned(),
+            )?))
+        }
+        dt => Err(ArrowError::SchemaError(format!(
+            "Column {} is not a float column, can not round its values: {:?}",
+            field.name(),
+            dt
+        ))),
+    }
+}
+
+/// Reorder the components of each vector, the `i`-th output component being the
+/// `permutation[i]`-th input component.
+fn permute_vectors(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    permutation: &[usize],
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let DataType::FixedSizeList(item, dim) = array.data_type() else {
+        return Err(ArrowError::SchemaError(format!(
+            "Column {} is not a vector column, can not permute it: {:?}",
+            field.name(),
+            array.data_type()
+        )));
+    };
+    let mut seen = vec![false; permutation.len()];
+    let is_permutation = permutation.len() == *dim as usize
+        && permutation
+            .iter()
+            .all(|&i| i < seen.len() && !std::mem::replace(&mut seen[i], true));
+    if !is_permutation {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "Invalid permutation of the dimension {} column {}: {:?}",
+            dim,
+            field.name(),
+            permutation
+        )));
+    }
+
+    let vectors = array.as_fixed_size_list();
+    let indices = (0..vectors.len())
+        .flat_map(|row| {
+            let start = vectors.value_offset(row) as usize;
+            permutation.iter().map(move |i| (start + i) as u32)
+        })
+        .collect::<UInt32Array>();
+    Ok(Arc::new(FixedSizeListArray::try_new(
+        item.clone(),
+        *dim,
+        take(vectors.values(), &indices, None)?,
+        vectors.nulls().cloned(),
+    )?))
+}
+
+/// Field metadata key listing the meaning of the components of a vector column, as
+/// comma-separated names, e.g., `x,y,z`.
+pub const DIMENSION_ORDER_METADATA_KEY: &str = "lance:dim_order";
+
+fn dimension_order(field: &Field) -> Option<Vec<&str>> {
+    field
+        .metadata()
+        .get(DIMENSION_ORDER_METADATA_KEY)
+        .map(|order| order.split(',').map(str::trim).collect())
+}
+
+/// Whether the components of a `target` vector column are ordered differently from its
+/// `input` column.
+fn dimension_orders_differ(input: &Schema, target: &Schema) -> bool {
+    target.fields().iter().any(|field| {
+        let source = input.field_with_name(field.name());
+        match (
+            source.ok().and_then(dimension_order),
+            dimension_order(field),
+        ) {
+            (Some(from), Some(to)) => from != to,
+            _ => false,
+        }
+    })
+}
+
+/// Reorder the components of a vector column from the dimension order of its `source`
+/// field to the one of the target `field`, when both declare one.
+fn reorder_dimensions(
+    column: Arc<dyn Array>,
+    source: &Field,
+    field: &Field,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let (Some(from), Some(to)) = (dimension_order(source), dimension_order(field)) else {
+        return Ok(column);
+    };
+    if from == to {
+        return Ok(column);
+    }
+    let permutation = to
+        .iter()
+        .map(|name| from.iter().position(|n| n == name))
+        .collect::<Option<Vec<_>>>()
+        .filter(|p| from.len() == to.len() && p.iter().collect::<HashSet<_>>().len() == p.len())
+        .ok_or_else(|| {
+            ArrowError::SchemaError(format!(
+                "Column {} has incompatible dimension orders: [{}] and [{}]",
+                field.name(),
+                from.join(","),
+                to.join(",")
+            ))
+        })?;
+    permute_vectors(&column, field, &permutation)
+}
+
+/// Check that the vectors of a floating point vector column have a unit L2 norm, within
+/// `tolerance`, re-normalizing the others if `normalize` is set.
+///
+/// `first_row` is the index of the first row of the batch in the stream, to report errors.
+fn check_unit_vectors(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    tolerance: f32,
+    normalize: bool,
+    zero_norm: ZeroNormPolicy,
+    first_row: usize,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let DataType::FixedSizeList(item_field, dim) = array.data_type() else {
+        return Ok(array.clone());
+    };
+    if !item_field.data_type().is_floating() {
+        return Ok(array.clone());
+    }
+    let vectors = array.as_fixed_size_list();
+    let norms = vector_norms(vectors, VectorNorm::L2)?;
+    let deviating = norms
+        .iter()
+        .enumerate()
+        .filter_map(|(row, norm)| norm.map(|norm| (row, norm)))
+        // A NaN or infinite norm is never within the tolerance.
+        .filter(|(_, norm)| !norm.is_finite() || (norm - 1.0).abs() > tolerance)
+        .collect::<Vec<_>>();
+    if deviating.is_empty() {
+        return Ok(array.clone());
+    }
+    let rejected = if normalize {
+        // Vectors with a NaN or infinite norm can not be normalized either.
+        deviating.iter().find(|(_, norm)| {
+            !norm.is_finite() || (*norm == 0.0 && zero_norm == ZeroNormPolicy::Error)
+        })
+    } else {
+        deviating.first()
+    };
+    if let Some((row, norm)) = rejected {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "Vector at row {} of column {} has a L2 norm of {}, which is not within {} of 1",
+            first_row + row,
+            field.name(),
+            norm,
+            tolerance
+        )));
+    }
+
+    // Zero vectors have no direction, and are kept as is.
+    let rows = deviating
+        .iter()
+        .filter(|(_, norm)| *norm != 0.0)
+        .map(|(row, _)| *row)
+        .collect::<Vec<_>>();
+    let normalized = match item_field.data_type() {
+        DataType::Float16 => normalize_rows::<Float16Type>(vectors, &rows),
+        DataType::Float32 => normalize_rows::<Float32Type>(vectors, &rows),
+        _ => normalize_rows::<Float64Type>(vectors, &rows),
+    };
+    Ok(Arc::new(FixedSizeListArray::try_new(
+        item_field.clone(),
+        *dim,
+        normalized,
+        vectors.nulls().cloned(),
+    )?))
+}
+
+/// Divide the vectors at `rows` by their L2 norm, computed in their own float type, and
+/// keep the other vectors as is.
+fn normalize_rows<T: ArrowPrimitiveType>(
+    vectors: &FixedSizeListArray,
+    rows: &[usize],
+) -> Arc<dyn Array>
+where
+    T::Native: Float,
+{
+    let values = vectors.values().as_primitive::<T>();
+    let dim = vectors.value_length() as usize;
+    let mut normalized = values.values().to_vec();
+    for &row in rows {
+        let start = vectors.value_offset(row) as usize;
+        let vector = &mut normalized[start..start + dim];
+        let norm = vector
+            .iter()
+            .fold(T::Native::zero(), |acc, v| acc + *v * *v)
+            .sqrt();
+        if norm > T::Native::zero() && norm.is_finite() {
+            vector.iter_mut().for_each(|v| *v = *v / norm);
+        }
+    }
+    Arc::new(PrimitiveArray::<T>::new(
+        normalized.into(),
+        values.nulls().cloned(),
+    ))
+}
+
+/// Count the vectors of a floating point vector column that have a zero norm.
+fn count_zero_norms(array: &Arc<dyn Array>) -> std::result::Result<usize, ArrowError> {
+    match array.data_type() {
+        DataType::FixedSizeList(item, _) if item.data_type().is_floating() => {
+            let norms = vector_norms(array.as_fixed_size_list(), VectorNorm::L2)?;
+            Ok(norms.iter().filter(|norm| *norm == Some(0.0)).count())
+        }
+        _ => Ok(0),
+    }
+}
+
+/// Sort the values of each list ascending, with nulls last.
+fn sort_list<O: OffsetSizeTrait>(
+    list: &GenericListArray<O>,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let sort_options = SortOptions {
+        descending: false,
+        nulls_first: false,
+    };
+    let offsets = list.value_offsets();
+    let mut indices = Vec::with_capacity(offsets[offsets.len() - 1].as_usize());
+    for (row, start) in offsets[..list.len()].iter().enumerate() {
+        let start = start.as_usize();
+        let sorted = sort_to_indices(&list.value(row), Some(sort_options), None)?;
+        indices.extend(sorted.values().iter().map(|i| (start + *i as usize) as u32));
+    }
+    let values = take(list.values(), &UInt32Array::from(indices), None)?;
+    let offsets = OffsetBuffer::new(offsets.iter().map(|o| *o - offsets[0]).collect());
+    let item = match list.data_type() {
+        DataType::List(item) | DataType::LargeList(item) => item.clone(),
+        _ => unreachable!(),
+    };
+    Ok(Arc::new(GenericListArray::<O>::try_new(
+        item,
+        offsets,
+        values,
+        list.nulls().cloned(),
+    )?))
+}
+
+/// Sort the values of each list of a list column.
+fn sort_list_values(
+    array: &Arc<dyn Array>,
+    field: &Field,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    match array.data_type() {
+        DataType::List(_) => sort_list(array.as_list::<i32>()),
+        DataType::LargeList(_) => sort_list(array.as_list::<i64>()),
+        dt => Err(ArrowError::SchemaError(format!(
+            "Column {} is not a list, can not sort its values: {:?}",
+            field.name(),
+            dt
+        ))),
+    }
+}
+
+/// Map integer codes to their labels.
+fn int_to_label(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    labels: &HashMap<i64, String>,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let codes = cast(array, &DataType::Int64)?;
+    let labeled = codes
+        .as_primitive::<Int64Type>()
+        .iter()
+        .enumerate()
+        .map(|(row, code)| match code {
+            None => Ok(None),
+            Some(code) => match labels.get(&code) {
+                Some(label) => Ok(Some(label.as_str())),
+                None if options.coerce_errors_to_null => Ok(None),
+                None => Err(ArrowError::CastError(format!(
+                    "Field {} has no label for code {} at row {}",
+                    field.name(),
+                    code,
+                    row
+                ))),
+            },
+        })
+        .collect::<std::result::Result<StringArray, ArrowError>>()?;
+    cast(&labeled, field.data_type())
+}
+
+/// Map labels to their integer codes, assigning new codes to unknown labels with
+/// [UnknownLabel::AssignNew].
+fn label_to_int<O: OffsetSizeTrait>(
+    labels: &GenericStringArray<O>,
+    field: &Field,
+    codes: &SharedLabelCodes,
+    on_unknown: UnknownLabel,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let mut codes = codes.lock().unwrap();
+    let mut next_code = codes.values().max().map_or(Some(0), |c| c.checked_add(1));
+    let coded = labels
+        .iter()
+        .enumerate()
+        .map(|(row, label)| match label {
+            None => Ok(None),
+            Some(label) => match (codes.get(label), on_unknown) {
+                (Some(code), _) => Ok(Some(*code)),
+                (None, UnknownLabel::Null) => Ok(None),
+                (None, UnknownLabel::AssignNew) => {
+                    let code = next_code.ok_or_else(|| {
+                        ArrowError::CastError(format!(
+                            "Field {} has no code left for label {:?} at row {}",
+                            field.name(),
+                            label,
+                            row
+                        ))
+                    })?;
+                    codes.insert(label.to_string(), code);
+                    next_code = code.checked_add(1);
+                    Ok(Some(code))
+                }
+                (None, UnknownLabel::Error) => Err(ArrowError::CastError(format!(
+                    "Field {} has no code for label {:?} at row {}",
+                    field.name(),
+                    label,
+                    row
+                ))),
+            },
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    // A code that does not fit the field can not be told apart from a missing label.
+    cast_with_options(
+        &Int64Array::from(coded),
+        field.data_type(),
+        &CastOptions {
+            safe: false,
+            ..Default::default()
+        },
+    )
+    .map_err(|e| {
+        ArrowError::CastError(format!(
+            "Coercing the labels of field {} to {:?}: {}",
+            field.name(),
+            field.data_type(),
+            e
+        ))
+    })
+}
+
+/// Decode binary values as UTF-8 strings.
+fn binary_to_string<O: OffsetSizeTrait>(
+    array: &GenericBinaryArray<O>,
+    field: &Field,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let strings = array
+        .iter()
+        .enumerate()
+        .map(|(row, value)| match value.map(std::str::from_utf8) {
+            None => Ok(None),
+            Some(Ok(s)) => Ok(Some(s)),
+            Some(Err(_)) if options.coerce_errors_to_null => Ok(None),
+            Some(Err(e)) => Err(ArrowError::CastError(format!(
+                "Field {} has invalid UTF-8 at row {}: {}",
+                field.name(),
+                row,
+                e
+            ))),
+        })
+        .collect::<std::result::Result<StringArray, ArrowError>>()?;
+    cast(&strings, field.data_type())
+}
+
+/// Format each fixed size binary value as a lowercase hex string, e.g., a hash or an ID.
+fn fixed_size_binary_to_hex<O: OffsetSizeTrait>(
+    array: &FixedSizeBinaryArray,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let strings = array
+        .iter()
+        .map(|value| {
+            value.map(|bytes| {
+                bytes
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>()
+            })
+        })
+        .collect::<GenericStringArray<O>>();
+    Ok(Arc::new(strings))
+}
+
+/// Parse hex strings, in either case, into fixed size binary values of `width` bytes.
+fn hex_to_fixed_size_binary<O: OffsetSizeTrait>(
+    strings: &GenericStringArray<O>,
+    field: &Field,
+    width: i32,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let mut values = Vec::with_capacity(strings.len() * width as usize);
+    for (row, hex) in strings.iter().enumerate() {
+        let Some(hex) = hex else {
+            values.resize(values.len() + width as usize, 0);
+            continue;
+        };
+        if hex.len() != 2 * width as usize {
+            return Err(ArrowError::CastError(format!(
+                "Coercing field {}: expected {} hex characters but got {} at row {}",
+                field.name(),
+                2 * width,
+                hex.len(),
+                row
+            )));
+        }
+        for pair in hex.as_bytes().chunks_exact(2) {
+            let byte = std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .filter(|_| pair.iter().all(u8::is_ascii_hexdigit))
+                .ok_or_else(|| {
+                    ArrowError::CastError(format!(
+                        "Coercing field {}: invalid hex string {:?} at row {}",
+                        field.name(),
+                        hex,
+                        row
+                    ))
+                })?;
+            values.push(byte);
+        }
+    }
+    Ok(Arc::new(FixedSizeBinaryArray::try_new(
+        width,
+        values.into(),
+        strings.nulls().cloned(),
+    )?))
+}
+
+/// Serialize each struct value to a JSON object string, a null struct being null.
+fn struct_to_json<O: OffsetSizeTrait>(
+    array: &StructArray,
+    order: JsonKeyOrder,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let strings = struct_json_values(array, order)?
+        .into_iter()
+        .collect::<GenericStringArray<O>>();
+    Ok(Arc::new(strings))
+}
+
+/// Serialize each struct value to a JSON object, recursing into the struct children so
+/// that their keys follow the `order` too.
+fn struct_json_values(
+    array: &StructArray,
+    order: JsonKeyOrder,
+) -> std::result::Result<Vec<Option<String>>, ArrowError> {
+    let names = array.column_names();
+    let mut keys = (0..names.len()).collect::<Vec<_>>();
+    if order == JsonKeyOrder::Sorted {
+        keys.sort_by_key(|&i| names[i]);
+    }
+    let values = array
+        .columns()
+        .iter()
+        .map(|c| match c.as_struct_opt() {
+            Some(child) => Ok(struct_json_values(child, order)?
+                .into_iter()
+                .map(|value| value.unwrap_or_else(|| "null".to_string()))
+                .collect()),
+            None => Ok(array_to_json_array(c.as_ref())?
+                .iter()
+                .map(|value| value.to_string())
+                .collect()),
+        })
+        .collect::<std::result::Result<Vec<Vec<_>>, ArrowError>>()?;
+    Ok((0..array.len())
+        .map(|row| {
+            array.is_valid(row).then(|| {
+                let entries = keys
+                    .iter()
+                    .map(|&i| format!("{}:{}", serde_json::Value::from(names[i]), values[i][row]))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", entries.join(","))
+            })
+        })
+        .collect())
+}
+
+/// Parse JSON object strings into a struct.
+///
+/// Absent keys are null, and the values are coerced to the types of the struct fields.
+fn json_to_struct<O: OffsetSizeTrait>(
+    array: &GenericStringArray<O>,
+    field: &Field,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let mut rows = Vec::with_capacity(array.len());
+    for (row, value) in array.iter().enumerate() {
+        let parsed = match value.map(serde_json::from_str::<serde_json::Value>) {
+            None => serde_json::Value::Null,
+            Some(Ok(object @ serde_json::Value::Object(_))) => object,
+            Some(_) if options.coerce_errors_to_null => serde_json::Value::Null,
+            Some(Ok(other)) => {
+                return Err(ArrowError::CastError(format!(
+                    "Field {} expects a JSON object at row {}, got {}",
+                    field.name(),
+                    row,
+                    other
+                )))
+            }
+            Some(Err(e)) => {
+                return Err(ArrowError::CastError(format!(
+                    "Field {} has malformed JSON at row {}: {}",
+                    field.name(),
+                    row,
+                    e
+                )))
+            }
+        };
+        // The decoder only accepts objects at the top level, so wrap each row.
+        rows.push(serde_json::json!({ "value": parsed }));
+    }
+
+    let schema = Schema::new(vec![Field::new("value", field.data_type().clone(), true)]);
+    let mut decoder = ReaderBuilder::new(Arc::new(schema))
+        .with_coerce_primitive(true)
+        .build_decoder()?;
+    decoder.serialize(&rows)?;
+    let decoded = decoder.flush().map_err(|e| {
+        ArrowError::CastError(format!("Field {} can not decode JSON: {}", field.name(), e))
+    })?;
+    Ok(match decoded {
+        Some(batch) => batch.column(0).clone(),
+        None => new_empty_array(field.data_type()),
+    })
+}
+
+/// Whether coercing `from` to `to` may lose floating point precision.
+fn loses_precision(from: &DataType, to: &DataType) -> bool {
+    match (from, to) {
+        (DataType::FixedSizeList(from_field, _), DataType::FixedSizeList(to_field, _))
+        | (DataType::List(from_field), DataType::FixedSizeList(to_field, _))
+        | (DataType::LargeList(from_field), DataType::FixedSizeList(to_field, _)) => {
+            loses_precision(from_field.data_type(), to_field.data_type())
+        }
+        (from, to) if from.is_primitive() && to.is_primitive() => {
+            (from.is_floating() || to.is_floating()) && from.byte_width() > to.byte_width()
+        }
+        _ => false,
+    }
+}
+
+/// Check that a fixed size list has `len * dim` values, truncating the extra values
+/// with [CoercionOptions::repair_fixed_size_lists].
+fn check_fixed_size_list(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let DataType::FixedSizeList(item, dim) = array.data_type() else {
+        return Ok(array.clone());
+    };
+    let list = array.as_fixed_size_list();
+    let expected = list.len() * *dim as usize;
+    if list.values().len() == expected {
+        return Ok(array.clone());
+    }
+    if !options.repair_fixed_size_lists || list.values().len() < expected {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "Field {} is a malformed fixed size list: {} values for {} vectors of dimension {}",
+            field.name(),
+            list.values().len(),
+            list.len(),
+            dim
+        )));
+    }
+    Ok(Arc::new(FixedSizeListArray::try_new(
+        item.clone(),
+        *dim,
+        list.values().slice(0, expected),
+        list.nulls().cloned(),
+    )?))
+}
+
+pub(crate) fn coerce_array(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let array = &check_fixed_size_list(array, field, options)?;
+    let coerced = cast_to_field(array, field, options)?;
+    match (options.nan_policy, coerced.data_type()) {
+        (NanPolicy::NanToInnerNull, DataType::FixedSizeList(inner, _))
+            if inner.data_type().is_floating() =>
+        {
+            nan_to_inner_null(coerced.as_fixed_size_list())
+        }
+        (_, DataType::Float16) if options.canonicalize_f16_nan => canonicalize_f16_nans(&coerced),
+        (_, DataType::FixedSizeList(inner, _))
+            if options.canonicalize_f16_nan && inner.data_type() == &DataType::Float16 =>
+        {
+            canonicalize_f16_nans(&coerced)
+        }
+        (_, DataType::Map(_, _)) if options.map_duplicate_keys != MapDuplicateKeys::Keep => {
+            dedup_map_keys(coerced.as_map(), field, options.map_duplicate_keys)
+        }
+        _ => Ok(coerced),
+    }
+}
+
+/// Apply the duplicate key `policy` to each map of a map column.
+fn dedup_map_keys(
+    map: &MapArray,
+    field: &Field,
+    policy: MapDuplicateKeys,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let converter = RowConverter::new(vec![SortField::new(map.key_type().clone())])?;
+    let keys = converter.convert_columns(&[map.keys().clone()])?;
+
+    let mut indices = Vec::with_capacity(keys.num_rows());
+    let mut lengths = Vec::with_capacity(map.len());
+    for (row, window) in map.value_offsets().windows(2).enumerate() {
+        let entries = window[0] as usize..window[1] as usize;
+        if map.is_null(row) {
+            lengths.push(entries.len());
+            indices.extend(entries.map(|i| i as u32));
+            continue;
+        }
+        // Walk the entries backwards, so the last value of each key wins.
+        let mut seen = HashSet::new();
+        let mut kept = vec![];
+        for i in entries.rev() {
+            if seen.insert(keys.row(i)) {
+                kept.push(i as u32);
+            } else if policy == MapDuplicateKeys::Error {
+                return Err(ArrowError::CastError(format!(
+                    "Field {} has duplicate map key {} at row {}",
+                    field.name(),
+                    array_value_to_string(map.keys(), i)?,
+                    row
+                )));
+            }
+        }
+        lengths.push(kept.len());
+        indices.extend(kept.into_iter().rev());
+    }
+
+    let DataType::Map(entries_field, ordered) = map.data_type() else {
+        unreachable!()
+    };
+    let entries = take(map.entries(), &UInt32Array::from(indices), None)?;
+    Ok(Arc::new(MapArray::try_new(
+        entries_field.clone(),
+        OffsetBuffer::from_lengths(lengths),
+        entries.as_struct().clone(),
+        map.nulls().cloned(),
+        *ordered,
+    )?))
+}
+
+/// Rewrite every NaN of a `Float16` column, or of the items of a `Float16` vector column,
+/// to the canonical NaN bit pattern.
+fn canonicalize_f16_nans(
+    array: &Arc<dyn Array>,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    match array.data_type() {
+        DataType::Float16 => Ok(Arc::new(
+            array
+                .as_primitive::<Float16Type>()
+                .unary::<_, Float16Type>(|v| if v.is_nan() { f16::NAN } else { v }),
+        )),
+        DataType::FixedSizeList(field, dim) => {
+            let list = array.as_fixed_size_list();
+            Ok(Arc::new(FixedSizeListArray::try_new(
+                field.clone(),
+                *dim,
+                canonicalize_f16_nans(list.values())?,
+                list.nulls().cloned(),
+            )?))
+        }
+        _ => Ok(array.clone()),
+    }
+}
+
+/// Replace the NaN components of float vectors with null inner values.
+fn nan_to_inner_null(
+    array: &FixedSizeListArray,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    fn nan_to_null<T: ArrowPrimitiveType>(arr: &PrimitiveArray<T>) -> Arc<dyn Array>
+    where
+        T::Native: Float,
+    {
+        Arc::new(
+            arr.iter()
+                .map(|v| v.filter(|v| !v.is_nan()))
+                .collect::<PrimitiveArray<T>>(),
+        )
+    }
+    let values = array.values();
+    let values = match values.data_type() {
+        DataType::Float16 => nan_to_null(values.as_primitive::<Float16Type>()),
+        DataType::Float32 => nan_to_null(values.as_primitive::<Float32Type>()),
+        DataType::Float64 => nan_to_null(values.as_primitive::<Float64Type>()),
+        _ => values.clone(),
+    };
+    let DataType::FixedSizeList(field, dim) = array.data_type() else {
+        unreachable!()
+    };
+    Ok(Arc::new(FixedSizeListArray::try_new(
+        field.clone(),
+        *dim,
+        values,
+        array.nulls().cloned(),
+    )?))
+}
+
+/// Pad the lists with null items, or truncate them, to `dim` items.
+fn fit_lists_to_dimension<O: OffsetSizeTrait>(
+    list: &GenericListArray<O>,
+    exp_field: &FieldRef,
+    dim: i32,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let offsets = list.value_offsets();
+    let indices = (0..list.len())
+        .flat_map(|row| {
+            let start = offsets[row].as_usize();
+            let len = offsets[row + 1].as_usize() - start;
+            (0..dim as usize).map(move |i| (i < len).then_some((start + i) as u32))
+        })
+        .collect::<UInt32Array>();
+    let values = take(list.values(), &indices, None)?;
+    let values = coerce_array(&values, exp_field, options)?;
+    Ok(Arc::new(FixedSizeListArray::try_new(
+        exp_field.clone(),
+        dim,
+        values,
+        list.nulls().cloned(),
+    )?))
+}
+
+fn cast_to_field(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    if array.data_type() == field.data_type() {
+        return Ok(array.clone());
+    }
+    match (array.data_type(), field.data_type()) {
+        (adt, DataType::Utf8 | DataType::LargeUtf8)
+            if adt.is_integer() && options.int_labels.is_some() =>
+        {
+            int_to_label(array, field, options.int_labels.as_ref().unwrap(), options)
+        }
+        (DataType::Utf8, dt)
+            if dt.is_integer() && options.label_codes.contains_key(field.name()) =>
+        {
+            label_to_int(
+                array.as_string::<i32>(),
+                field,
+                &options.label_codes[field.name()],
+                options.on_unknown_label,
+            )
+        }
+        (DataType::LargeUtf8, dt)
+            if dt.is_integer() && options.label_codes.contains_key(field.name()) =>
+        {
+            label_to_int(
+                array.as_string::<i64>(),
+                field,
+                &options.label_codes[field.name()],
+                options.on_unknown_label,
+            )
+        }
+        (DataType::Utf8, DataType::Timestamp(unit, tz)) if options.parse_timestamps => {
+            string_to_timestamp(
+                array.as_string::<i32>(),
+                field,
+                unit,
+                tz.clone(),
+                options.timestamp_format.as_deref(),
+            )
+        }
+        (DataType::LargeUtf8, DataType::Timestamp(unit, tz)) if options.parse_timestamps => {
+            string_to_timestamp(
+                array.as_string::<i64>(),
+                field,
+                unit,
+                tz.clone(),
+                options.timestamp_format.as_deref(),
+            )
+        }
+        #[cfg(feature = "parquet")]
+        (DataType::FixedSizeBinary(12), DataType::Timestamp(_, tz)) => {
+            let timestamps: Arc<dyn Array> = Arc::new(int96_to_timestamp(
+                array.as_fixed_size_binary(),
+                tz.clone(),
+            )?);
+            cast_to_field(&timestamps, field, options)
+        }
+        (adt, DataType::RunEndEncoded(run_ends, values))
+            if !matches!(adt, DataType::RunEndEncoded(_, _)) =>
+        {
+            run_end_encode(array, field, run_ends, values, options)
+        }
+        (DataType::Utf8 | DataType::LargeUtf8, DataType::FixedSizeList(_, dim))
+            if options.base64_vector_dim.is_some_and(|d| d != *dim) =>
+        {
+            Err(ArrowError::SchemaError(format!(
+                "Coercing field {}: base64 vectors have dimension {} but the field has {}",
+                field.name(),
+                options.base64_vector_dim.unwrap(),
+                dim
+            )))
+        }
+        (DataType::Utf8, DataType::FixedSizeList(exp_field, dim))
+            if options.base64_vector_dim.is_some() =>
+        {
+            base64_to_vector(array.as_string::<i32>(), field, exp_field, *dim, options)
+        }
+        (DataType::LargeUtf8, DataType::FixedSizeList(exp_field, dim))
+            if options.base64_vector_dim.is_some() =>
+        {
+            base64_to_vector(array.as_string::<i64>(), field, exp_field, *dim, options)
+        }
+        (DataType::UInt16, dt) if dt.is_floating() && options.decode_bf16 => {
+            let values: Arc<dyn Array> =
+                Arc::new(bf16_bits_to_f32(array.as_primitive::<UInt16Type>()));
+            cast_to_field(&values, field, options)
+        }
+        (DataType::FixedSizeList(f, d), DataType::FixedSizeList(exp_field, dim))
+            if options.decode_bf16
+                && f.data_type() == &DataType::UInt16
+                && d == dim
+                && exp_field.data_type().is_floating() =>
+        {
+            bf16_to_vector(array, field, exp_field, *dim, options)
+        }
+        (DataType::FixedSizeBinary(width), DataType::FixedSizeList(exp_field, dim))
+            if options.decode_bf16 && exp_field.data_type().is_floating() =>
+        {
+            if *width != 2 * dim {
+                return Err(ArrowError::CastError(format!(
+                    "Coercing field {}: {} bytes cannot hold a bfloat16 vector of dimension {}",
+                    field.name(),
+                    width,
+                    dim
+                )));
+            }
+            bf16_to_vector(array, field, exp_field, *dim, options)
+        }
+        (DataType::Utf8, DataType::FixedSizeList(exp_field, 2)) if options.decode_geohash => {
+            geohash_to_vector(array.as_string::<i32>(), field, exp_field, options)
+        }
+        (DataType::LargeUtf8, DataType::FixedSizeList(exp_field, 2)) if options.decode_geohash => {
+            geohash_to_vector(array.as_string::<i64>(), field, exp_field, options)
+        }
+        (DataType::List(_) | DataType::LargeList(_), DataType::Utf8 | DataType::LargeUtf8)
+            if options.list_delimiter.is_some() =>
+        {
+            join_lists(array, field, options.list_delimiter.unwrap())
+        }
+        (DataType::Utf8 | DataType::LargeUtf8, DataType::List(exp_field))
+            if options.list_delimiter.is_some() =>
+        {
+            split_strings::<i32>(array, exp_field, options.list_delimiter.unwrap(), options)
+        }
+        (DataType::Utf8 | DataType::LargeUtf8, DataType::LargeList(exp_field))
+            if options.list_delimiter.is_some() =>
+        {
+            split_strings::<i64>(array, exp_field, options.list_delimiter.unwrap(), options)
+        }
+        (DataType::Boolean, DataType::FixedSizeList(exp_field, 1))
+            if exp_field.data_type().is_integer() =>
+        {
+            boolean_to_vector(array, exp_field)
+        }
+        (DataType::FixedSizeList(item, 1), DataType::Boolean) if item.data_type().is_integer() => {
+            vector_to_boolean(array, field)
+        }
+        (DataType::Duration(from), dt) if dt.is_numeric() && options.duration_unit.is_some() => {
+            time_to_number(
+                array,
+                from,
+                options.duration_unit.as_ref().unwrap(),
+                field,
+                options,
+            )
+        }
+        (DataType::Timestamp(from, _), dt) if dt.is_numeric() && options.epoch_unit.is_some() => {
+            time_to_number(
+                array,
+                from,
+                options.epoch_unit.as_ref().unwrap(),
+                field,
+                options,
+            )
+        }
+        (DataType::FixedSizeBinary(_), DataType::Utf8) => {
+            fixed_size_binary_to_hex::<i32>(array.as_fixed_size_binary())
+        }
+        (DataType::FixedSizeBinary(_), DataType::LargeUtf8) => {
+            fixed_size_binary_to_hex::<i64>(array.as_fixed_size_binary())
+        }
+        (DataType::Utf8, DataType::FixedSizeBinary(width)) => {
+            hex_to_fixed_size_binary(array.as_string::<i32>(), field, *width)
+        }
+        (DataType::LargeUtf8, DataType::FixedSizeBinary(width)) => {
+            hex_to_fixed_size_binary(array.as_string::<i64>(), field, *width)
+        }
+        (DataType::Struct(_), DataType::Utf8) => {
+            struct_to_json::<i32>(array.as_struct(), options.json_key_order)
+        }
+        (DataType::Struct(_), DataType::LargeUtf8) => {
+            struct_to_json::<i64>(array.as_struct(), options.json_key_order)
+        }
+        (DataType::Utf8, DataType::Struct(_)) if options.parse_json_structs => {
+            json_to_struct(array.as_string::<i32>(), field, options)
+        }
+        (DataType::LargeUtf8, DataType::Struct(_)) if options.parse_json_structs => {
+            json_to_struct(array.as_string::<i64>(), field, options)
+        }
+        (DataType::Binary, DataType::Utf8 | DataType::LargeUtf8) => {
+            binary_to_string(array.as_binary::<i32>(), field, options)
+        }
+        (DataType::LargeBinary, DataType::Utf8 | DataType::LargeUtf8) => {
+            binary_to_string(array.as_binary::<i64>(), field, options)
+        }
+        // Any numeric conversion involving f16 takes the explicit path.
+        (adt, dt) if is_half_float_cast(adt, dt) => {
+            if loses_precision(adt, dt) {
+                warn!(
+                    "Coercing field {} {:?} to {:?} might lose precision",
+                    field.name(),
+                    adt,
+                    dt
+                );
+            }
+            match (adt, options.f16_rounding) {
+                (DataType::Float16, _) if dt.is_integer() => {
+                    let widened = cast_half_float_array(array, &DataType::Float32)?;
+                    narrow_integer_array(&widened, field, options.integer_narrowing)
+                }
+                (DataType::Float32, F16Rounding::Stochastic { seed }) => Ok(
+                    stochastic_half_float_array(array.as_primitive::<Float32Type>(), seed),
+                ),
+                (DataType::Float64, F16Rounding::Stochastic { seed }) => Ok(
+                    stochastic_half_float_array(array.as_primitive::<Float64Type>(), seed),
+                ),
+                _ => cast_half_float_array(array, dt),
+            }
+        }
+        // Cast a fixed size array with same dimension to the expected type. The values are
+        // coerced recursively, so nested (matrix-valued) lists have every level validated.
+        (DataType::FixedSizeList(_, dim), DataType::FixedSizeList(exp_field, exp_dim)) => {
+            if dim != exp_dim {
+                return Err(ArrowError::SchemaError(format!(
+                    "Incompatible coerce fixed size list: expected dimension {} but got {}",
+                    exp_dim, dim
+                )));
+            }
+            let actual_sub = array.as_fixed_size_list();
+            let checked;
+            let options = if options.integer_narrowing == NarrowingPolicy::CheckValues
+                && is_integer_narrowing(&actual_sub.value_type(), exp_field.data_type())
+            {
+                check_vector_overflow(actual_sub, field, exp_field)?;
+                // Any component left that overflows belongs to a null vector.
+                checked = options.clone().integer_narrowing(NarrowingPolicy::Allow);
+                &checked
+            } else {
+                options
+            };
+            let values = coerce_array(actual_sub.values(), exp_field, options)?;
+            // Only the item field differs: rewrap the values, without copying them.
+            if values.data_type() == exp_field.data_type()
+                && (exp_field.is_nullable() || values.null_count() == 0)
+            {
+                return Ok(Arc::new(FixedSizeListArray::try_new(
+                    exp_field.clone(),
+                    *dim,
+                    values,
+                    actual_sub.nulls().cloned(),
+                )?));
+            }
+            Ok(Arc::new(FixedSizeListArray::try_new_from_values(
+                values.clone(),
+                *dim,
+            )?) as Arc<dyn Array>)
+        }
+        (DataType::Timestamp(unit, tz), DataType::Date32 | DataType::Date64) => {
+            timestamp_to_date(array, unit, tz.as_deref(), field, options.day_boundary)
+        }
+        (DataType::Date32 | DataType::Date64, DataType::Timestamp(unit, tz)) => {
+            date_to_timestamp(array, field, unit, tz.clone())
+        }
+        // Export a fixed size list as a variable-length list, with uniform offsets.
+        (DataType::FixedSizeList(_, dim), DataType::LargeList(exp_field)) => {
+            let actual_sub = array.as_fixed_size_list();
+            let values = coerce_array(actual_sub.values(), exp_field, options)?;
+            let offsets = OffsetBuffer::from_lengths(vec![*dim as usize; array.len()]);
+            Ok(Arc::new(LargeListArray::try_new(
+                exp_field.clone(),
+                offsets,
+                values,
+                actual_sub.nulls().cloned(),
+            )?))
+        }
+        (adt, dt) if is_integer_narrowing(adt, dt) => {
+            narrow_integer_array(array, field, options.integer_narrowing)
+        }
+        // Coerce the values of variable-length lists recursively, e.g. to re-key dictionaries.
+        (DataType::List(_), DataType::List(exp_field)) => {
+            let list = array.as_list::<i32>();
+            let values = coerce_array(list.values(), exp_field, options)?;
+            Ok(Arc::new(ListArray::try_new(
+                exp_field.clone(),
+                list.offsets().clone(),
+                values,
+                list.nulls().cloned(),
+            )?))
+        }
+        (DataType::LargeList(_), DataType::LargeList(exp_field)) => {
+            let list = array.as_list::<i64>();
+            let values = coerce_array(list.values(), exp_field, options)?;
+            Ok(Arc::new(LargeListArray::try_new(
+                exp_field.clone(),
+                list.offsets().clone(),
+                values,
+                list.nulls().cloned(),
+            )?))
+        }
+        (DataType::Decimal128(_, _), DataType::Int64) if options.decimals_as_scaled_int => {
+            decimal_to_scaled_int(array, field)
+        }
+        (DataType::Decimal128(_, scale), DataType::Utf8 | DataType::LargeUtf8) => {
+            decimal_to_string(array, *scale, field.data_type())
+        }
+        (DataType::Decimal128(_, from_scale), DataType::Decimal128(precision, scale)) => {
+            rescale_decimal(
+                array,
+                field,
+                *from_scale,
+                (*precision, *scale),
+                options.integer_narrowing,
+            )
+        }
+        // The entries of maps are matched by position, as producers name them differently.
+        (DataType::Map(_, _), DataType::Map(exp_entries, ordered)) => {
+            let map = array.as_map();
+            let DataType::Struct(exp_fields) = exp_entries.data_type() else {
+                return Err(ArrowError::SchemaError(format!(
+                    "Field {} has invalid map entries {:?}",
+                    field.name(),
+                    exp_entries
+                )));
+            };
+            let columns = map
+                .entries()
+                .columns()
+                .iter()
+                .zip(exp_fields.iter())
+                .map(|(c, f)| coerce_array(c, f, options))
+                .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+            let entries =
+                StructArray::try_new(exp_fields.clone(), columns, map.entries().nulls().cloned())?;
+            Ok(Arc::new(MapArray::try_new(
+                exp_entries.clone(),
+                map.offsets().clone(),
+                entries,
+                map.nulls().cloned(),
+                *ordered,
+            )?))
+        }
+        // Match the children of structs by name, so their order does not matter.
+        (DataType::Struct(_), DataType::Struct(exp_fields)) => {
+            let actual = array.as_struct();
+            let columns = exp_fields
+                .iter()
+                .map(|exp_field| {
+                    let child = actual.column_by_name(exp_field.name()).ok_or_else(|| {
+                        ArrowError::SchemaError(format!(
+                            "Field {} is missing struct child {}",
+                            field.name(),
+                            exp_field.name()
+                        ))
+                    })?;
+                    coerce_array(child, exp_field, options)
+                })
+                .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+            Ok(Arc::new(StructArray::try_new(
+                exp_fields.clone(),
+                columns,
+                actual.nulls().cloned(),
+            )?))
+        }
+        // Normal cast-able types.
+        (adt, dt) if can_cast_types(adt, dt) => cast(&array, dt),
+        // Casting between f16/f32/f64 can be lossy.
+        (adt, dt) if (adt.is_floating() || dt.is_floating()) => {
+            if loses_precision(adt, dt) {
+                warn!(
+                    "Coercing field {} {:?} to {:?} might lose precision",
+                    field.name(),
+                    adt,
+                    dt
+                );
+            }
+            match adt {
+                DataType::Float16 => cast_float_array(array.as_primitive::<Float16Type>(), dt),
+                DataType::Float32 => cast_float_array(array.as_primitive::<Float32Type>(), dt),
+                DataType::Float64 => cast_float_array(array.as_primitive::<Float64Type>(), dt),
+                _ => unreachable!(),
+            }
+        }
+        (adt, DataType::FixedSizeList(exp_field, exp_dim)) => match adt {
+            DataType::Struct(_) => struct_to_fixed_size_list(array, exp_field, *exp_dim, options),
+            // The original lengths are kept by the valid length column.
+            DataType::List(_) if options.valid_length_column.is_some() => {
+                fit_lists_to_dimension(array.as_list::<i32>(), exp_field, *exp_dim, options)
+            }
+            DataType::LargeList(_) if options.valid_length_column.is_some() => {
+                fit_lists_to_dimension(array.as_list::<i64>(), exp_field, *exp_dim, options)
+            }
+            DataType::List(_) | DataType::LargeList(_) => {
+                let Some(dim) = (match adt {
+                    DataType::List(_) => infer_dimension::<Int32Type>(array.as_list::<i32>())
+                        .map_err(|e| {
+                            ArrowError::SchemaError(format!(
+                                "failed to infer dimension from list: {}",
+                                e
+                            ))
+                        })?
+                        .map(|d| d as i64),
+                    DataType::LargeList(_) => infer_dimension::<Int64Type>(array.as_list::<i64>())
+                        .map_err(|e| {
+                            ArrowError::SchemaError(format!(
+                                "failed to infer dimension from large list: {}",
+                                e
+                            ))
+                        })?,
+                    _ => unreachable!(),
+                }) else {
+                    return Err(ArrowError::SchemaError(format!(
+                        "Incompatible coerce fixed size list: unable to coerce {:?} from {:?}",
+                        field,
+                        array.data_type()
+                    )));
+                };
+
+                if dim != *exp_dim as i64 {
+                    return Err(ArrowError::SchemaError(format!(
+                        "Incompatible coerce fixed size list: expected dimension {} but got {}",
+                        exp_dim, dim
+                    )));
+                }
+
+                let values = match adt {
+                    DataType::List(_) => list_values(array.as_list::<i32>()),
+                    _ => list_values(array.as_list::<i64>()),
+                };
+                let values = coerce_array(&values, exp_field, options)?;
+                Ok(Arc::new(FixedSizeListArray::try_new_from_values(
+                    values.clone(),
+                    *exp_dim,
+                )?) as Arc<dyn Array>)
+            }
+            _ => Err(ArrowError::SchemaError(format!(
+                "Incompatible coerce fixed size list: unable to coerce {:?} from {:?}",
+                field,
+                array.data_type()
+            )))?,
+        },
+        _ => Err(ArrowError::SchemaError(format!(
+            "Incompatible change field {}: unable to coerce {:?} to {:?}",
+            field.name(),
+            array.data_type(),
+            field.data_type()
+        )))?,
+    }
+}
+
+/// How to handle duplicate keys within a map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MapDuplicateKeys {
+    /// Keep every entry.
+    #[default]
+    Keep,
+    /// Keep only the last entry of each key.
+    LastWins,
+    /// Fail with the row and the duplicate key.
+    Error,
+}
+
+/// How to redact the values of a column.
+#[derive(Debug, Clone)]
+pub enum RedactPolicy {
+    /// Replace every value with null.
+    Null,
+    /// Replace every non-null value with the hex SHA-256 digest of its string form, a
+    /// `Utf8` value that is stable across runs.
+    Hash,
+    /// Replace every value with a constant, held by a single-element array.
+    Constant(Arc<dyn Array>),
+}
+
+/// How to handle NaN components of float vectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NanPolicy {
+    /// Keep the NaN components.
+    #[default]
+    Keep,
+    /// Replace the NaN components with null inner values, keeping the rows.
+    NanToInnerNull,
+}
+
+/// How floats are rounded when they are narrowed to `Float16`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum F16Rounding {
+    /// Round to the nearest representable value.
+    #[default]
+    RoundToNearest,
+    /// Round up or down at random, with a probability proportional to the proximity of
+    /// each neighbour, so repeated quantization is not biased. Reproducible given the seed.
+    Stochastic { seed: u64 },
+}
+
+/// A Unicode normalization form.
+#[cfg(feature = "unicode")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NfForm {
+    /// Canonical decomposition, followed by canonical composition.
+    Nfc,
+    /// Canonical decomposition.
+    Nfd,
+    /// Compatibility decomposition, followed by canonical composition.
+    Nfkc,
+    /// Compatibility decomposition.
+    Nfkd,
+}
+
+/// Which timezone decides the day boundary when truncating timestamps to dates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DayBoundary {
+    /// Use the timezone of the source timestamp column, if it has one.
+    #[default]
+    SourceTimezone,
+    /// Always use UTC.
+    Utc,
+}
+
+/// How to handle narrowing integer coercions, e.g. `Int64` to `Int32` or `Float32` to `UInt8`.
+///
+/// The policy also governs reducing the scale of decimals, where the values that lose
+/// digits are rounded rather than nulled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NarrowingPolicy {
+    /// Allow the narrowing. Values that overflow become null.
+    #[default]
+    Allow,
+    /// Allow the narrowing, but warn if any value overflows.
+    WarnOnOverflow,
+    /// Reject the narrowing regardless of the values.
+    Error,
+    /// Allow the narrowing, but fail if any value overflows. The error names the row and
+    /// the component of an overflowing vector component.
+    CheckValues,
+}
+
+/// What to do with the zero vectors found by [CoercionOptions::normalize_unit_vectors],
+/// which have no direction and can not be normalized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ZeroNormPolicy {
+    /// Fail the batch.
+    #[default]
+    Error,
+    /// Keep the zero vectors, and count them in [ColumnReport::zero_norm_vectors].
+    Report,
+}
+
+/// What to do with the strings longer than [CoercionOptions::max_string_bytes].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OversizedString {
+    /// Fail the batch.
+    #[default]
+    Error,
+    /// Truncate the string, without splitting a multi-byte character.
+    Truncate,
+}
+
+/// How to handle coerced columns that end up with different lengths within a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LengthMismatch {
+    /// Fail with [Error::ColumnLengthMismatch].
+    #[default]
+    Error,
+    /// Truncate all columns to the length of the shortest column.
+    Truncate,
+    /// Pad all columns with nulls to the length of the longest column.
+    Pad,
+}
+
+/// How to map labels that have no integer code, see [CoercionOptions::label_to_int].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownLabel {
+    /// Fail the coercion.
+    #[default]
+    Error,
+    /// Replace the label with null.
+    Null,
+    /// Assign the next unused code to the label, growing the vocabulary.
+    AssignNew,
+}
+
+/// The order of the keys of the JSON objects serialized from structs, see
+/// [CoercionOptions::json_key_order].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonKeyOrder {
+    /// The order of the struct fields.
+    #[default]
+    Schema,
+    /// The lexicographic order of the field names.
+    Sorted,
+}
+
+/// The byte order of binary values reinterpreted as numbers, see
+/// [CoercionOptions::endianness].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Endianness {
+    /// The byte order of the machine.
+    #[default]
+    Native,
+    /// The least significant byte first.
+    Little,
+    /// The most significant byte first.
+    Big,
+}
+
+impl Endianness {
+    fn f32_from_bytes(self, bytes: [u8; 4]) -> f32 {
+        match self {
+            Self::Native => f32::from_ne_bytes(bytes),
+            Self::Little => f32::from_le_bytes(bytes),
+            Self::Big => f32::from_be_bytes(bytes),
+        }
+    }
+
+    fn u16_from_bytes(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            Self::Native => u16::from_ne_bytes(bytes),
+            Self::Little => u16::from_le_bytes(bytes),
+            Self::Big => u16::from_be_bytes(bytes),
+        }
+    }
+}
+
+/// A per-column transform applied after the values are cast to the target type, see
+/// [CoercionOptions::pipeline_order].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PipelineStep {
+    /// Clamp the values, see [CoercionOptions::clamp].
+    Clamp,
+    /// Round the values, see [CoercionOptions::round_floats].
+    Round,
+    /// Reorder the vector components, see [CoercionOptions::vector_permutation].
+    Permute,
+    /// Check or normalize the vector norms, see [CoercionOptions::require_unit_vectors].
+    Normalize,
+    /// Sort the list values, see [CoercionOptions::sort_list_values].
+    SortLists,
+}
+
+/// The default order of the [PipelineStep]s.
+pub const DEFAULT_PIPELINE_ORDER: [PipelineStep; 5] = [
+    PipelineStep::Clamp,
+    PipelineStep::Round,
+    PipelineStep::Permute,
+    PipelineStep::Normalize,
+    PipelineStep::SortLists,
+];
+
+/// The codes of the labels, shared with the readers that assign new codes.
+pub type SharedLabelCodes = Arc<Mutex<HashMap<String, i64>>>;
+
+/// Options to control how [coerce_schema_with_options] coerces the input data.
+///
+/// Most options only affect the columns whose type differs from the target schema. The
+/// options that clean up values, e.g., [Self::nan_policy] or [Self::trim_strings], apply
+/// to every column they concern, even if its type already matches the target schema.
+#[derive(Debug, Clone)]
+pub struct CoercionOptions {
+    drop_extra_columns: bool,
+    length_mismatch: LengthMismatch,
+    repair_fixed_size_lists: bool,
+    dim_divisor: Option<i32>,
+    max_vector_dim: Option<i32>,
+    day_boundary: DayBoundary,
+    integer_narrowing: NarrowingPolicy,
+    non_nullable_default: Option<Arc<dyn Array>>,
+    canonicalize_metadata: bool,
+    nan_policy: NanPolicy,
+    canonicalize_f16_nan: bool,
+    int_labels: Option<Arc<HashMap<i64, String>>>,
+    label_codes: HashMap<String, SharedLabelCodes>,
+    on_unknown_label: UnknownLabel,
+    coerce_errors_to_null: bool,
+    parse_json_structs: bool,
+    json_key_order: JsonKeyOrder,
+    sort_list_columns: Vec<String>,
+    pipeline_order: Vec<PipelineStep>,
+    f16_rounding: F16Rounding,
+    clamp_ranges: HashMap<String, (f64, f64)>,
+    map_duplicate_keys: MapDuplicateKeys,
+    max_rows: Option<usize>,
+    redactions: HashMap<String, RedactPolicy>,
+    structural_equality_only: bool,
+    parse_timestamps: bool,
+    timestamp_format: Option<String>,
+    drop_all_null_columns: bool,
+    unit_vector_tolerance: Option<f32>,
+    normalize_unit_vectors: bool,
+    zero_norm_policy: ZeroNormPolicy,
+    duration_unit: Option<TimeUnit>,
+    epoch_unit: Option<TimeUnit>,
+    round_decimals: HashMap<String, u32>,
+    valid_length_column: Option<String>,
+    audit_log: Option<PathBuf>,
+    require_stable_schema: bool,
+    vector_permutations: HashMap<String, Vec<usize>>,
+    decode_geohash: bool,
+    decode_bf16: bool,
+    endianness: Endianness,
+    decimals_as_scaled_int: bool,
+    base64_vector_dim: Option<i32>,
+    list_delimiter: Option<char>,
+    defaults: HashMap<String, Arc<dyn Array>>,
+    trim_strings: bool,
+    strip_bom: bool,
+    max_string_bytes: HashMap<String, usize>,
+    oversized_strings: OversizedString,
+    #[cfg(feature = "unicode")]
+    unicode_form: Option<NfForm>,
+    #[cfg(feature = "datafusion")]
+    expressions: HashMap<String, String>,
+    #[cfg(feature = "datafusion")]
+    planned_expressions: Option<Arc<super::expression::PlannedExpressions>>,
+}
+
+impl Default for CoercionOptions {
+    fn default() -> Self {
+        Self {
+            drop_extra_columns: true,
+            length_mismatch: LengthMismatch::default(),
+            repair_fixed_size_lists: false,
+            dim_divisor: None,
+            max_vector_dim: None,
+            day_boundary: DayBoundary::default(),
+            integer_narrowing: NarrowingPolicy::default(),
+            non_nullable_default: None,
+            canonicalize_metadata: false,
+            nan_policy: NanPolicy::default(),
+            canonicalize_f16_nan: false,
+            int_labels: None,
+            label_codes: HashMap::new(),
+            on_unknown_label: UnknownLabel::default(),
+            coerce_errors_to_null: false,
+            parse_json_structs: false,
+            json_key_order: JsonKeyOrder::default(),
+            sort_list_columns: vec![],
+            pipeline_order: DEFAULT_PIPELINE_ORDER.to_vec(),
+            f16_rounding: F16Rounding::default(),
+            clamp_ranges: HashMap::new(),
+            map_duplicate_keys: MapDuplicateKeys::default(),
+            max_rows: None,
+            redactions: HashMap::new(),
+            structural_equality_only: false,
+            parse_timestamps: false,
+            timestamp_format: None,
+            drop_all_null_columns: false,
+            unit_vector_tolerance: None,
+            normalize_unit_vectors: false,
+            zero_norm_policy: ZeroNormPolicy::default(),
+            duration_unit: None,
+            epoch_unit: None,
+            round_decimals: HashMap::new(),
+            valid_length_column: None,
+            audit_log: None,
+            require_stable_schema: false,
+            vector_permutations: HashMap::new(),
+            decode_geohash: false,
+            decode_bf16: false,
+            endianness: Endianness::Native,
+            decimals_as_scaled_int: false,
+            base64_vector_dim: None,
+            list_delimiter: None,
+            defaults: HashMap::new(),
+            trim_strings: false,
+            strip_bom: false,
+            max_string_bytes: HashMap::new(),
+            oversized_strings: OversizedString::default(),
+            #[cfg(feature = "unicode")]
+            unicode_form: None,
+            #[cfg(feature = "datafusion")]
+            expressions: HashMap::new(),
+            #[cfg(feature = "datafusion")]
+            planned_expressions: None,
+        }
+    }
+}
+
+impl CoercionOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set whether to drop the input columns that are not in the target schema.
+    ///
+    /// The output columns are always exactly the fields of the target schema, in the
+    /// order of the target schema, regardless of the column order of the input.
+    /// If set to false, input with columns that are not in the target schema is rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `drop_extra_columns` - Whether to drop the extra columns. Defaults to true.
+    pub fn drop_extra_columns(mut self, drop_extra_columns: bool) -> Self {
+        self.drop_extra_columns = drop_extra_columns;
+        self
+    }
+
+    /// Set how to handle columns of different lengths within a batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `length_mismatch` - The [LengthMismatch] policy. Defaults to [LengthMismatch::Error].
+    pub fn length_mismatch(mut self, length_mismatch: LengthMismatch) -> Self {
+        self.length_mismatch = length_mismatch;
+        self
+    }
+
+    /// Truncate the values of fixed size list columns that have more values than their
+    /// vectors, instead of rejecting them.
+    ///
+    /// Such arrays break the `len * dim` invariant of fixed size lists, e.g., when they are
+    /// built from a values array whose length is not a multiple of the dimension, and are
+    /// rejected by default. Vector columns that need no coercion are repaired too.
+    ///
+    /// # Arguments
+    ///
+    /// * `repair_fixed_size_lists` - Whether to truncate the extra values.
+    pub fn repair_fixed_size_lists(mut self, repair_fixed_size_lists: bool) -> Self {
+        self.repair_fixed_size_lists = repair_fixed_size_lists;
+        self
+    }
+
+    /// Require the dimension of every vector column of the target schema to be a multiple
+    /// of `factor`, e.g., the number of sub-vectors of the PQ index built on it, so that
+    /// the mismatch is caught before the data is written.
+    ///
+    /// # Arguments
+    ///
+    /// * `factor` - The factor, or `None` to not check the dimensions (the default).
+    pub fn require_dim_divisible_by(mut self, factor: Option<i32>) -> Self {
+        self.dim_divisor = factor;
+        self
+    }
+
+    /// Reject the input data and target schemas that have a fixed size list column with
+    /// more than `max_dim` values per row, e.g., to guard against ingesting vectors so
+    /// large that they blow up the memory and the indexing time.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_dim` - The largest allowed dimension, or `None` for no limit (the default).
+    pub fn max_vector_dim(mut self, max_dim: Option<i32>) -> Self {
+        self.max_vector_dim = max_dim;
+        self
+    }
+
+    /// Set which timezone decides the day boundary when coercing timestamps to dates.
+    ///
+    /// # Arguments
+    ///
+    /// * `day_boundary` - The [DayBoundary]. Defaults to [DayBoundary::SourceTimezone].
+    pub fn day_boundary(mut self, day_boundary: DayBoundary) -> Self {
+        self.day_boundary = day_boundary;
+        self
+    }
+
+    /// Set how to handle narrowing integer coercions.
+    ///
+    /// The policy also governs the decimal coercions that reduce the scale, see
+    /// [NarrowingPolicy].
+    ///
+    /// # Arguments
+    ///
+    /// * `integer_narrowing` - The [NarrowingPolicy]. Defaults to [NarrowingPolicy::Allow].
+    pub fn integer_narrowing(mut self, integer_narrowing: NarrowingPolicy) -> Self {
+        self.integer_narrowing = integer_narrowing;
+        self
+    }
+
+    /// Fill the nulls of non-nullable target fields with a default value, instead of
+    /// rejecting the data.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A single-element array holding the default value. It is coerced to
+    ///   the type of each non-nullable field that contains nulls.
+    pub fn fill_non_nullable_with_default(mut self, value: Arc<dyn Array>) -> Self {
+        self.non_nullable_default = Some(value);
+        self
+    }
+
+    /// Set the default value of some target columns, e.g., `status = "pending"`.
+    ///
+    /// A column that is missing from the input is filled with its default, and the nulls
+    /// of a non-nullable column are replaced with it, taking precedence over
+    /// [Self::fill_non_nullable_with_default].
+    ///
+    /// # Arguments
+    ///
+    /// * `defaults` - The default of each column, held by a single-element array. It is
+    ///   coerced to the type of the target field.
+    pub fn defaults(mut self, defaults: HashMap<String, Arc<dyn Array>>) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Set whether to canonicalize the metadata before comparing the input schema with
+    /// the target schema.
+    ///
+    /// Metadata keys are compared with surrounding whitespace trimmed and case-insensitively,
+    /// values with surrounding whitespace trimmed. This applies to the schema metadata and to
+    /// the metadata of the top-level fields. Input that only differs from the target schema
+    /// in such metadata is relabeled with the target schema without coercing any column.
+    ///
+    /// # Arguments
+    ///
+    /// * `canonicalize_metadata` - Whether to canonicalize the metadata. Defaults to false.
+    pub fn canonicalize_metadata(mut self, canonicalize_metadata: bool) -> Self {
+        self.canonicalize_metadata = canonicalize_metadata;
+        self
+    }
+
+    /// Set how to handle NaN components of float vector columns.
+    ///
+    /// This applies to every vector column, see [CoercionOptions].
+    ///
+    /// # Arguments
+    ///
+    /// * `nan_policy` - The [NanPolicy]. Defaults to [NanPolicy::Keep].
+    pub fn nan_policy(mut self, nan_policy: NanPolicy) -> Self {
+        self.nan_policy = nan_policy;
+        self
+    }
+
+    /// Rewrite the NaNs of `Float16` columns and vector columns to a single canonical bit
+    /// pattern, for consumers that compare NaNs bitwise.
+    ///
+    /// Columns that already have their target type are rewritten as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `canonicalize_f16_nan` - Whether to canonicalize the NaNs.
+    pub fn canonicalize_f16_nan(mut self, canonicalize_f16_nan: bool) -> Self {
+        self.canonicalize_f16_nan = canonicalize_f16_nan;
+        self
+    }
+
+    /// Set how `Float32` and `Float64` values are rounded when narrowed to `Float16`,
+    /// e.g., when quantizing vector columns.
+    ///
+    /// # Arguments
+    ///
+    /// * `f16_rounding` - The [F16Rounding]. Defaults to [F16Rounding::RoundToNearest].
+    pub fn f16_rounding(mut self, f16_rounding: F16Rounding) -> Self {
+        self.f16_rounding = f16_rounding;
+        self
+    }
+
+    /// Map integer codes to labels when an integer column is coerced to a string field.
+    ///
+    /// Codes without a label are rejected, unless [Self::coerce_errors_to_null] is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `labels` - The label of each code.
+    pub fn int_to_label(mut self, labels: HashMap<i64, String>) -> Self {
+        self.int_labels = Some(Arc::new(labels));
+        self
+    }
+
+    /// Map labels to integer codes when a string column is coerced to an integer field,
+    /// e.g., to encode a categorical column at ingest.
+    ///
+    /// Labels without a code are handled with [Self::on_unknown_label]. Codes that do not
+    /// fit the integer field fail the coercion.
+    ///
+    /// # Arguments
+    ///
+    /// * `codes` - The code of each label, by the name of the column.
+    pub fn label_to_int(mut self, codes: HashMap<String, HashMap<String, i64>>) -> Self {
+        self.label_codes = codes
+            .into_iter()
+            .map(|(column, codes)| (column, Arc::new(Mutex::new(codes))))
+            .collect();
+        self
+    }
+
+    /// Set how labels without a code are mapped by [Self::label_to_int].
+    ///
+    /// # Arguments
+    ///
+    /// * `on_unknown` - The [UnknownLabel] policy. Defaults to [UnknownLabel::Error].
+    pub fn on_unknown_label(mut self, on_unknown: UnknownLabel) -> Self {
+        self.on_unknown_label = on_unknown;
+        self
+    }
+
+    /// The codes of the `column` of [Self::label_to_int], including the codes assigned so
+    /// far by the readers coercing with these options.
+    pub fn label_codes(&self, column: &str) -> Option<SharedLabelCodes> {
+        self.label_codes.get(column).cloned()
+    }
+
+    /// Set whether values that can not be coerced become null, instead of failing the
+    /// coercion.
+    ///
+    /// # Arguments
+    ///
+    /// * `coerce_errors_to_null` - Whether to null the invalid values. Defaults to false.
+    pub fn coerce_errors_to_null(mut self, coerce_errors_to_null: bool) -> Self {
+        self.coerce_errors_to_null = coerce_errors_to_null;
+        self
+    }
+
+    /// Set whether JSON object strings are parsed when a string column is coerced to a
+    /// struct field.
+    ///
+    /// Absent keys become null, and malformed JSON is rejected with its row index,
+    /// unless [Self::coerce_errors_to_null] is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `parse_json_structs` - Whether to parse JSON strings. Defaults to false.
+    pub fn parse_json_structs(mut self, parse_json_structs: bool) -> Self {
+        self.parse_json_structs = parse_json_structs;
+        self
+    }
+
+    /// Set the order of the keys of the JSON objects, when a struct column is serialized
+    /// to a string field.
+    ///
+    /// Struct values are always serialized to compact JSON objects, and null structs
+    /// stay null; null children are serialized as `null`. The keys of the objects within
+    /// lists are always sorted.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The [JsonKeyOrder] of the keys of the struct and its nested structs.
+    pub fn json_key_order(mut self, order: JsonKeyOrder) -> Self {
+        self.json_key_order = order;
+        self
+    }
+
+    /// Sort the values of each list of the named list columns ascending, with nulls last.
+    ///
+    /// This gives set-like columns a canonical order, e.g., for deduplication.
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - The names of the list columns to sort.
+    pub fn sort_list_values(mut self, columns: Vec<String>) -> Self {
+        self.sort_list_columns = columns;
+        self
+    }
+
+    /// Clamp the values of the named numeric columns to inclusive ranges.
+    ///
+    /// The values are clamped after they are cast to the type of the target field, so
+    /// the bounds are converted to that type, e.g., truncated for integer columns.
+    /// Null values are left untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `ranges` - The `(min, max)` range of each column.
+    pub fn clamp(mut self, ranges: HashMap<String, (f64, f64)>) -> Self {
+        self.clamp_ranges = ranges;
+        self
+    }
+
+    /// Reorder the components of the named vector columns, e.g., after an embedding model
+    /// changed the order of its output dimensions.
+    ///
+    /// The `i`-th component of each output vector is the `permutation[i]`-th component of
+    /// the input vector. The permutation must have one entry per dimension, and use each
+    /// index once. Null vectors stay null.
+    ///
+    /// # Arguments
+    ///
+    /// * `permutations` - The permutation of each vector column.
+    pub fn vector_permutation(mut self, permutations: HashMap<String, Vec<usize>>) -> Self {
+        self.vector_permutations = permutations;
+        self
+    }
+
+    /// Join lists into delimited strings when a list column is coerced to a string field,
+    /// e.g., `["a", "b"]` to `"a,b"`, and split strings into lists the other way around.
+    ///
+    /// Null rows stay null. Null list elements are skipped when joining, and an empty
+    /// string splits into an empty list.
+    ///
+    /// # Arguments
+    ///
+    /// * `delimiter` - The delimiter between the list elements.
+    pub fn list_delimiter(mut self, delimiter: char) -> Self {
+        self.list_delimiter = Some(delimiter);
+        self
+    }
+
+    /// Decode geohash strings into `[lat, lon]` vectors, when a string column is coerced
+    /// to a vector field of dimension 2.
+    ///
+    /// A geohash decodes to the center of its cell. Invalid geohashes are rejected with
+    /// their row index.
+    ///
+    /// # Arguments
+    ///
+    /// * `decode_geohash` - Whether to decode geohashes. Defaults to false.
+    pub fn decode_geohash(mut self, decode_geohash: bool) -> Self {
+        self.decode_geohash = decode_geohash;
+        self
+    }
+
+    /// Decode `UInt16` values as the bits of bfloat16 numbers, when they are coerced to
+    /// a floating point type, e.g., embeddings exported as bf16 by a model server. Arrow
+    /// has no native bfloat16 type.
+    ///
+    /// This applies to `UInt16` columns, to fixed size lists of `UInt16`, and to fixed
+    /// size binaries of bf16 bytes coerced to vectors of floats, in the byte order of
+    /// [CoercionOptions::endianness].
+    ///
+    /// # Arguments
+    ///
+    /// * `decode_bf16` - Whether to decode bfloat16 values. Defaults to false.
+    pub fn decode_bf16(mut self, decode_bf16: bool) -> Self {
+        self.decode_bf16 = decode_bf16;
+        self
+    }
+
+    /// Set the byte order of the binary values reinterpreted as numbers, i.e., by
+    /// [CoercionOptions::decode_base64_vectors] and [CoercionOptions::decode_bf16].
+    ///
+    /// # Arguments
+    ///
+    /// * `endianness` - The [Endianness]. Defaults to [Endianness::Native].
+    pub fn endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    /// Store decimal columns coerced to `Int64` fields as their unscaled values, instead
+    /// of truncating them to integers, e.g., for compact storage of prices.
+    ///
+    /// The scale is recorded in the [DECIMAL_SCALE_METADATA_KEY] metadata of the output
+    /// field, and values that do not fit `Int64` are rejected. Such a column is coerced
+    /// back to a decimal field losslessly, with or without this option.
+    ///
+    /// # Arguments
+    ///
+    /// * `decimals_as_scaled_int` - Whether to store the unscaled values. Defaults to
+    ///   false.
+    pub fn decimals_as_scaled_int(mut self, decimals_as_scaled_int: bool) -> Self {
+        self.decimals_as_scaled_int = decimals_as_scaled_int;
+        self
+    }
+
+    /// Decode base64 strings of `f32` bytes into vectors, when a string column is coerced
+    /// to a vector field, e.g., embeddings shipped as base64 by an API. The bytes are in
+    /// the byte order of [CoercionOptions::endianness].
+    ///
+    /// Strings that do not decode to exactly `4 * dim` bytes are rejected with their row
+    /// index, and so is a vector field of another dimension.
+    ///
+    /// # Arguments
+    ///
+    /// * `dim` - The dimension of the vectors, or `None` to not decode base64 (the
+    ///   default).
+    pub fn decode_base64_vectors(mut self, dim: Option<i32>) -> Self {
+        self.base64_vector_dim = dim;
+        self
+    }
+
+    /// Require the vectors of every floating point vector column to have a L2 norm of 1,
+    /// within `tolerance`.
+    ///
+    /// A vector whose norm deviates further fails the batch, with the row index and the
+    /// measured norm, unless [Self::normalize_unit_vectors] is set. Null vectors are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `tolerance` - The maximum deviation of a norm from 1.
+    pub fn require_unit_vectors(mut self, tolerance: f32) -> Self {
+        self.unit_vector_tolerance = Some(tolerance);
+        self
+    }
+
+    /// Set whether the vectors that fail [Self::require_unit_vectors] are re-normalized,
+    /// instead of failing the batch. Zero vectors can not be normalized, and still fail,
+    /// unless [Self::on_zero_norm_vectors] is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `normalize` - Whether to re-normalize the vectors. Defaults to false.
+    pub fn normalize_unit_vectors(mut self, normalize: bool) -> Self {
+        self.normalize_unit_vectors = normalize;
+        self
+    }
+
+    /// Set what to do with the zero vectors, whose norm stays zero after
+    /// [Self::normalize_unit_vectors], e.g., to keep them but audit them through the
+    /// [CoercionReport] of [coerce_schema_with_report].
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The [ZeroNormPolicy]. Defaults to [ZeroNormPolicy::Error].
+    pub fn on_zero_norm_vectors(mut self, policy: ZeroNormPolicy) -> Self {
+        self.zero_norm_policy = policy;
+        self
+    }
+
+    /// Set how to handle duplicate keys within the maps of map columns.
+    ///
+    /// The keys of every map column are checked, including the ones that need no coercion.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The [MapDuplicateKeys] policy. Defaults to [MapDuplicateKeys::Keep].
+    pub fn map_duplicate_keys(mut self, policy: MapDuplicateKeys) -> Self {
+        self.map_duplicate_keys = policy;
+        self
+    }
+
+    /// Limit the total number of rows of the coerced stream, as a guardrail against
+    /// accidentally huge ingests.
+    ///
+    /// The batch that exceeds the limit fails with [Error::RowLimitExceeded], wrapped in
+    /// an [ArrowError::ExternalError].
+    ///
+    /// # Arguments
+    ///
+    /// * `max_rows` - The maximum number of rows, or `None` for no limit (the default).
+    pub fn max_rows(mut self, max_rows: Option<usize>) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    /// Convert durations to plain numbers counting `unit`s, when a duration column is
+    /// coerced to a numeric field, e.g., to store milliseconds as seconds.
+    ///
+    /// Integer fields truncate the sub-unit remainders, with a warning; float fields keep
+    /// them as fractions.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - The unit of the numbers.
+    pub fn duration_as_unit(mut self, unit: TimeUnit) -> Self {
+        self.duration_unit = Some(unit);
+        self
+    }
+
+    /// Convert timestamps to epoch numbers counting `unit`s, when a timestamp column is
+    /// coerced to a numeric field, e.g., to store microsecond timestamps as epoch millis.
+    ///
+    /// Like [Self::duration_as_unit], integer fields truncate the sub-unit remainders,
+    /// with a warning, and fail if upscaling overflows.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - The unit of the epoch numbers.
+    pub fn timestamp_as_epoch_unit(mut self, unit: TimeUnit) -> Self {
+        self.epoch_unit = Some(unit);
+        self
+    }
+
+    /// Round the values of the named float columns, or of the items of float vector and
+    /// list columns, to a number of decimal digits, e.g., so that near-equal values are
+    /// stored and deduplicated deterministically.
+    ///
+    /// Ties are rounded half to even, e.g., `0.0625` becomes `0.062` with 3 decimals.
+    /// The rounding is decimal, so the rounded value is the nearest float to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `decimals` - The number of decimal digits of each column.
+    pub fn round_floats(mut self, decimals: HashMap<String, u32>) -> Self {
+        self.round_decimals = decimals;
+        self
+    }
+
+    /// Override the order of the transforms applied to each column once it is cast.
+    ///
+    /// Each column goes through a fixed pipeline: redaction, then parsing and casting to
+    /// the target type, then string trimming, unicode normalization and length limits,
+    /// then the non-nullable check, and finally the [PipelineStep]s. These are applied in
+    /// [DEFAULT_PIPELINE_ORDER] unless overridden, and the order matters, e.g., clamping
+    /// `0.8` to `(0.0, 0.74)` then rounding to 1 decimal gives `0.7`, while rounding
+    /// first gives `0.74`. Only the steps configured for a column apply to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The order of the steps. Steps missing from it are applied after it,
+    ///   in the default order, and repeated steps are applied once.
+    pub fn pipeline_order(mut self, order: Vec<PipelineStep>) -> Self {
+        let mut steps: Vec<PipelineStep> = vec![];
+        for step in order.into_iter().chain(DEFAULT_PIPELINE_ORDER) {
+            if !steps.contains(&step) {
+                steps.push(step);
+            }
+        }
+        self.pipeline_order = steps;
+        self
+    }
+
+    /// Append a `UInt32` column with the original length of each list, when a list column
+    /// is coerced to a vector column.
+    ///
+    /// The lists are then padded with null items, or truncated, to the dimension of the
+    /// vector column, instead of failing on lists of another length. Only one list column
+    /// can be coerced to a vector column, so that the lengths are not ambiguous.
+    ///
+    /// # Arguments
+    ///
+    /// * `column` - The name of the length column, or `None` to not append it.
+    pub fn emit_valid_length_column(mut self, column: Option<String>) -> Self {
+        self.valid_length_column = column;
+        self
+    }
+
+    /// Append a JSON line to an audit log file once the coercion stream is drained, so
+    /// that schema changes can be audited across runs.
+    ///
+    /// Each line records the time of the run, the source and target schemas, the
+    /// [CoercionPlan], and the row counts of the [CoercionReport]. The file is created if
+    /// it does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the audit log, or `None` to not write one (the default).
+    pub fn audit_log(mut self, path: Option<PathBuf>) -> Self {
+        self.audit_log = path;
+        self
+    }
+
+    /// Require every batch of the stream to share the schema of the first batch.
+    ///
+    /// Readers do not always produce batches matching their declared schema. With this
+    /// option, a batch whose fields differ from the first batch, in name or type, fails
+    /// the stream at that batch instead of being coerced on its own.
+    ///
+    /// # Arguments
+    ///
+    /// * `require` - Whether to fail on batches with a different schema. Defaults to false.
+    pub fn require_stable_schema(mut self, require: bool) -> Self {
+        self.require_stable_schema = require;
+        self
+    }
+
+    /// Compute the named target columns with SQL scalar expressions, evaluated over each
+    /// input batch, e.g., `"lower(trim(name))"`. The result is then coerced to the target
+    /// field like any input column.
+    ///
+    /// The expressions are planned by DataFusion, without any table or user-defined
+    /// function. The supported subset is:
+    ///
+    /// - column references, literals, arithmetic, comparison and boolean operators,
+    ///   `CASE`, `CAST` and `IS [NOT] NULL`;
+    /// - the built-in string functions, e.g., `upper`, `lower`, `trim`, `concat`,
+    ///   `replace`, and the `regexp_*` functions;
+    /// - the built-in math functions, e.g., `abs`, `round`, `sqrt`, and the conditional
+    ///   functions `coalesce` and `nullif`.
+    ///
+    /// The unicode functions, e.g., `substr` or `character_length`, and the crypto
+    /// functions are not compiled in, and fail.
+    ///
+    /// Requires the `datafusion` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `expressions` - The expression of each computed target column.
+    #[cfg(feature = "datafusion")]
+    pub fn expressions(mut self, expressions: HashMap<String, String>) -> Self {
+        self.expressions = expressions;
+        self.planned_expressions = None;
+        self
+    }
+
+    /// Plan the expressions once over the `schema` of the input, so that an invalid
+    /// expression fails before any batch is read.
+    #[cfg(feature = "datafusion")]
+    fn plan_expressions(mut self, schema: &SchemaRef) -> Result<Self> {
+        if !self.expressions.is_empty() {
+            let planned =
+                super::expression::PlannedExpressions::try_new(&self.expressions, schema.clone())?;
+            self.planned_expressions = Some(Arc::new(planned));
+        }
+        Ok(self)
+    }
+
+    #[cfg(not(feature = "datafusion"))]
+    fn plan_expressions(self, _schema: &SchemaRef) -> Result<Self> {
+        Ok(self)
+    }
+
+    /// Normalize the strings of every string column to a Unicode normalization form, so
+    /// that differently composed strings, e.g., `"é"` and `"e\u{301}"`, compare equal.
+    ///
+    /// Every top-level string column is normalized, not only the coerced ones.
+    ///
+    /// Requires the `unicode` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `form` - The [NfForm] to normalize to, or `None` to keep the strings as they are
+    ///   (the default).
+    #[cfg(feature = "unicode")]
+    pub fn unicode_normalize(mut self, form: Option<NfForm>) -> Self {
+        self.unicode_form = form;
+        self
+    }
+
+    /// Trim the leading and trailing whitespace of the strings of every string column,
+    /// e.g., padding from a CSV export that breaks equality and joins.
+    ///
+    /// Top-level string columns are trimmed whether or not they are coerced.
+    ///
+    /// # Arguments
+    ///
+    /// * `trim_strings` - Whether to trim the strings.
+    pub fn trim_strings(mut self, trim_strings: bool) -> Self {
+        self.trim_strings = trim_strings;
+        self
+    }
+
+    /// Strip a leading byte order mark (`U+FEFF`) from the strings of every string column,
+    /// e.g., the first value of a CSV file saved with one.
+    ///
+    /// The marks are stripped from all top-level string columns, coerced or not.
+    ///
+    /// # Arguments
+    ///
+    /// * `strip_bom` - Whether to strip the byte order marks.
+    pub fn strip_bom(mut self, strip_bom: bool) -> Self {
+        self.strip_bom = strip_bom;
+        self
+    }
+
+    /// Limit the length in bytes of the values of the named string columns, e.g., to
+    /// respect the constraints of a downstream store.
+    ///
+    /// The limit counts the bytes of the UTF-8 encoding, not the characters. Longer values
+    /// fail the batch with their row index, unless [Self::on_oversized_strings] is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `limits` - The maximum number of bytes of each column, which must be a string
+    ///   column of the target schema.
+    pub fn max_string_bytes(mut self, limits: HashMap<String, usize>) -> Self {
+        self.max_string_bytes = limits;
+        self
+    }
+
+    /// Set what to do with the strings longer than [Self::max_string_bytes].
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The [OversizedString] policy. Defaults to [OversizedString::Error].
+    pub fn on_oversized_strings(mut self, policy: OversizedString) -> Self {
+        self.oversized_strings = policy;
+        self
+    }
+
+    /// Parse strings into timestamps when a string column is coerced to a timestamp field.
+    ///
+    /// Strings without an offset are in the timezone of the target field, or UTC if it has
+    /// none. Unparseable strings are rejected with their row index.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - A strptime-like format, e.g., `"%d/%m/%Y %H:%M"`, or `None` to parse
+    ///   ISO-8601 (RFC 3339) strings.
+    pub fn parse_timestamp_strings(mut self, format: Option<String>) -> Self {
+        self.parse_timestamps = true;
+        self.timestamp_format = format;
+        self
+    }
+
+    /// Set whether the source columns that are entirely null within a batch are dropped,
+    /// rather than coerced.
+    ///
+    /// An all-null column that is not in the target schema is dropped even if
+    /// [Self::drop_extra_columns] is false, and an all-null column of a nullable target
+    /// field is replaced by nulls of the target type, whatever its source type.
+    ///
+    /// All-null-ness is decided per batch, so the same source column may be dropped in one
+    /// batch and rejected or coerced in another. Dropping columns this way would make the
+    /// output schema batch-dependent if nothing pinned it; here the target schema does, so
+    /// every coerced batch still has the target schema.
+    ///
+    /// # Arguments
+    ///
+    /// * `drop_all_null_columns` - Whether to drop all-null columns. Defaults to false.
+    pub fn drop_all_null_columns(mut self, drop_all_null_columns: bool) -> Self {
+        self.drop_all_null_columns = drop_all_null_columns;
+        self
+    }
+
+    /// Set whether an input schema that only differs from the target schema by its
+    /// metadata or by the nullability of its fields skips the coercion.
+    ///
+    /// The schemas are then compared structurally: the field names and data types must
+    /// be exactly the same, in the same order, but the schema and field metadata, and
+    /// the top-level nullability, are ignored. The batches are relabeled with the target
+    /// schema, which still rejects nulls in non-nullable fields. This does not apply
+    /// with [Self::fill_non_nullable_with_default], which needs to see the values.
+    ///
+    /// # Arguments
+    ///
+    /// * `structural_equality_only` - Whether to compare the schemas structurally.
+    ///   Defaults to false.
+    pub fn structural_equality_only(mut self, structural_equality_only: bool) -> Self {
+        self.structural_equality_only = structural_equality_only;
+        self
+    }
+
+    /// Redact the values of the named columns, e.g., for privacy-preserving ingestion.
+    ///
+    /// The redaction runs before the column is coerced, so the redacted values are
+    /// coerced to the type of the target field, e.g., digests need a string field.
+    ///
+    /// # Arguments
+    ///
+    /// * `redactions` - The [RedactPolicy] of each column.
+    pub fn redact(mut self, redactions: HashMap<String, RedactPolicy>) -> Self {
+        self.redactions = redactions;
+        self
+    }
+
+    /// Whether the values need to be rewritten even if the types already match.
+    fn rewrites_values(&self) -> bool {
+        self.nan_policy != NanPolicy::Keep
+            || self.canonicalize_f16_nan
+            || self.repair_fixed_size_lists
+            || !self.redactions.is_empty()
+            || self.map_duplicate_keys != MapDuplicateKeys::Keep
+            || !self.sort_list_columns.is_empty()
+            || !self.clamp_ranges.is_empty()
+            || !self.round_decimals.is_empty()
+            || self.unit_vector_tolerance.is_some()
+            || !self.vector_permutations.is_empty()
+            || self.trim_strings
+            || self.strip_bom
+            || !self.max_string_bytes.is_empty()
+            || self.has_expressions()
+            || self.normalizes_unicode()
+    }
+
+    #[cfg(feature = "unicode")]
+    fn normalizes_unicode(&self) -> bool {
+        self.unicode_form.is_some()
+    }
+
+    #[cfg(not(feature = "unicode"))]
+    fn normalizes_unicode(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "datafusion")]
+    fn has_expressions(&self) -> bool {
+        !self.expressions.is_empty()
+    }
+
+    #[cfg(not(feature = "datafusion"))]
+    fn has_expressions(&self) -> bool {
+        false
+    }
+}
+
+/// Redact the values of a column with the `policy`.
+fn redact(
+    column: &Arc<dyn Array>,
+    field: &Field,
+    policy: &RedactPolicy,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    match policy {
+        RedactPolicy::Null => Ok(new_null_array(column.data_type(), column.len())),
+        RedactPolicy::Hash => {
+            let digests = (0..column.len())
+                .map(|i| {
+                    if column.is_null(i) {
+                        return Ok(None);
+                    }
+                    let value = array_value_to_string(column, i)?;
+                    Ok(Some(format!("{:x}", Sha256::digest(value.as_bytes()))))
+                })
+                .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+            Ok(Arc::new(StringArray::from(digests)))
+        }
+        RedactPolicy::Constant(value) => {
+            if value.len() != 1 {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "Redaction constant of column {} must have exactly one element, got {}",
+                    field.name(),
+                    value.len()
+                )));
+            }
+            take(value, &UInt32Array::from(vec![0; column.len()]), None)
+        }
+    }
+}
+
+/// Replace the nulls of `array` with `value`, a single-element array of the same type.
+fn fill_nulls(
+    array: &Arc<dyn Array>,
+    value: &Arc<dyn Array>,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    if value.len() != 1 {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "Default value must have exactly one element, got {}",
+            value.len()
+        )));
+    }
+    let len = array.len();
+    let combined = concat(&[array.as_ref(), value.as_ref()])?;
+    let indices = UInt32Array::from_iter_values((0..len).map(|i| {
+        if array.is_valid(i) {
+            i as u32
+        } else {
+            len as u32
+        }
+    }));
+    take(combined.as_ref(), &indices, None)
+}
+
+/// Make sure a coerced array does not carry nulls into a non-nullable field.
+fn check_non_nullable(
+    array: Arc<dyn Array>,
+    field: &Field,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    if field.is_nullable() || array.null_count() == 0 {
+        return Ok(array);
+    }
+    let default = options
+        .defaults
+        .get(field.name())
+        .or(options.non_nullable_default.as_ref());
+    match default {
+        Some(value) => {
+            let value = coerce_array(value, field, options)?;
+            fill_nulls(&array, &value)
+        }
+        None => Err(ArrowError::InvalidArgumentError(format!(
+            "Field {} is not nullable, but contains {} nulls",
+            field.name(),
+            array.null_count()
+        ))),
+    }
+}
+
+/// Make sure all the columns have the same length before assembling a batch.
+fn align_column_lengths(
+    schema: &Schema,
+    columns: Vec<Arc<dyn Array>>,
+    policy: LengthMismatch,
+) -> std::result::Result<Vec<Arc<dyn Array>>, ArrowError> {
+    let lengths = columns.iter().map(|c| c.len()).collect::<Vec<_>>();
+    let (Some(min), Some(max)) = (lengths.iter().min(), lengths.iter().max()) else {
+        return Ok(columns);
+    };
+    if min == max {
+        return Ok(columns);
+    }
+    match policy {
+        LengthMismatch::Error => Err(ArrowError::ExternalError(Box::new(
+            Error::ColumnLengthMismatch {
+                lengths: schema
+                    .fields()
+                    .iter()
+                    .map(|f| f.name().clone())
+                    .zip(lengths)
+                    .collect(),
+            },
+        ))),
+        LengthMismatch::Truncate => Ok(columns.iter().map(|c| c.slice(0, *min)).collect()),
+        LengthMismatch::Pad => columns
+            .iter()
+            .map(|c| {
+                if c.len() == *max {
+                    return Ok(c.clone());
+                }
+                let nulls = new_null_array(c.data_type(), max - c.len());
+                concat(&[c.as_ref(), nulls.as_ref()])
+            })
+            .collect(),
+    }
+}
+
+/// Field metadata key recording the dimension of a fixed size list column that was
+/// exported as a variable-length list.
+pub const ORIGINAL_DIMENSION_METADATA_KEY: &str = "lance:original_dimension";
+
+/// The names of the list columns of `input` that are coerced to vector columns of `target`.
+fn list_to_vector_columns(input: &Schema, target: &Schema) -> Vec<String> {
+    target
+        .fields()
+        .iter()
+        .filter(|f| matches!(f.data_type(), DataType::FixedSizeList(_, _)))
+        .filter(|f| {
+            matches!(
+                input.field_with_name(f.name()).map(|f| f.data_type()),
+                Ok(DataType::List(_) | DataType::LargeList(_))
+            )
+        })
+        .map(|f| f.name().clone())
+        .collect()
+}
+
+/// Append the field of [CoercionOptions::emit_valid_length_column] to the target schema.
+fn add_valid_length_field(
+    input: &Schema,
+    target: SchemaRef,
+    options: &CoercionOptions,
+) -> Result<SchemaRef> {
+    let Some(name) = &options.valid_length_column else {
+        return Ok(target);
+    };
+    let columns = list_to_vector_columns(input, &target);
+    if columns.len() != 1 {
+        return Err(Error::Schema {
+            message: format!(
+                "Valid length column {} needs exactly one list column coerced to a vector column, got {:?}",
+                name, columns
+            ),
+        });
+    }
+    if target.field_with_name(name).is_ok() || input.field_with_name(name).is_ok() {
+        return Err(Error::Schema {
+            message: format!("Valid length column {} already exists", name),
+        });
+    }
+    let mut fields = target.fields().to_vec();
+    fields.push(Arc::new(Field::new(name, DataType::UInt32, true)));
+    Ok(Arc::new(Schema::new_with_metadata(
+        fields,
+        target.metadata().clone(),
+    )))
+}
+
+/// The original lengths of the list column coerced to a vector column.
+fn valid_lengths(
+    batch: &RecordBatch,
+    schema: &Schema,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    let columns = list_to_vector_columns(&batch.schema(), schema);
+    let column = columns
+        .first()
+        .and_then(|name| batch.column_by_name(name))
+        .ok_or_else(|| {
+            ArrowError::SchemaError("No list column to record the lengths of".to_string())
+        })?;
+    cast(&length(column)?, &DataType::UInt32)
+}
+
+/// Field metadata key declaring the dimension of a vector column.
+pub const VECTOR_DIMENSION_METADATA_KEY: &str = "lance:vector_dim";
+
+/// Check that the vector columns of the target schema have the dimension declared by
+/// their metadata, which catches schemas that were built inconsistently.
+fn check_declared_dimensions(schema: &Schema) -> Result<()> {
+    for field in schema.fields() {
+        let (Some(declared), DataType::FixedSizeList(_, dim)) = (
+            field.metadata().get(VECTOR_DIMENSION_METADATA_KEY),
+            field.data_type(),
+        ) else {
+            continue;
+        };
+        if declared.trim().parse::<i32>().ok() != Some(*dim) {
+            return Err(Error::Schema {
+                message: format!(
+                    "Vector column {} declares dimension {} in its {} metadata, but has dimension {}",
+                    field.name(),
+                    declared,
+                    VECTOR_DIMENSION_METADATA_KEY,
+                    dim
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn check_dimension_divisor(schema: &Schema, options: &CoercionOptions) -> Result<()> {
+    let Some(factor) = options.dim_divisor else {
+        return Ok(());
+    };
+    if factor <= 0 {
+        return Err(Error::Schema {
+            message: format!("Vector dimension factor must be positive, got {}", factor),
+        });
+    }
+    for field in schema.fields() {
+        let DataType::FixedSizeList(item, dim) = field.data_type() else {
+            continue;
+        };
+        if item.data_type().is_floating() && dim % factor != 0 {
+            return Err(Error::Schema {
+                message: format!(
+                    "Vector column {} has dimension {}, which is not divisible by {}",
+                    field.name(),
+                    dim,
+                    factor
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn check_max_string_bytes(schema: &Schema, options: &CoercionOptions) -> Result<()> {
+    for column in options.max_string_bytes.keys() {
+        match schema.field_with_name(column).map(|f| f.data_type()) {
+            Ok(DataType::Utf8 | DataType::LargeUtf8) => {}
+            Ok(data_type) => {
+                return Err(Error::Schema {
+                    message: format!(
+                        "Column {} has type {:?}, but its string length is limited",
+                        column, data_type
+                    ),
+                })
+            }
+            Err(_) => {
+                return Err(Error::Schema {
+                    message: format!(
+                        "Column {} has a string length limit but is not in the target schema",
+                        column
+                    ),
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_max_vector_dim(schema: &Schema, options: &CoercionOptions) -> Result<()> {
+    let Some(max_dim) = options.max_vector_dim else {
+        return Ok(());
+    };
+    for field in schema.fields() {
+        match field.data_type() {
+            DataType::FixedSizeList(_, dim) if *dim > max_dim => {
+                return Err(Error::Schema {
+                    message: format!(
+                        "Vector column {} has dimension {}, which exceeds the maximum of {}",
+                        field.name(),
+                        dim,
+                        max_dim
+                    ),
+                })
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Field metadata key recording the scale of a decimal column stored as unscaled
+/// `Int64` values, see [CoercionOptions::decimals_as_scaled_int].
+pub const DECIMAL_SCALE_METADATA_KEY: &str = "lance:decimal_scale";
+
+/// Record the dimension of the fixed size list columns of `input` that are coerced
+/// to variable-length lists in the `target` schema, and the scale of the decimal columns
+/// stored as scaled integers.
+fn annotate_source_types(
+    input: &Schema,
+    target: SchemaRef,
+    options: &CoercionOptions,
+) -> SchemaRef {
+    let mut annotated = false;
+    let fields = target
+        .fields()
+        .iter()
+        .map(|field| {
+            let source = input.field_with_name(field.name());
+            let (key, value) = match (source.map(|f| f.data_type()), field.data_type()) {
+                (Ok(DataType::FixedSizeList(_, dim)), DataType::LargeList(_)) => {
+                    (ORIGINAL_DIMENSION_METADATA_KEY, dim.to_string())
+                }
+                (Ok(DataType::Decimal128(_, scale)), DataType::Int64)
+                    if options.decimals_as_scaled_int =>
+                {
+                    (DECIMAL_SCALE_METADATA_KEY, scale.to_string())
+                }
+                _ => return field.clone(),
+            };
+            annotated = true;
+            let mut metadata = field.metadata().clone();
+            metadata.insert(key.to_string(), value);
+            Arc::new(field.as_ref().clone().with_metadata(metadata))
+        })
+        .collect::<Vec<_>>();
+    if !annotated {
+        return target;
+    }
+    Arc::new(Schema::new_with_metadata(fields, target.metadata().clone()))
+}
+
+fn canonical_metadata(metadata: &HashMap<String, String>) -> HashMap<String, String> {
+    metadata
+        .iter()
+        .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+        .collect()
+}
+
+fn canonical_schema(schema: &Schema) -> Schema {
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|f| {
+            f.as_ref()
+                .clone()
+                .with_metadata(canonical_metadata(f.metadata()))
+        })
+        .collect::<Vec<_>>();
+    Schema::new_with_metadata(fields, canonical_metadata(schema.metadata()))
+}
+
+/// Whether `input` and `target` have the same structure: the same field names, in the
+/// same order, with exactly the same data types.
+///
+/// The metadata of the schemas and of their top-level fields, and the nullability of
+/// the top-level fields, are ignored. Nested fields are part of the data types, so they
+/// are still compared strictly.
+fn is_structurally_equal(input: &Schema, target: &Schema) -> bool {
+    input.fields().len() == target.fields().len()
+        && input
+            .fields()
+            .iter()
+            .zip(target.fields())
+            .all(|(a, b)| a.name() == b.name() && a.data_type() == b.data_type())
+}
+
+/// Whether `input` can be relabeled with `target` without coercing any column.
+fn is_equivalent_schema(input: &Schema, target: &Schema, options: &CoercionOptions) -> bool {
+    !options.rewrites_values()
+        && !dimension_orders_differ(input, target)
+        && (input == target
+            || (options.canonicalize_metadata
+                && canonical_schema(input) == canonical_schema(target))
+            || (options.structural_equality_only
+                && options.non_nullable_default.is_none()
+                && options.defaults.is_empty()
+                && is_structurally_equal(input, target)))
+}
+
+/// Whether every value of the array is null.
+fn is_all_null(array: &Arc<dyn Array>) -> bool {
+    array.data_type() == &DataType::Null || array.null_count() == array.len()
+}
+
+/// Find the first column of `input` that is not in the `target` schema.
+fn find_extra_column(input: &Schema, target: &Schema) -> Option<String> {
+    input
+        .fields()
+        .iter()
+        .find(|f| target.field_with_name(f.name()).is_err())
+        .map(|f| f.name().clone())
+}
+
+/// Reject or truncate the strings longer than `limit` bytes, as set by
+/// [CoercionOptions::max_string_bytes]. Strings are truncated at a character boundary,
+/// so they may end up shorter than the limit.
+///
+/// `first_row` is the index of the first row of the batch in the stream, to report errors.
+fn limit_string_bytes(
+    array: &Arc<dyn Array>,
+    field: &Field,
+    limit: usize,
+    policy: OversizedString,
+    first_row: usize,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    fn limit_strings<O: OffsetSizeTrait>(
+        strings: &GenericStringArray<O>,
+        field: &Field,
+        limit: usize,
+        policy: OversizedString,
+        first_row: usize,
+    ) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+        let oversized = strings
+            .iter()
+            .position(|s| s.is_some_and(|s| s.len() > limit));
+        let Some(row) = oversized else {
+            return Ok(Arc::new(strings.clone()));
+        };
+        if policy == OversizedString::Error {
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "String at row {} of column {} has {} bytes, which exceeds the limit of {}",
+                first_row + row,
+                field.name(),
+                strings.value(row).len(),
+                limit
+            )));
+        }
+        let truncated = strings
+            .iter()
+            .map(|s| {
+                s.map(|s| {
+                    let end = (0..=limit.min(s.len()))
+                        .rev()
+                        .find(|&i| s.is_char_boundary(i))
+                        .unwrap_or(0);
+                    &s[..end]
+                })
+            })
+            .collect::<GenericStringArray<O>>();
+        Ok(Arc::new(truncated))
+    }
+
+    match array.data_type() {
+        DataType::Utf8 => limit_strings(array.as_string::<i32>(), field, limit, policy, first_row),
+        DataType::LargeUtf8 => {
+            limit_strings(array.as_string::<i64>(), field, limit, policy, first_row)
+        }
+        dt => Err(ArrowError::SchemaError(format!(
+            "Column {} is not a string column, can not limit its length: {:?}",
+            field.name(),
+            dt
+        ))),
+    }
+}
+
+/// Strip the byte order marks and trim the whitespace of a string column, as set by
+/// [CoercionOptions::strip_bom] and [CoercionOptions::trim_strings].
+fn clean_strings(array: &Arc<dyn Array>, options: &CoercionOptions) -> Arc<dyn Array> {
+    fn clean<O: OffsetSizeTrait>(
+        strings: &GenericStringArray<O>,
+        options: &CoercionOptions,
+    ) -> Arc<dyn Array> {
+        Arc::new(
+            strings
+                .iter()
+                .map(|s| {
+                    s.map(|s| {
+                        let s = if options.strip_bom {
+                            s.strip_prefix('\u{feff}').unwrap_or(s)
+                        } else {
+                            s
+                        };
+                        if options.trim_strings {
+                            s.trim()
+                        } else {
+                            s
+                        }
+                    })
+                })
+                .collect::<GenericStringArray<O>>(),
+        )
+    }
+
+    if !options.trim_strings && !options.strip_bom {
+        return array.clone();
+    }
+    match array.data_type() {
+        DataType::Utf8 => clean(array.as_string::<i32>(), options),
+        DataType::LargeUtf8 => clean(array.as_string::<i64>(), options),
+        _ => array.clone(),
+    }
+}
+
+/// Normalize the strings of a string column to the Unicode normalization `form`.
+#[cfg(feature = "unicode")]
+fn normalize_unicode(array: &Arc<dyn Array>, form: NfForm) -> Arc<dyn Array> {
+    fn normalize<O: OffsetSizeTrait>(
+        strings: &GenericStringArray<O>,
+        form: NfForm,
+    ) -> Arc<dyn Array> {
+        use unicode_normalization::UnicodeNormalization;
+
+        Arc::new(
+            strings
+                .iter()
+                .map(|s| {
+                    s.map(|s| match form {
+                        NfForm::Nfc => s.nfc().collect::<String>(),
+                        NfForm::Nfd => s.nfd().collect(),
+                        NfForm::Nfkc => s.nfkc().collect(),
+                        NfForm::Nfkd => s.nfkd().collect(),
+                    })
+                })
+                .collect::<GenericStringArray<O>>(),
+        )
+    }
+
+    match array.data_type() {
+        DataType::Utf8 => normalize(array.as_string::<i32>(), form),
+        DataType::LargeUtf8 => normalize(array.as_string::<i64>(), form),
+        _ => array.clone(),
+    }
+}
+
+/// Get the input column of a target field, computing it if it has an expression.
+fn source_column(
+    batch: &RecordBatch,
+    field: &Field,
+    options: &CoercionOptions,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    #[cfg(feature = "datafusion")]
+    if let Some(expression) = options.expressions.get(field.name()) {
+        use super::expression::{evaluate_expression, plan_expression};
+        // The batches of a stream with varying schemas are planned one by one.
+        let schema = batch.schema();
+        let planned = options
+            .planned_expressions
+            .as_ref()
+            .and_then(|planned| planned.get(&schema, field.name()));
+        return match planned {
+            Some(planned) => evaluate_expression(batch, planned),
+            None => evaluate_expression(batch, &plan_expression(expression, &schema)?),
+        };
+    }
+    let schema = batch.schema();
+    if let Ok(index) = schema.index_of(field.name()) {
+        let source = schema.field(index);
+        let column = scaled_int_to_decimal(batch.column(index).clone(), source, field)?;
+        return reorder_dimensions(column, source, field);
+    }
+    match options.defaults.get(field.name()) {
+        Some(value) => repeat_value(value, batch.num_rows(), field),
+        None => Err(ArrowError::SchemaError(format!(
+            "Column {} not found",
+            field.name()
+        ))),
+    }
+}
+
+/// Repeat the value of a single-element array `len` times.
+fn repeat_value(
+    value: &Arc<dyn Array>,
+    len: usize,
+    field: &Field,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    if value.len() != 1 {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "Default value of column {} must have exactly one element, got {}",
+            field.name(),
+            value.len()
+        )));
+    }
+    take(value, &UInt32Array::from(vec![0; len]), None)
+}
+
+/// Apply one [PipelineStep] to a column, if it is configured for it.
+fn apply_pipeline_step(
+    step: PipelineStep,
+    array: Arc<dyn Array>,
+    field: &Field,
+    options: &CoercionOptions,
+    first_row: usize,
+) -> std::result::Result<Arc<dyn Array>, ArrowError> {
+    match step {
+        PipelineStep::Clamp => match options.clamp_ranges.get(field.name()) {
+            Some(range) => clamp_values(&array, field, *range),
+            None => Ok(array),
+        },
+        PipelineStep::Round => match options.round_decimals.get(field.name()) {
+            Some(decimals) => round_values(&array, field, *decimals),
+            None => Ok(array),
+        },
+        PipelineStep::Permute => match options.vector_permutations.get(field.name()) {
+            Some(permutation) => permute_vectors(&array, field, permutation),
+            None => Ok(array),
+        },
+        PipelineStep::Normalize => match options.unit_vector_tolerance {
+            Some(tolerance) => check_unit_vectors(
+                &array,
+                field,
+                tolerance,
+                options.normalize_unit_vectors,
+                options.zero_norm_policy,
+                first_row,
+            ),
+            None => Ok(array),
+        },
+        PipelineStep::SortLists if options.sort_list_columns.contains(field.name()) => {
+            sort_list_values(&array, field)
+        }
+        PipelineStep::SortLists => Ok(array),
+    }
+}
+
+fn coerce_schema_batch(
+    batch: RecordBatch,
+    schema: Arc<Schema>,
+    options: &CoercionOptions,
+    report: &mut CoercionReport,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let num_rows = batch.num_rows();
+    report.batches += 1;
+    report.rows += num_rows;
+    if let Some(limit) = options.max_rows {
+        if report.rows > limit {
+            return Err(ArrowError::ExternalError(Box::new(
+                Error::RowLimitExceeded { limit },
+            )));
+        }
+    }
+    if batch.schema() == schema && !options.rewrites_values() {
+        return Ok(batch);
+    }
+    if is_equivalent_schema(&batch.schema(), &schema, options) {
+        return RecordBatch::try_new(schema, batch.columns().to_vec());
+    }
+    if !options.drop_extra_columns {
+        let extra = batch
+            .schema()
+            .fields()
+            .iter()
+            .zip(batch.columns())
+            .find(|(f, c)| {
+                schema.field_with_name(f.name()).is_err()
+                    && !(options.drop_all_null_columns && is_all_null(c))
+            })
+            .map(|(f, _)| f.name().clone());
+        if let Some(extra) = extra {
+            return Err(ArrowError::SchemaError(format!(
+                "Column {} is not in the target schema",
+                extra
+            )));
+        }
+    }
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            if options.valid_length_column.as_ref() == Some(field.name()) {
+                return valid_lengths(&batch, &schema);
+            }
+            let column = &source_column(&batch, field, options)?;
+            if options.drop_all_null_columns && field.is_nullable() && is_all_null(column) {
+                return Ok(new_null_array(field.data_type(), column.len()));
+            }
+            let redacted = match options.redactions.get(field.name()) {
+                Some(policy) => redact(column, field, policy)?,
+                None => column.clone(),
+            };
+            let coerced = coerce_array(&redacted, field, options)?;
+            let coerced = clean_strings(&coerced, options);
+            #[cfg(feature = "unicode")]
+            let coerced = match options.unicode_form {
+                Some(form) => normalize_unicode(&coerced, form),
+                None => coerced,
+            };
+            let coerced = match options.max_string_bytes.get(field.name()) {
+                Some(limit) => limit_string_bytes(
+                    &coerced,
+                    field,
+                    *limit,
+                    options.oversized_strings,
+                    report.rows - num_rows,
+                )?,
+                None => coerced,
+            };
+            let coerced = check_non_nullable(coerced, field, options)?;
+            let coerced = options
+                .pipeline_order
+                .iter()
+                .try_fold(coerced, |coerced, step| {
+                    apply_pipeline_step(*step, coerced, field, options, report.rows - num_rows)
+                })?;
+            report.record(field.name(), column, &coerced);
+            if options.normalize_unit_vectors
+                && options.unit_vector_tolerance.is_some()
+                && options.zero_norm_policy == ZeroNormPolicy::Report
+            {
+                let zero_norms = count_zero_norms(&coerced)?;
+                if zero_norms > 0 {
+                    let column = report.columns.entry(field.name().clone()).or_default();
+                    column.zero_norm_vectors += zero_norms;
+                }
+            }
+            Ok(coerced)
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    let columns = align_column_lengths(&schema, columns, options.length_mismatch)?;
+    let batch = RecordBatch::try_new(schema, columns)?;
+    report.dropped_rows += num_rows.saturating_sub(batch.num_rows());
+    Ok(batch)
+}
+
+/// A type transition applied to one column, and the number of batches it was applied to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnTransition {
+    pub from: DataType,
+    pub to: DataType,
+    pub batches: usize,
+}
+
+/// What the coercion did to one column.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ColumnReport {
+    /// The type transitions applied to the column.
+    pub transitions: Vec<ColumnTransition>,
+    /// Number of rows whose type was changed.
+    pub rows_coerced: usize,
+    /// Number of cells that were not null before coercion, but are null after it.
+    pub nulled_cells: usize,
+    /// Number of batches in which the coercion might have lost precision.
+    pub precision_loss_events: usize,
+    /// Number of vectors left with a zero norm after normalization, see
+    /// [ZeroNormPolicy::Report].
+    pub zero_norm_vectors: usize,
+}
+
+/// An auditable record of a coercion stream, accumulated as the batches flow.
+///
+/// A summary of the report is logged at `info` level once the stream is drained.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CoercionReport {
+    /// Number of input batches.
+    pub batches: usize,
+    /// Number of input rows.
+    pub rows: usize,
+    /// Number of input rows that were dropped, e.g., by [LengthMismatch::Truncate].
+    pub dropped_rows: usize,
+    /// Per-column reports, keyed by column name so the summary is printed in a stable order.
+    pub columns: BTreeMap<String, ColumnReport>,
+}
+
+impl CoercionReport {
+    fn record(&mut self, column: &str, input: &Arc<dyn Array>, output: &Arc<dyn Array>) {
+        let nulled_cells = output.null_count().saturating_sub(input.null_count());
+        let (from, to) = (input.data_type(), output.data_type());
+        if from == to && nulled_cells == 0 {
+            return;
+        }
+        let report = self.columns.entry(column.to_string()).or_default();
+        report.nulled_cells += nulled_cells;
+        if from == to {
+            return;
+        }
+        report.rows_coerced += input.len();
+        if loses_precision(from, to) {
+            report.precision_loss_events += 1;
+        }
+        match report
+            .transitions
+            .iter_mut()
+            .find(|t| &t.from == from && &t.to == to)
+        {
+            Some(t) => t.batches += 1,
+            None => report.transitions.push(ColumnTransition {
+                from: from.clone(),
+                to: to.clone(),
+                batches: 1,
+            }),
+        }
+    }
+
+    /// Total number of precision-loss events across all columns.
+    pub fn precision_loss_events(&self) -> usize {
+        self.columns.values().map(|c| c.precision_loss_events).sum()
+    }
+
+    /// Total number of cells that were nulled across all columns.
+    pub fn nulled_cells(&self) -> usize {
+        self.columns.values().map(|c| c.nulled_cells).sum()
+    }
+
+    /// Total number of zero-norm vectors left by normalization across all columns.
+    pub fn zero_norm_vectors(&self) -> usize {
+        self.columns.values().map(|c| c.zero_norm_vectors).sum()
+    }
+}
+
+impl fmt::Display for CoercionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Coerced {} batches ({} rows)", self.batches, self.rows)?;
+        let columns = self
+            .columns
+            .iter()
+            .flat_map(|(name, report)| {
+                report.transitions.iter().map(move |t| {
+                    format!(
+                        "{}: {:?} -> {:?} ({} batches)",
+                        name, t.from, t.to, t.batches
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        if columns.is_empty() {
+            write!(f, ", no columns changed")?;
+        } else {
+            write!(f, ", columns [{}]", columns.join(", "))?;
+        }
+        let nulled_cells = self.nulled_cells();
+        if nulled_cells > 0 {
+            write!(f, ", {} cells nulled", nulled_cells)?;
+        }
+        if self.dropped_rows > 0 {
+            write!(f, ", {} rows dropped", self.dropped_rows)?;
+        }
+        let zero_norm_vectors = self.zero_norm_vectors();
+        if zero_norm_vectors > 0 {
+            write!(f, ", {} zero-norm vectors", zero_norm_vectors)?;
+        }
+        write!(f, ", {} warnings", self.precision_loss_events())
+    }
+}
+
+/// A [CoercionReport] that is shared with the reader that updates it.
+pub type SharedCoercionReport = Arc<Mutex<CoercionReport>>;
+
+fn schema_to_json(schema: &Schema) -> serde_json::Value {
+    schema
+        .fields()
+        .iter()
+        .map(|f| {
+            serde_json::json!({
+                "name": f.name(),
+                "type": type_name(f.data_type()),
+                "nullable": f.is_nullable(),
+            })
+        })
+        .collect()
+}
+
+/// Append one JSON line describing a coercion run to the audit log at `path`.
+fn write_audit_log(
+    path: &Path,
+    source: &Schema,
+    target: &Schema,
+    plan: &CoercionPlan,
+    report: &CoercionReport,
+) -> Result<()> {
+    let plan = plan
+        .fields
+        .iter()
+        .map(|f| {
+            serde_json::json!({
+                "name": f.name,
+                "from": f.from.as_ref().map(type_name),
+                "to": f.to.as_ref().map(type_name),
+                "action": f.action.to_string(),
+                "note": f.note,
+            })
+        })
+        .collect::<Vec<_>>();
+    let record = serde_json::json!({
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "source_schema": schema_to_json(source),
+        "target_schema": schema_to_json(target),
+        "plan": plan,
+        "batches": report.batches,
+        "rows": report.rows,
+        "dropped_rows": report.dropped_rows,
+    });
+    let append = || -> std::io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", record)
+    };
+    append().map_err(|e| Error::Store {
+        message: format!("Failed to write audit log {}: {}", path.display(), e),
+    })
+}
+
+/// Streaming reader that coerces each batch to the target schema, and logs the
+/// [CoercionReport] when the input is exhausted.
+struct CoerceSchemaReader<R: RecordBatchReader> {
+    reader: R,
+    source_schema: SchemaRef,
+    schema: SchemaRef,
+    options: CoercionOptions,
+    report: SharedCoercionReport,
+    first_schema: Option<SchemaRef>,
+    finished: bool,
+}
+
+impl<R: RecordBatchReader> CoerceSchemaReader<R> {
+    fn new(reader: R, schema: SchemaRef, options: CoercionOptions) -> Self {
+        Self {
+            source_schema: reader.schema(),
+            reader,
+            schema,
+            options,
+            report: Arc::new(Mutex::new(CoercionReport::default())),
+            first_schema: None,
+            finished: false,
+        }
+    }
+
+    fn check_stable_schema(&mut self, batch: &RecordBatch) -> std::result::Result<(), ArrowError> {
+        if !self.options.require_stable_schema {
+            return Ok(());
+        }
+        let Some(first) = &self.first_schema else {
+            self.first_schema = Some(batch.schema());
+            return Ok(());
+        };
+        if !is_structurally_equal(first, &batch.schema()) {
+            return Err(ArrowError::SchemaError(format!(
+                "Batch {} has schema {:?}, which differs from the schema of the first batch {:?}",
+                self.report.lock().unwrap().batches,
+                batch.schema(),
+                first
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<R: RecordBatchReader> Iterator for CoerceSchemaReader<R> {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.next() {
+            Some(batch) => Some(batch.and_then(|b| {
+                self.check_stable_schema(&b)?;
+                let mut report = self.report.lock().unwrap();
+                coerce_schema_batch(b, self.schema.clone(), &self.options, &mut report)
+            })),
+            None => {
+                if !self.finished {
+                    self.finished = true;
+                    let report = self.report.lock().unwrap();
+                    info!("{}", report);
+                    if let Some(path) = &self.options.audit_log {
+                        let plan = plan_coercion(&self.source_schema, &self.schema, &self.options);
+                        let written = write_audit_log(
+                            path,
+                            &self.source_schema,
+                            &self.schema,
+                            &plan,
+                            &report,
+                        );
+                        if let Err(e) = written {
+                            return Some(Err(ArrowError::ExternalError(Box::new(e))));
+                        }
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+impl<R: RecordBatchReader> RecordBatchReader for CoerceSchemaReader<R> {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+/// What the coercion does to one field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldAction {
+    /// The field already has the target type.
+    Keep,
+    /// The field is coerced to the target type.
+    Coerce,
+    /// The source field is not in the target schema, and is dropped.
+    Drop,
+    /// The target field is not in the source schema.
+    Missing,
+    /// The coercion is rejected by the options.
+    Reject,
+}
+
+impl fmt::Display for FieldAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Self::Keep => "keep",
+            Self::Coerce => "coerce",
+            Self::Drop => "drop",
+            Self::Missing => "missing",
+            Self::Reject => "reject",
+        };
+        f.pad(label)
+    }
+}
+
+/// The planned coercion of one field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldPlan {
+    pub name: String,
+    /// The source type, if the field is in the source schema.
+    pub from: Option<DataType>,
+    /// The target type, if the field is in the target schema.
+    pub to: Option<DataType>,
+    pub action: FieldAction,
+    pub note: Option<String>,
+}
+
+/// A preview of what [coerce_schema_with_options] would do to each field, without
+/// reading any data.
+///
+/// The [fmt::Display] implementation renders the plan as a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoercionPlan {
+    /// The plan of the target fields, in target order, followed by the dropped fields.
+    pub fields: Vec<FieldPlan>,
+}
+
+impl CoercionPlan {
+    /// Whether the coercion is expected to fail.
+    pub fn has_errors(&self) -> bool {
+        self.fields
+            .iter()
+            .any(|f| matches!(f.action, FieldAction::Missing | FieldAction::Reject))
+    }
+
+    /// Render the plan as a table, one row per field.
+    pub fn to_table_string(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// A compact rendering of a type, e.g., `FixedSizeList<Float32, 128>`.
+fn type_name(dt: &DataType) -> String {
+    match dt {
+        DataType::List(f) => format!("List<{}>", type_name(f.data_type())),
+        DataType::LargeList(f) => format!("LargeList<{}>", type_name(f.data_type())),
+        DataType::FixedSizeList(f, dim) => {
+            format!("FixedSizeList<{}, {}>", type_name(f.data_type()), dim)
+        }
+        DataType::Struct(fields) => format!(
+            "Struct<{}>",
+            fields
+                .iter()
+                .map(|f| format!("{}: {}", f.name(), type_name(f.data_type())))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        dt => format!("{:?}", dt),
+    }
+}
+
+impl fmt::Display for CoercionPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = ["name", "from", "to", "action", "note"];
+        let rows = self
+            .fields
+            .iter()
+            .map(|p| {
+                [
+                    p.name.clone(),
+                    p.from.as_ref().map(type_name).unwrap_or("-".to_string()),
+                    p.to.as_ref().map(type_name).unwrap_or("-".to_string()),
+                    p.action.to_string(),
+                    p.note.clone().unwrap_or_default(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let mut widths = header.map(|h| h.chars().count());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let write_row = |f: &mut fmt::Formatter<'_>, cells: &[&str]| {
+            let line = cells
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(f, "{}", line.trim_end())
+        };
+        write_row(f, &header)?;
+        let rule = widths.map(|w| "-".repeat(w));
+        write_row(f, &rule.iter().map(String::as_str).collect::<Vec<_>>())?;
+        for row in &rows {
+            write_row(f, &row.iter().map(String::as_str).collect::<Vec<_>>())?;
+        }
+        Ok(())
+    }
+}
+
+/// Plan the coercion of data with the `source` schema to the `target` schema.
+///
+/// This is a dry run of [coerce_schema_with_options]: the plan only depends on the
+/// schemas, so value-dependent failures, e.g., overflows, are not detected.
+pub fn plan_coercion(source: &Schema, target: &Schema, options: &CoercionOptions) -> CoercionPlan {
+    let mut fields = target
+        .fields()
+        .iter()
+        .map(|to| {
+            let Ok(from) = source.field_with_name(to.name()) else {
+                if options.defaults.contains_key(to.name()) {
+                    return FieldPlan {
+                        name: to.name().to_string(),
+                        from: None,
+                        to: Some(to.data_type().clone()),
+                        action: FieldAction::Coerce,
+                        note: Some("filled with the default".to_string()),
+                    };
+                }
+                return FieldPlan {
+                    name: to.name().to_string(),
+                    from: None,
+                    to: Some(to.data_type().clone()),
+                    action: FieldAction::Missing,
+                    note: Some("not in the source schema".to_string()),
+                };
+            };
+            let (from_type, to_type) = (from.data_type(), to.data_type());
+            let (action, note) = if from_type == to_type {
+                (FieldAction::Keep, None)
+            } else if is_integer_narrowing(from_type, to_type) {
+                match options.integer_narrowing {
+                    NarrowingPolicy::Error => (
+                        FieldAction::Reject,
+                        Some("integer narrowing is not allowed".to_string()),
+                    ),
+                    NarrowingPolicy::CheckValues => (
+                        FieldAction::Coerce,
+                        Some("integer narrowing, fails on overflow".to_string()),
+                    ),
+                    _ => (
+                        FieldAction::Coerce,
+                        Some("integer narrowing, overflows become null".to_string()),
+                    ),
+                }
+            } else if loses_precision(from_type, to_type) {
+                (
+                    FieldAction::Coerce,
+                    Some("might lose precision".to_string()),
+                )
+            } else {
+                (FieldAction::Coerce, None)
+            };
+            FieldPlan {
+                name: to.name().to_string(),
+                from: Some(from_type.clone()),
+                to: Some(to_type.clone()),
+                action,
+                note,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    fields.extend(
+        source
+            .fields()
+            .iter()
+            .filter(|f| target.field_with_name(f.name()).is_err())
+            .map(|from| {
+                let (action, note) = if options.drop_extra_columns {
+                    (FieldAction::Drop, None)
+                } else {
+                    (
+                        FieldAction::Reject,
+                        Some("not in the target schema".to_string()),
+                    )
+                };
+                FieldPlan {
+                    name: from.name().to_string(),
+                    from: Some(from.data_type().clone()),
+                    to: None,
+                    action,
+                    note,
+                }
+            }),
+    );
+    CoercionPlan { fields }
+}
+
+/// How a field drifted from the baseline schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriftKind {
+    /// The field is not in the baseline schema.
+    Added,
+    /// The baseline field is missing.
+    Removed,
+    /// The field has another type than in the baseline schema.
+    Retyped,
+    /// The vector column has another dimension than in the baseline schema.
+    DimensionChanged,
+}
+
+/// How severe a drift is, from the point of view of coercing the data to the baseline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DriftSeverity {
+    /// The drift does not affect the coercion to the baseline, e.g., an added field.
+    Info,
+    /// The data coerces to the baseline, but its values may not, e.g., a retyped field.
+    Warning,
+    /// The data does not coerce to the baseline.
+    Critical,
+}
+
+/// The drift of one field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDrift {
+    pub name: String,
+    pub kind: DriftKind,
+    pub severity: DriftSeverity,
+    /// The type in the baseline schema, if the field is in it.
+    pub baseline: Option<DataType>,
+    /// The current type, if the field is in the current schema.
+    pub current: Option<DataType>,
+}
+
+/// The fields of a schema that drifted from a baseline schema.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DriftReport {
+    pub fields: Vec<FieldDrift>,
+}
+
+impl DriftReport {
+    /// Whether the schema did not drift.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The severity of the most severe drift, if any.
+    pub fn max_severity(&self) -> Option<DriftSeverity> {
+        self.fields.iter().map(|f| f.severity).max()
+    }
+}
+
+/// Detect how the schema of incoming data drifted from a `baseline` schema, e.g., to
+/// monitor an ingestion pipeline.
+///
+/// The fields are matched like [plan_coercion] does, with the default [CoercionOptions].
+/// A retyped field is only critical if [plan_coercion] rejects it.
+pub fn detect_drift(reader_schema: &Schema, baseline: &Schema) -> DriftReport {
+    let options = CoercionOptions::default();
+    let fields = plan_coercion(reader_schema, baseline, &options)
+        .fields
+        .into_iter()
+        .filter_map(|plan| {
+            let (kind, severity) = match (&plan.action, &plan.from, &plan.to) {
+                (FieldAction::Keep, _, _) => return None,
+                (FieldAction::Drop, _, _) => (DriftKind::Added, DriftSeverity::Info),
+                (FieldAction::Missing, _, _) => (DriftKind::Removed, DriftSeverity::Critical),
+                (
+                    _,
+                    Some(DataType::FixedSizeList(_, dim)),
+                    Some(DataType::FixedSizeList(_, baseline_dim)),
+                ) if dim != baseline_dim => (DriftKind::DimensionChanged, DriftSeverity::Critical),
+                (FieldAction::Reject, _, _) => (DriftKind::Retyped, DriftSeverity::Critical),
+                _ => (DriftKind::Retyped, DriftSeverity::Warning),
+            };
+            Some(FieldDrift {
+                name: plan.name,
+                kind,
+                severity,
+                baseline: plan.to,
+                current: plan.from,
+            })
+        })
+        .collect();
+    DriftReport { fields }
+}
+
+/// Coerce the reader (input data) to match the given [Schema].
+///
+/// A summary of the applied coercions is logged at `info` level once the
+/// returned reader is fully drained.
+pub fn coerce_schema(
+    reader: impl RecordBatchReader + Send + 'static,
+    schema: Arc<Schema>,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    coerce_schema_with_options(reader, schema, CoercionOptions::default())
+}
+
+/// Coerce the reader (input data) to match the given [Schema], with [CoercionOptions].
+pub fn coerce_schema_with_options(
+    reader: impl RecordBatchReader + Send + 'static,
+    schema: Arc<Schema>,
+    options: CoercionOptions,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    check_declared_dimensions(&schema)?;
+    check_dimension_divisor(&schema, &options)?;
+    check_max_string_bytes(&schema, &options)?;
+    check_max_vector_dim(&reader.schema(), &options)?;
+    check_max_vector_dim(&schema, &options)?;
+    // The row limit, the valid lengths and the audit log are handled by the coercion
+    // stream, so it needs every batch.
+    let counts_rows = options.max_rows.is_some()
+        || options.valid_length_column.is_some()
+        || options.audit_log.is_some()
+        || options.require_stable_schema;
+    if reader.schema() == schema && !options.rewrites_values() && !counts_rows {
+        // Nothing to coerce: pass the reader through without touching any batch.
+        return Ok(Box::new(reader));
+    }
+    if is_equivalent_schema(&reader.schema(), &schema, &options) && !counts_rows {
+        let s = schema.clone();
+        let batches =
+            reader.map(move |batch| RecordBatch::try_new(s.clone(), batch?.columns().to_vec()));
+        return Ok(Box::new(RecordBatchIterator::new(batches, schema)));
+    }
+    let (reader, _) = coerce_schema_with_report(reader, schema, options)?;
+    Ok(reader)
+}
+
+/// Field metadata key marking a numeric or vector column of a batch as big-endian, when
+/// set to `big`.
+pub const BYTE_ORDER_METADATA_KEY: &str = "lance:byte_order";
+
+/// Swap the byte order of the columns of the batch marked as big-endian.
+fn swap_big_endian_columns(batch: RecordBatch) -> std::result::Result<RecordBatch, ArrowError> {
+    let schema = batch.schema();
+    if !schema.fields().iter().any(|f| {
+        f.metadata()
+            .get(BYTE_ORDER_METADATA_KEY)
+            .map(String::as_str)
+            == Some("big")
+    }) {
+        return Ok(batch);
+    }
+    let mut fields = vec![];
+    let mut columns = vec![];
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        if field
+            .metadata()
+            .get(BYTE_ORDER_METADATA_KEY)
+            .map(String::as_str)
+            == Some("big")
+        {
+            let mut metadata = field.metadata().clone();
+            metadata.remove(BYTE_ORDER_METADATA_KEY);
+            fields.push(Arc::new(field.as_ref().clone().with_metadata(metadata)));
+            columns.push(byteswap_array(column)?);
+        } else {
+            fields.push(field.clone());
+            columns.push(column.clone());
+        }
+    }
+    let schema = Schema::new_with_metadata(fields, schema.metadata().clone());
+    RecordBatch::try_new(Arc::new(schema), columns)
+}
+
+/// Coerce a stream whose batches may not share a schema to match the given [Schema],
+/// e.g., a stream merged from several sources with different float precisions.
+///
+/// Unlike [coerce_schema_with_options], the schema declared by the reader is not trusted:
+/// every batch is coerced on its own, from its own schema. The columns of a batch marked
+/// with [BYTE_ORDER_METADATA_KEY] are byte swapped before they are coerced.
+pub fn normalize_stream(
+    reader: impl RecordBatchReader + Send + 'static,
+    schema: Arc<Schema>,
+    options: CoercionOptions,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    check_declared_dimensions(&schema)?;
+    check_dimension_divisor(&schema, &options)?;
+    check_max_string_bytes(&schema, &options)?;
+    check_max_vector_dim(&schema, &options)?;
+    let source_schema = reader.schema();
+    let options = options.plan_expressions(&source_schema)?;
+    let batches = reader.map(|batch| swap_big_endian_columns(batch?));
+    let reader = RecordBatchIterator::new(batches, source_schema);
+    Ok(Box::new(CoerceSchemaReader::new(reader, schema, options)))
+}
+
+/// Coerce the reader (input data) to match the schema of an existing Lance [Dataset],
+/// e.g., before appending to it.
+///
+/// The dimensions of the vector columns are checked upfront when the input column is
+/// already a vector column, and as the batches flow for list columns.
+pub fn coerce_to_dataset_schema(
+    reader: impl RecordBatchReader + Send + 'static,
+    dataset: &Dataset,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema: SchemaRef = Arc::new(dataset.schema().into());
+    for field in schema.fields() {
+        let DataType::FixedSizeList(_, dim) = field.data_type() else {
+            continue;
+        };
+        if let Ok(DataType::FixedSizeList(_, input_dim)) = reader
+            .schema()
+            .field_with_name(field.name())
+            .map(|f| f.data_type())
+        {
+            if input_dim != dim {
+                return Err(Error::Schema {
+                    message: format!(
+                        "Vector column {} has dimension {}, but the dataset expects {}",
+                        field.name(),
+                        input_dim,
+                        dim
+                    ),
+                });
+            }
+        }
+    }
+    coerce_schema(reader, schema)
+}
+
+/// Coerce the reader (input data) to match the given [Schema], and return a
+/// [CoercionReport] alongside the coerced reader.
+///
+/// The report is updated as the batches flow, and is complete once the reader is drained.
+pub fn coerce_schema_with_report(
+    reader: impl RecordBatchReader + Send + 'static,
+    schema: Arc<Schema>,
+    options: CoercionOptions,
+) -> Result<(Box<dyn RecordBatchReader + Send>, SharedCoercionReport)> {
+    check_declared_dimensions(&schema)?;
+    check_dimension_divisor(&schema, &options)?;
+    check_max_string_bytes(&schema, &options)?;
+    check_max_vector_dim(&reader.schema(), &options)?;
+    check_max_vector_dim(&schema, &options)?;
+    // All-null extra columns can only be told apart once the batches are read.
+    if !options.drop_extra_columns && !options.drop_all_null_columns {
+        if let Some(extra) = find_extra_column(&reader.schema(), &schema) {
+            return Err(Error::Schema {
+                message: format!("Column {} is not in the target schema", extra),
+            });
+        }
+    }
+    let schema = annotate_source_types(&reader.schema(), schema, &options);
+    let schema = add_valid_length_field(&reader.schema(), schema, &options)?;
+    let options = options.plan_expressions(&reader.schema())?;
+    let reader = CoerceSchemaReader::new(reader, schema, options);
+    let report = reader.report.clone();
+    Ok((Box::new(reader), report))
+}
+
+/// The column of a rejects reader that holds why each row was rejected.
+pub const REJECT_REASON_COLUMN: &str = "_coercion_error";
+
+/// The coercion shared by the good and the rejects readers of
+/// [coerce_schema_with_rejects], which buffers the batches one reader has not read yet.
+struct RejectingCoercion {
+    reader: Box<dyn RecordBatchReader + Send>,
+    schema: SchemaRef,
+    rejects_schema: SchemaRef,
+    options: CoercionOptions,
+    report: CoercionReport,
+    good: VecDeque<std::result::Result<RecordBatch, ArrowError>>,
+    rejects: VecDeque<std::result::Result<RecordBatch, ArrowError>>,
+    finished: bool,
+}
+
+impl RejectingCoercion {
+    /// Coerce the next input batch, or return false once the input is exhausted.
+    fn advance(&mut self) -> bool {
+        if self.finished {
+            return false;
+        }
+        match self.reader.next() {
+            Some(Ok(batch)) => self.coerce(batch),
+            Some(Err(e)) => self.good.push_back(Err(e)),
+            None => {
+                self.finished = true;
+                info!("{}", self.report);
+                return false;
+            }
+        }
+        true
+    }
+
+    fn coerce(&mut self, batch: RecordBatch) {
+        // The codes assigned to the labels of the rejected rows are taken back.
+        let codes = match self.options.on_unknown_label {
+            UnknownLabel::AssignNew => self
+                .options
+                .label_codes
+                .values()
+                .map(|codes| (codes.clone(), codes.lock().unwrap().clone()))
+                .collect(),
+            _ => vec![],
+        };
+        let restore_codes = || {
+            for (codes, snapshot) in &codes {
+                *codes.lock().unwrap() = snapshot.clone();
+            }
+        };
+
+        let mut report = self.report.clone();
+        let err = match coerce_schema_batch(
+            batch.clone(),
+            self.schema.clone(),
+            &self.options,
+            &mut report,
+        ) {
+            Ok(coerced) => {
+                self.report = report;
+                self.good.push_back(Ok(coerced));
+                return;
+            }
+            Err(e) => e,
+        };
+        restore_codes();
+        // Errors that do not depend on the values of the rows fail the stream.
+        if let Err(e) = self.check_batch_columns(&batch.schema()) {
+            self.good.push_back(Err(e));
+            return;
+        }
+        if is_batch_error(&err) || batch.num_rows() == 0 {
+            self.good.push_back(Err(err));
+            return;
+        }
+
+        // Bisect the slices that fail, to tell the good rows from the rejected ones without
+        // coercing every row on its own.
+        let mut rejected = vec![];
+        let mut reasons = vec![];
+        let mut slices = vec![(0, batch.num_rows())];
+        while let Some((offset, length)) = slices.pop() {
+            match self.try_coerce(&batch, offset, length) {
+                Ok(()) => {}
+                Err(e) if length == 1 => {
+                    rejected.push(offset as u32);
+                    reasons.push(e.to_string());
+                }
+                Err(_) => {
+                    let half = length / 2;
+                    slices.push((offset + half, length - half));
+                    slices.push((offset, half));
+                }
+            }
+        }
+        restore_codes();
+
+        // Coerce the good rows together, now that the rejected ones are known.
+        let good = (0..batch.num_rows() as u32)
+            .filter(|row| rejected.binary_search(row).is_err())
+            .collect::<UInt32Array>();
+        let good = take_batch(&batch, &good).and_then(|good| {
+            coerce_schema_batch(good, self.schema.clone(), &self.options, &mut self.report)
+        });
+        match good {
+            Ok(good) if good.num_rows() == 0 => {}
+            good => self.good.push_back(good),
+        }
+        if rejected.is_empty() {
+            return;
+        }
+        let rejects = take_batch(&batch, &UInt32Array::from(rejected)).and_then(|rejects| {
+            let mut columns = rejects.columns().to_vec();
+            columns.push(Arc::new(StringArray::from(reasons)));
+            RecordBatch::try_new(self.rejects_schema.clone(), columns)
+        });
+        self.rejects.push_back(rejects);
+    }
+
+    /// Check that the batch has a source for every target column, and no column out of the
+    /// target schema, which would fail all of its rows alike.
+    fn check_batch_columns(&self, input: &Schema) -> std::result::Result<(), ArrowError> {
+        let options = &self.options;
+        if !options.drop_extra_columns && !options.drop_all_null_columns {
+            if let Some(extra) = find_extra_column(input, &self.schema) {
+                return Err(ArrowError::SchemaError(format!(
+                    "Column {} is not in the target schema",
+                    extra
+                )));
+            }
+        }
+        for field in self.schema.fields() {
+            #[cfg(feature = "datafusion")]
+            if options.expressions.contains_key(field.name()) {
+                continue;
+            }
+            if input.field_with_name(field.name()).is_err()
+                && options.valid_length_column.as_ref() != Some(field.name())
+                && !options.defaults.contains_key(field.name())
+            {
+                return Err(ArrowError::SchemaError(format!(
+                    "Column {} not found",
+                    field.name()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Coerce a slice of the batch to find out whether it has rows to reject, leaving the
+    /// report as it is.
+    fn try_coerce(
+        &self,
+        batch: &RecordBatch,
+        offset: usize,
+        length: usize,
+    ) -> std::result::Result<(), ArrowError> {
+        // The row numbers of the errors count the rows before the slice.
+        let mut report = CoercionReport {
+            rows: self.report.rows + offset,
+            ..Default::default()
+        };
+        coerce_schema_batch(
+            batch.slice(offset, length),
+            self.schema.clone(),
+            &self.options,
+            &mut report,
+        )
+        .map(|_| ())
+    }
+}
+
+/// Whether the coercion of a batch failed as a whole, e.g., over the row limit, rather
+/// than because of the values of some of its rows.
+fn is_batch_error(err: &ArrowError) -> bool {
+    let ArrowError::ExternalError(err) = err else {
+        return false;
+    };
+    matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::RowLimitExceeded { .. } | Error::ColumnLengthMismatch { .. })
+    )
+}
+
+/// Take the rows at `indices` of every column of the batch.
+fn take_batch(
+    batch: &RecordBatch,
+    indices: &UInt32Array,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let columns = batch
+        .columns()
+        .iter()
+        .map(|c| take(c, indices, None))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    RecordBatch::try_new_with_options(
+        batch.schema(),
+        columns,
+        &RecordBatchOptions::new().with_row_count(Some(indices.len())),
+    )
+}
+
+/// One of the two readers of [coerce_schema_with_rejects].
+struct RejectsSplitReader {
+    coercion: Arc<Mutex<RejectingCoercion>>,
+    rejects: bool,
+}
+
+impl Iterator for RejectsSplitReader {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut coercion = self.coercion.lock().unwrap();
+        loop {
+            let queue = if self.rejects {
+                &mut coercion.rejects
+            } else {
+                &mut coercion.good
+            };
+            if let Some(batch) = queue.pop_front() {
+                return Some(batch);
+            }
+            if !coercion.advance() {
+                return None;
+            }
+        }
+    }
+}
+
+impl RecordBatchReader for RejectsSplitReader {
+    fn schema(&self) -> SchemaRef {
+        let coercion = self.coercion.lock().unwrap();
+        if self.rejects {
+            coercion.rejects_schema.clone()
+        } else {
+            coercion.schema.clone()
+        }
+    }
+}
+
+/// Coerce the reader (input data) to match the given [Schema], routing the rows that can
+/// not be coerced to a separate rejects reader instead of failing the batch.
+///
+/// Returns the reader of the coerced rows, and the reader of the rejected rows, which
+/// keep the input schema plus a [REJECT_REASON_COLUMN] with the coercion error of each row.
+/// Errors that fail every row of a batch alike, e.g., a missing column or exceeding
+/// [CoercionOptions::max_rows], are returned by the reader of the coerced rows instead.
+/// The readers share the input, so the batches of one reader are buffered until read,
+/// and both readers should be drained.
+pub fn coerce_schema_with_rejects(
+    reader: impl RecordBatchReader + Send + 'static,
+    schema: Arc<Schema>,
+    options: CoercionOptions,
+) -> Result<(
+    Box<dyn RecordBatchReader + Send>,
+    Box<dyn RecordBatchReader + Send>,
+)> {
+    check_declared_dimensions(&schema)?;
+    check_dimension_divisor(&schema, &options)?;
+    check_max_string_bytes(&schema, &options)?;
+    check_max_vector_dim(&reader.schema(), &options)?;
+    check_max_vector_dim(&schema, &options)?;
+    let input_schema = reader.schema();
+    if !options.drop_extra_columns && !options.drop_all_null_columns {
+        if let Some(extra) = find_extra_column(&input_schema, &schema) {
+            return Err(Error::Schema {
+                message: format!("Column {} is not in the target schema", extra),
+            });
+        }
+    }
+    if input_schema.field_with_name(REJECT_REASON_COLUMN).is_ok() {
+        return Err(Error::Schema {
+            message: format!(
+                "Column {} is reserved for rejected rows",
+                REJECT_REASON_COLUMN
+            ),
+        });
+    }
+    let mut fields = input_schema.fields().to_vec();
+    fields.push(Arc::new(Field::new(
+        REJECT_REASON_COLUMN,
+        DataType::Utf8,
+        false,
+    )));
+    let rejects_schema = Arc::new(Schema::new_with_metadata(
+        fields,
+        input_schema.metadata().clone(),
+    ));
+    let schema = annotate_source_types(&input_schema, schema, &options);
+    let schema = add_valid_length_field(&input_schema, schema, &options)?;
+    let options = options.plan_expressions(&input_schema)?;
+    let coercion = Arc::new(Mutex::new(RejectingCoercion {
+        reader: Box::new(reader),
+        schema,
+        rejects_schema,
+        options,
+        report: CoercionReport::default(),
+        good: VecDeque::new(),
+        rejects: VecDeque::new(),
+        finished: false,
+    }));
+    let good = RejectsSplitReader {
+        coercion: coercion.clone(),
+        rejects: false,
+    };
+    let rejects = RejectsSplitReader {
+        coercion,
+        rejects: true,
+    };
+    Ok((Box::new(good), Box::new(rejects)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Once,
+    };
+
+    use arrow::buffer::NullBuffer;
+    use arrow_array::{
+        builder::{Int32Builder, ListBuilder, MapBuilder, StringBuilder, StringDictionaryBuilder},
+        BinaryArray, Date32Array, Decimal128Array, DictionaryArray, DurationMillisecondArray,
+        FixedSizeBinaryArray, FixedSizeListArray, Float16Array, Float32Array, Float64Array,
+        Int16Array, Int32Array, Int64Array, Int8Array, LargeBinaryArray, RecordBatch,
+        RecordBatchIterator, StringArray, StructArray, TimestampMicrosecondArray,
+        TimestampMillisecondArray, TimestampSecondArray, UInt16Array,
+    };
+    use arrow_schema::Field;
+    use half::f16;
+    use lance::arrow::FixedSizeListArrayExt;
+    use log::{LevelFilter, Log, Metadata, Record};
+
+    static LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct CaptureLogger;
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            LOGS.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn capture_logs() {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&CaptureLogger).unwrap();
+            log::set_max_level(LevelFilter::Info);
+        });
+    }
+
+    fn captured(pattern: &str) -> Vec<String> {
+        LOGS.lock()
+            .unwrap()
+            .iter()
+            .filter(|l| l.contains(pattern))
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn test_coerce_list_to_fixed_size_list() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                "fl",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 64),
+                true,
+            ),
+            Field::new("s", DataType::Utf8, true),
+            Field::new("f", DataType::Float16, true),
+            Field::new("i", DataType::Int32, true),
+        ]));
+
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(
+                    FixedSizeListArray::try_new_from_values(
+                        Float32Array::from_iter_values((0..256).map(|v| v as f32)),
+                        64,
+                    )
+                    .unwrap(),
+                ),
+                Arc::new(StringArray::from(vec![
+                    Some("hello"),
+                    Some("world"),
+                    Some("from"),
+                    Some("lance"),
+                ])),
+                Arc::new(Float16Array::from_iter_values(
+                    (0..4).map(|v| f16::from_f32(v as f32)),
+                )),
+                Arc::new(Int32Array::from_iter_values(0..4)),
+            ],
+        )
+        .unwrap();
+        let reader =
+            RecordBatchIterator::new(vec![batch.clone()].into_iter().map(Ok), schema.clone());
+
+        let expected_schema = Arc::new(Schema::new(vec![
+            Field::new(
+                "fl",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float16, true)), 64),
+                true,
+            ),
+            Field::new("s", DataType::Utf8, true),
+            Field::new("f", DataType::Float64, true),
+            Field::new("i", DataType::Int8, true),
+        ]));
+        let stream = coerce_schema(reader, expected_schema.clone()).unwrap();
+        let batches = stream.collect::<Vec<_>>();
+        assert_eq!(batches.len(), 1);
+        let batch = batches[0].as_ref().unwrap();
+        assert_eq!(batch.schema(), expected_schema);
+
+        let expected = RecordBatch::try_new(
+            expected_schema,
+            vec![
+                Arc::new(
+                    FixedSizeListArray::try_new_from_values(
+                        Float16Array::from_iter_values((0..256).map(|v| f16::from_f32(v as f32))),
+                        64,
+                    )
+                    .unwrap(),
+                ),
+                Arc::new(StringArray::from(vec![
                     Some("hello"),
                     Some("world"),
                     Some("from"),
                     Some("lance"),
                 ])),
-                Arc::new(Float16Array::from_iter_values(
-                    (0..4).map(|v| f16::from_f32(v as f32)),
-                )),
-                Arc::new(Int32Array::from_iter_values(0..4)),
+                Arc::new(Float64Array::from_iter_values((0..4).map(|v| v as f64))),
+                Arc::new(Int8Array::from_iter_values(0..4)),
+            ],
+        )
+        .unwrap();
+        assert_eq!(batch, &expected);
+    }
+
+    #[test]
+    fn test_coerce_sliced_list_to_fixed_size_list() {
+        let lists: Arc<dyn Array> =
+            Arc::new(ListArray::from_iter_primitive::<Float64Type, _, _>(vec![
+                Some(vec![Some(1.0), Some(2.0)]),
+                Some(vec![Some(3.0), Some(4.0)]),
+                Some(vec![Some(5.0), Some(6.0)]),
+            ]));
+        let field = Field::new(
+            "vec",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+            true,
+        );
+        let vectors =
+            coerce_array(&lists.slice(1, 2), &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(
+            vectors
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float32Type>()
+                .values(),
+            &[3.0, 4.0, 5.0, 6.0]
+        );
+    }
+
+    #[test]
+    fn test_report_precision_of_non_primitive_columns() {
+        let schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from(vec!["1.5", "2"]))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let target = Arc::new(Schema::new(vec![Field::new("s", DataType::Float32, true)]));
+        let (stream, report) =
+            coerce_schema_with_report(reader, target, CoercionOptions::default()).unwrap();
+        stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let report = report.lock().unwrap();
+        assert_eq!(report.columns["s"].rows_coerced, 2);
+        assert_eq!(report.precision_loss_events(), 0);
+    }
+
+    #[test]
+    fn test_emit_valid_length_column() {
+        let input = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "vec",
+                DataType::List(Arc::new(Field::new("item", DataType::Float64, true))),
+                true,
+            ),
+        ]));
+        let target = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 3),
+                true,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            input.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4])),
+                Arc::new(ListArray::from_iter_primitive::<Float64Type, _, _>(vec![
+                    Some(vec![Some(1.0), Some(2.0), Some(3.0)]),
+                    Some(vec![Some(4.0)]),
+                    None,
+                    Some(vec![Some(5.0), Some(6.0), Some(7.0), Some(8.0), Some(9.0)]),
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], input.clone());
+        let mut stream = coerce_schema(reader, target.clone()).unwrap();
+        assert!(stream.next().unwrap().is_err());
+
+        let options = CoercionOptions::new().emit_valid_length_column(Some("valid_len".into()));
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], input.clone());
+        let stream = coerce_schema_with_options(reader, target.clone(), options.clone()).unwrap();
+        assert_eq!(stream.schema().fields().len(), 3);
+        assert_eq!(stream.schema().field(2).data_type(), &DataType::UInt32);
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let vectors = batches[0].column(1).as_fixed_size_list();
+        assert_eq!(
+            vectors
+                .values()
+                .as_primitive::<Float32Type>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![
+                Some(1.0),
+                Some(2.0),
+                Some(3.0),
+                Some(4.0),
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(5.0),
+                Some(6.0),
+                Some(7.0),
+            ]
+        );
+        assert!(vectors.is_null(2));
+        assert_eq!(
+            batches[0].column(2).as_primitive::<UInt32Type>(),
+            &UInt32Array::from(vec![Some(3), Some(1), None, Some(5)])
+        );
+
+        // The lengths are ambiguous without a list column coerced to a vector column.
+        let reader = RecordBatchIterator::new(vec![], target.clone());
+        assert!(coerce_schema_with_options(reader, target, options).is_err());
+    }
+
+    #[test]
+    fn test_coerce_summary_logged_after_last_batch() {
+        capture_logs();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("summary_f", DataType::Float32, true),
+            Field::new("summary_i", DataType::Int32, true),
+        ]));
+        let batches = (0..2)
+            .map(|_| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Float32Array::from_iter_values((0..4).map(|v| v as f32))),
+                        Arc::new(Int32Array::from_iter_values(0..4)),
+                    ],
+                )
+            })
+            .collect::<Vec<_>>();
+        let reader = RecordBatchIterator::new(batches, schema);
+
+        let expected_schema = Arc::new(Schema::new(vec![
+            Field::new("summary_f", DataType::Float16, true),
+            Field::new("summary_i", DataType::Int32, true),
+        ]));
+        let mut stream = coerce_schema(reader, expected_schema).unwrap();
+        assert!(stream.next().unwrap().is_ok());
+        assert!(stream.next().unwrap().is_ok());
+        assert!(captured("summary_f:").is_empty());
+
+        assert!(stream.next().is_none());
+        assert_eq!(
+            captured("summary_f:"),
+            vec![
+                "Coerced 2 batches (8 rows), columns [summary_f: Float32 -> Float16 (2 batches)], \
+                 2 warnings"
+                    .to_string()
+            ]
+        );
+
+        // Draining again does not log a second summary.
+        assert!(stream.next().is_none());
+        assert_eq!(captured("summary_f:").len(), 1);
+    }
+
+    #[test]
+    fn test_coerce_half_float_rounding() {
+        let f16_field = Field::new("h", DataType::Float16, true);
+
+        // Round to nearest, ties to even: 2049 lies halfway between 2048 and 2050.
+        let arr: Arc<dyn Array> = Arc::new(Float32Array::from(vec![
+            Some(2049.0),
+            Some(2051.0),
+            Some(0.1),
+            Some(1e6),
+            None,
+        ]));
+        let coerced = coerce_array(&arr, &f16_field, &CoercionOptions::default()).unwrap();
+        let expected = Float16Array::from(vec![
+            Some(f16::from_f32(2048.0)),
+            Some(f16::from_f32(2052.0)),
+            Some(f16::from_bits(0x2e66)),
+            Some(f16::INFINITY),
+            None,
+        ]);
+        assert_eq!(coerced.as_primitive::<Float16Type>(), &expected);
+
+        // f64 is rounded directly, not through f32.
+        let arr: Arc<dyn Array> = Arc::new(Float64Array::from(vec![0.1, 65504.0, 65520.0]));
+        let coerced = coerce_array(&arr, &f16_field, &CoercionOptions::default()).unwrap();
+        let expected = Float16Array::from(vec![f16::from_bits(0x2e66), f16::MAX, f16::INFINITY]);
+        assert_eq!(coerced.as_primitive::<Float16Type>(), &expected);
+
+        let arr: Arc<dyn Array> = Arc::new(Int32Array::from(vec![Some(2049), Some(-3), None]));
+        let coerced = coerce_array(&arr, &f16_field, &CoercionOptions::default()).unwrap();
+        let expected = Float16Array::from(vec![
+            Some(f16::from_f32(2048.0)),
+            Some(f16::from_f32(-3.0)),
+            None,
+        ]);
+        assert_eq!(coerced.as_primitive::<Float16Type>(), &expected);
+
+        // f16 to integer truncates towards zero.
+        let arr: Arc<dyn Array> = Arc::new(Float16Array::from(vec![
+            f16::from_f32(2.75),
+            f16::from_f32(-2.75),
+        ]));
+        let coerced = coerce_array(
+            &arr,
+            &Field::new("i", DataType::Int8, true),
+            &CoercionOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            coerced.as_primitive::<Int8Type>(),
+            &Int8Array::from(vec![2, -2])
+        );
+
+        let coerced = coerce_array(
+            &arr,
+            &Field::new("f", DataType::Float64, true),
+            &CoercionOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            coerced.as_primitive::<Float64Type>(),
+            &Float64Array::from(vec![2.75, -2.75])
+        );
+    }
+
+    #[test]
+    fn test_coerce_drop_extra_columns_in_target_order() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("extra1", DataType::Utf8, true),
+            Field::new("c", DataType::Int32, true),
+            Field::new("a", DataType::Float32, true),
+            Field::new("extra2", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["x", "y"])),
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(Float32Array::from(vec![1.0, 2.0])),
+                Arc::new(Int32Array::from(vec![3, 4])),
+                Arc::new(StringArray::from(vec!["b1", "b2"])),
+            ],
+        )
+        .unwrap();
+
+        let target = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Float64, true),
+            Field::new("b", DataType::Utf8, true),
+            Field::new("c", DataType::Int64, true),
+        ]));
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let options = CoercionOptions::new().drop_extra_columns(true);
+        let batches = coerce_schema_with_options(reader, target.clone(), options)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        let names = batches[0]
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        assert_eq!(batches[0].schema(), target);
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let options = CoercionOptions::new().drop_extra_columns(false);
+        assert!(matches!(
+            coerce_schema_with_options(reader, target, options),
+            Err(Error::Schema { .. })
+        ));
+    }
+
+    #[test]
+    fn test_malformed_fixed_size_list() {
+        let item = Arc::new(Field::new("item", DataType::Float32, true));
+        // Five values for two vectors of dimension 2.
+        let values = Arc::new(Float32Array::from(vec![1.0, 2.0, 3.0, 4.0, 5.0]));
+        let malformed: Arc<dyn Array> =
+            Arc::new(FixedSizeListArray::try_new(item.clone(), 2, values, None).unwrap());
+        assert_eq!(malformed.len(), 2);
+        let field = Field::new("vec", DataType::FixedSizeList(item, 2), true);
+
+        let err = coerce_array(&malformed, &field, &CoercionOptions::default()).unwrap_err();
+        assert!(err.to_string().contains(
+            "Field vec is a malformed fixed size list: 5 values for 2 vectors of dimension 2"
+        ));
+
+        let options = CoercionOptions::new().repair_fixed_size_lists(true);
+        let repaired = coerce_array(&malformed, &field, &options).unwrap();
+        let repaired = repaired.as_fixed_size_list();
+        assert_eq!(repaired.len(), 2);
+        assert_eq!(
+            repaired.values().as_primitive::<Float32Type>().values(),
+            &[1.0, 2.0, 3.0, 4.0]
+        );
+    }
+
+    #[test]
+    fn test_coerce_nested_fixed_size_list() {
+        let matrix_type = |dt: DataType, cols: i32| {
+            DataType::FixedSizeList(
+                Arc::new(Field::new(
+                    "item",
+                    DataType::FixedSizeList(Arc::new(Field::new("item", dt, true)), cols),
+                    true,
+                )),
+                2,
+            )
+        };
+        // Two rows of 2x3 matrices.
+        let rows = FixedSizeListArray::try_new_from_values(
+            Float32Array::from_iter_values((0..12).map(|v| v as f32)),
+            3,
+        )
+        .unwrap();
+        let matrices: Arc<dyn Array> =
+            Arc::new(FixedSizeListArray::try_new_from_values(rows, 2).unwrap());
+        assert_eq!(matrices.data_type(), &matrix_type(DataType::Float32, 3));
+
+        let field = Field::new("m", matrix_type(DataType::Float16, 3), true);
+        let coerced = coerce_array(&matrices, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(coerced.data_type(), field.data_type());
+        let values = coerced
+            .as_fixed_size_list()
+            .values()
+            .as_fixed_size_list()
+            .values()
+            .clone();
+        assert_eq!(
+            values.as_primitive::<Float16Type>(),
+            &Float16Array::from_iter_values((0..12).map(|v| f16::from_f32(v as f32)))
+        );
+
+        let field = Field::new("m", matrix_type(DataType::Float16, 4), true);
+        let err = coerce_array(&matrices, &field, &CoercionOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("expected dimension 4 but got 3"));
+    }
+
+    #[test]
+    fn test_align_column_lengths() {
+        let schema = Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, true),
+        ]);
+        let columns: Vec<Arc<dyn Array>> = vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["x"])),
+        ];
+
+        let err =
+            align_column_lengths(&schema, columns.clone(), LengthMismatch::Error).unwrap_err();
+        let ArrowError::ExternalError(err) = err else {
+            panic!("expected an external error, got {:?}", err);
+        };
+        match err.downcast_ref::<Error>() {
+            Some(Error::ColumnLengthMismatch { lengths }) => {
+                assert_eq!(lengths, &vec![("a".to_string(), 3), ("b".to_string(), 1)])
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        let padded = align_column_lengths(&schema, columns.clone(), LengthMismatch::Pad).unwrap();
+        let batch = RecordBatch::try_new(Arc::new(schema.clone()), padded).unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(
+            batch.column(1).as_string::<i32>(),
+            &StringArray::from(vec![Some("x"), None, None])
+        );
+
+        let truncated = align_column_lengths(&schema, columns, LengthMismatch::Truncate).unwrap();
+        assert!(truncated.iter().all(|c| c.len() == 1));
+    }
+
+    #[test]
+    fn test_coerce_timestamp_date() {
+        // 2023-01-01T23:30:00Z, which is already 2023-01-02 in Tokyo.
+        let ts: Arc<dyn Array> = Arc::new(
+            TimestampSecondArray::from(vec![Some(1_672_615_800), Some(-1), None])
+                .with_timezone("Asia/Tokyo"),
+        );
+        let date32 = Field::new("d", DataType::Date32, true);
+
+        let coerced = coerce_array(&ts, &date32, &CoercionOptions::default()).unwrap();
+        assert_eq!(
+            coerced.as_primitive::<Date32Type>(),
+            &Date32Array::from(vec![Some(19359), Some(0), None])
+        );
+
+        let options = CoercionOptions::new().day_boundary(DayBoundary::Utc);
+        let coerced = coerce_array(&ts, &date32, &options).unwrap();
+        assert_eq!(
+            coerced.as_primitive::<Date32Type>(),
+            &Date32Array::from(vec![Some(19358), Some(-1), None])
+        );
+
+        let dates: Arc<dyn Array> = Arc::new(Date32Array::from(vec![Some(19358), Some(-1), None]));
+        let field = Field::new(
+            "t",
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+            true,
+        );
+        let coerced = coerce_array(&dates, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(coerced.data_type(), field.data_type());
+        assert_eq!(
+            coerced.as_primitive::<TimestampMillisecondType>(),
+            &TimestampMillisecondArray::from(vec![
+                Some(1_672_531_200_000),
+                Some(-86_400_000),
+                None
+            ])
+            .with_timezone("UTC")
+        );
+    }
+
+    #[test]
+    fn test_coerce_schema_with_report() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("f", DataType::Float64, true),
+            Field::new("s", DataType::Utf8, true),
+            Field::new("i", DataType::Int32, true),
+        ]));
+        let batches = (0..2)
+            .map(|_| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0])),
+                        Arc::new(StringArray::from(vec![Some("1"), Some("two"), None])),
+                        Arc::new(Int32Array::from(vec![1, 2, 3])),
+                    ],
+                )
+            })
+            .collect::<Vec<_>>();
+        let reader = RecordBatchIterator::new(batches, schema);
+
+        let target = Arc::new(Schema::new(vec![
+            Field::new("f", DataType::Float32, true),
+            Field::new("s", DataType::Int64, true),
+            Field::new("i", DataType::Int32, true),
+        ]));
+        let (stream, report) =
+            coerce_schema_with_report(reader, target, CoercionOptions::default()).unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches.len(), 2);
+
+        let report = report.lock().unwrap();
+        assert_eq!(report.batches, 2);
+        assert_eq!(report.rows, 6);
+        assert_eq!(report.dropped_rows, 0);
+        assert_eq!(report.precision_loss_events(), 2);
+        // "two" cannot be parsed as an integer, so it is nulled.
+        assert_eq!(report.nulled_cells(), 2);
+
+        let f = &report.columns["f"];
+        assert_eq!(f.rows_coerced, 6);
+        assert_eq!(f.precision_loss_events, 2);
+        assert_eq!(
+            f.transitions,
+            vec![ColumnTransition {
+                from: DataType::Float64,
+                to: DataType::Float32,
+                batches: 2,
+            }]
+        );
+        let s = &report.columns["s"];
+        assert_eq!(s.rows_coerced, 6);
+        assert_eq!(s.nulled_cells, 2);
+        assert_eq!(s.precision_loss_events, 0);
+        assert!(!report.columns.contains_key("i"));
+    }
+
+    #[test]
+    fn test_coerce_fixed_size_list_to_large_list() {
+        let item = Arc::new(Field::new("item", DataType::Float32, true));
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vec",
+            DataType::FixedSizeList(item.clone(), 5),
+            true,
+        )]));
+        let vectors = FixedSizeListArray::try_new(
+            item.clone(),
+            5,
+            Arc::new(Float32Array::from_iter_values((0..15).map(|v| v as f32))),
+            Some(NullBuffer::from(vec![true, false, true])),
+        )
+        .unwrap();
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+
+        let target = Arc::new(Schema::new(vec![Field::new(
+            "vec",
+            DataType::LargeList(item),
+            true,
+        )]));
+        let stream = coerce_schema(reader, target).unwrap();
+        let field = stream.schema().field(0).clone();
+        assert_eq!(
+            field.metadata().get(ORIGINAL_DIMENSION_METADATA_KEY),
+            Some(&"5".to_string())
+        );
+
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let list = batches[0].column(0).as_list::<i64>();
+        assert_eq!(list.value_offsets(), &[0, 5, 10, 15]);
+        assert!(list.is_null(1));
+        assert_eq!(
+            list.value(2).as_primitive::<Float32Type>(),
+            &Float32Array::from_iter_values((10..15).map(|v| v as f32))
+        );
+    }
+
+    #[test]
+    fn test_coerce_integer_narrowing() {
+        let arr: Arc<dyn Array> = Arc::new(Int64Array::from(vec![Some(1), Some(-300), None]));
+        let field = Field::new("i", DataType::Int16, true);
+
+        let options = CoercionOptions::new().integer_narrowing(NarrowingPolicy::Error);
+        assert!(coerce_array(&arr, &field, &options).is_err());
+        // Widening is not affected by the policy.
+        let wide = Field::new("i", DataType::Int64, true);
+        let narrow: Arc<dyn Array> = Arc::new(Int16Array::from(vec![1, 2]));
+        assert!(coerce_array(&narrow, &wide, &options).is_ok());
+
+        let options = CoercionOptions::new().integer_narrowing(NarrowingPolicy::CheckValues);
+        let coerced = coerce_array(&arr, &field, &options).unwrap();
+        assert_eq!(
+            coerced.as_primitive::<Int16Type>(),
+            &Int16Array::from(vec![Some(1), Some(-300), None])
+        );
+
+        let overflow: Arc<dyn Array> = Arc::new(Int64Array::from(vec![1, 1 << 20]));
+        assert!(coerce_array(&overflow, &field, &options).is_err());
+
+        let coerced = coerce_array(&overflow, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(coerced.null_count(), 1);
+    }
+
+    #[test]
+    fn test_coerce_integer_vector_narrowing() {
+        let vectors: Arc<dyn Array> = Arc::new(
+            FixedSizeListArray::try_new_from_values(Int16Array::from(vec![1, 2, 3, 4, 300, 6]), 3)
+                .unwrap(),
+        );
+        let vector_field = |dt| {
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", dt, true)), 3),
+                true,
+            )
+        };
+        let options = CoercionOptions::new().integer_narrowing(NarrowingPolicy::CheckValues);
+
+        let err = coerce_array(&vectors, &vector_field(DataType::Int8), &options).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("value 300 at row 1 component 1 overflows"),
+            "{}",
+            err
+        );
+        // Widening can not overflow.
+        let widened = coerce_array(&vectors, &vector_field(DataType::Int32), &options).unwrap();
+        assert_eq!(widened.as_fixed_size_list().values().null_count(), 0);
+
+        // The components of a null vector are not checked.
+        let vectors: Arc<dyn Array> = Arc::new(FixedSizeListArray::new(
+            Arc::new(Field::new("item", DataType::Int16, true)),
+            3,
+            Arc::new(Int16Array::from(vec![1, 2, 3, 4, 300, 6])),
+            Some(NullBuffer::from(vec![true, false])),
+        ));
+        let coerced = coerce_array(&vectors, &vector_field(DataType::Int8), &options).unwrap();
+        let coerced = coerced.as_fixed_size_list();
+        assert!(coerced.is_null(1));
+        assert_eq!(
+            coerced.value(0).as_primitive::<Int8Type>().values(),
+            &[1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_coerce_non_nullable_field() {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, true)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![
+                Some(1),
+                None,
+                Some(3),
+                None,
+            ]))],
+        )
+        .unwrap();
+        let target = Arc::new(Schema::new(vec![Field::new("i", DataType::Int64, false)]));
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let err = coerce_schema(reader, target.clone())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Field i is not nullable, but contains 2 nulls"));
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let options = CoercionOptions::new()
+            .fill_non_nullable_with_default(Arc::new(Int32Array::from(vec![-1])));
+        let batches = coerce_schema_with_options(reader, target, options)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            batches[0].column(0).as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![1, -1, 3, -1])
+        );
+    }
+
+    /// A reader that counts how many batches have been pulled from it.
+    struct CountingReader {
+        schema: SchemaRef,
+        batch: RecordBatch,
+        remaining: usize,
+        pulled: Arc<AtomicUsize>,
+    }
+
+    impl Iterator for CountingReader {
+        type Item = std::result::Result<RecordBatch, ArrowError>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.remaining == 0 {
+                return None;
+            }
+            self.remaining -= 1;
+            self.pulled.fetch_add(1, Ordering::SeqCst);
+            Some(Ok(self.batch.clone()))
+        }
+    }
+
+    impl RecordBatchReader for CountingReader {
+        fn schema(&self) -> SchemaRef {
+            self.schema.clone()
+        }
+    }
+
+    #[test]
+    fn test_coerce_identity_is_lazy() {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, true)]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(vec![1, 2]))])
+                .unwrap();
+        let pulled = Arc::new(AtomicUsize::new(0));
+        let reader = CountingReader {
+            schema: schema.clone(),
+            batch: batch.clone(),
+            remaining: 3,
+            pulled: pulled.clone(),
+        };
+
+        let mut stream = coerce_schema(reader, schema).unwrap();
+        assert_eq!(pulled.load(Ordering::SeqCst), 0);
+        assert_eq!(stream.next().unwrap().unwrap(), batch);
+        assert_eq!(pulled.load(Ordering::SeqCst), 1);
+        assert_eq!(stream.count(), 2);
+        assert_eq!(pulled.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_coerce_struct_to_point() {
+        let float_field = |name: &str| Arc::new(Field::new(name, DataType::Float32, true));
+        let floats = |values: Vec<f32>| Arc::new(Float32Array::from(values)) as Arc<dyn Array>;
+        let point_type = |dim| {
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float64, true)), dim)
+        };
+
+        // 2D points, with the children stored out of order.
+        let points: Arc<dyn Array> = Arc::new(StructArray::from(vec![
+            (float_field("y"), floats(vec![2.0, 4.0])),
+            (float_field("x"), floats(vec![1.0, 3.0])),
+        ]));
+        let coerced = coerce_array(
+            &points,
+            &Field::new("p", point_type(2), true),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(coerced.data_type(), &point_type(2));
+        assert_eq!(
+            coerced
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float64Type>(),
+            &Float64Array::from(vec![1.0, 2.0, 3.0, 4.0])
+        );
+
+        // 3D points.
+        let points: Arc<dyn Array> = Arc::new(StructArray::from(vec![
+            (float_field("x"), floats(vec![1.0, 4.0])),
+            (float_field("y"), floats(vec![2.0, 5.0])),
+            (float_field("z"), floats(vec![3.0, 6.0])),
+        ]));
+        let coerced = coerce_array(
+            &points,
+            &Field::new("p", point_type(3), true),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            coerced
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float64Type>(),
+            &Float64Array::from(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+        );
+
+        assert!(coerce_array(
+            &points,
+            &Field::new("p", point_type(2), true),
+            &Default::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_coerce_list_of_dictionary() {
+        let mut builder = ListBuilder::new(StringDictionaryBuilder::<Int8Type>::new());
+        builder.append_value([Some("a"), None, Some("b")]);
+        builder.append_null();
+        builder.append_value([Some("b"), Some("c")]);
+        let list: Arc<dyn Array> = Arc::new(builder.finish());
+
+        let dict_type =
+            |key: DataType| DataType::Dictionary(Box::new(key), Box::new(DataType::Utf8));
+        let field = Field::new(
+            "l",
+            DataType::List(Arc::new(Field::new(
+                "item",
+                dict_type(DataType::Int32),
+                true,
+            ))),
+            true,
+        );
+        let coerced = coerce_array(&list, &field, &Default::default()).unwrap();
+        assert_eq!(coerced.data_type(), field.data_type());
+
+        let coerced = coerced.as_list::<i32>();
+        assert!(coerced.is_null(1));
+        assert_eq!(coerced.value_offsets(), &[0, 3, 3, 5]);
+        let values = cast(coerced.values(), &DataType::Utf8).unwrap();
+        assert_eq!(
+            values.as_string::<i32>(),
+            &StringArray::from(vec![Some("a"), None, Some("b"), Some("b"), Some("c")])
+        );
+    }
+
+    #[test]
+    fn test_coerce_canonicalize_metadata() {
+        let field = |metadata: Vec<(&str, &str)>| {
+            Field::new("v", DataType::Int32, true).with_metadata(
+                metadata
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            )
+        };
+        let schema = Arc::new(Schema::new(vec![field(vec![(
+            "ARROW:extension:name",
+            "lance.vector ",
+        )])]));
+        let target = Arc::new(Schema::new(vec![field(vec![(
+            " arrow:extension:name",
+            "lance.vector",
+        )])]));
+        let options = CoercionOptions::new().canonicalize_metadata(true);
+        assert!(is_equivalent_schema(&schema, &target, &options));
+        assert!(!is_equivalent_schema(
+            &schema,
+            &target,
+            &CoercionOptions::default()
+        ));
+        let other = Schema::new(vec![field(vec![("arrow:extension:name", "other")])]);
+        assert!(!is_equivalent_schema(&schema, &other, &options));
+
+        let column = Arc::new(Int32Array::from(vec![1, 2, 3]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![column.clone()]).unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let batches = coerce_schema_with_options(reader, target.clone(), options)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches[0].schema(), target);
+        assert_eq!(
+            batches[0].column(0).to_data().buffers()[0].as_ptr(),
+            column.to_data().buffers()[0].as_ptr()
+        );
+    }
+
+    #[test]
+    fn test_structural_equality_only() {
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int32, false)]));
+        let target = Arc::new(Schema::new_with_metadata(
+            vec![Field::new("v", DataType::Int32, true)
+                .with_metadata(HashMap::from([("k".to_string(), "v".to_string())]))],
+            HashMap::from([("version".to_string(), "2".to_string())]),
+        ));
+        let options = CoercionOptions::new().structural_equality_only(true);
+        assert!(is_equivalent_schema(&schema, &target, &options));
+        assert!(!is_equivalent_schema(
+            &schema,
+            &target,
+            &CoercionOptions::default()
+        ));
+        let renamed = Schema::new(vec![Field::new("w", DataType::Int32, true)]);
+        assert!(!is_equivalent_schema(&schema, &renamed, &options));
+        let retyped = Schema::new(vec![Field::new("v", DataType::Int64, true)]);
+        assert!(!is_equivalent_schema(&schema, &retyped, &options));
+
+        let column = Arc::new(Int32Array::from(vec![1, 2, 3]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![column.clone()]).unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let batches = coerce_schema_with_options(reader, target.clone(), options)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches[0].schema(), target);
+        assert_eq!(
+            batches[0].column(0).to_data().buffers()[0].as_ptr(),
+            column.to_data().buffers()[0].as_ptr()
+        );
+    }
+
+    #[test]
+    fn test_coerce_nan_to_inner_null() {
+        let item = Arc::new(Field::new("item", DataType::Float32, true));
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vec",
+            DataType::FixedSizeList(item, 3),
+            true,
+        )]));
+        let vectors = FixedSizeListArray::try_new_from_values(
+            Float32Array::from(vec![1.0, f32::NAN, 3.0, 4.0, 5.0, 6.0]),
+            3,
+        )
+        .unwrap();
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+
+        let options = CoercionOptions::new().nan_policy(NanPolicy::NanToInnerNull);
+        let batches = coerce_schema_with_options(reader, schema, options)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        let vectors = batches[0].column(0).as_fixed_size_list();
+        assert_eq!(vectors.len(), 2);
+        assert_eq!(vectors.null_count(), 0);
+        assert_eq!(
+            vectors.values().as_primitive::<Float32Type>(),
+            &Float32Array::from(vec![
+                Some(1.0),
+                None,
+                Some(3.0),
+                Some(4.0),
+                Some(5.0),
+                Some(6.0)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_f16_nan() {
+        let values = [0x7E00, 0x7E01, 0xFE00, 0x7C01, 0x3C00].map(f16::from_bits);
+        let scalars: Arc<dyn Array> = Arc::new(Float16Array::from(values.to_vec()));
+        let vectors: Arc<dyn Array> = Arc::new(
+            FixedSizeListArray::try_new_from_values(Float16Array::from(values[1..].to_vec()), 2)
+                .unwrap(),
+        );
+        let scalar_field = Field::new("f", DataType::Float16, true);
+        let vector_field = Field::new("vec", vectors.data_type().clone(), true);
+        let bits = |array: &dyn Array| {
+            array
+                .as_primitive::<Float16Type>()
+                .values()
+                .iter()
+                .map(|v| v.to_bits())
+                .collect::<Vec<_>>()
+        };
+
+        let options = CoercionOptions::new();
+        let kept = coerce_array(&scalars, &scalar_field, &options).unwrap();
+        assert_eq!(bits(&kept), vec![0x7E00, 0x7E01, 0xFE00, 0x7C01, 0x3C00]);
+
+        let options = CoercionOptions::new().canonicalize_f16_nan(true);
+        let canonical = coerce_array(&scalars, &scalar_field, &options).unwrap();
+        let canonical_bits = f16::NAN.to_bits();
+        assert_eq!(
+            bits(&canonical),
+            vec![
+                canonical_bits,
+                canonical_bits,
+                canonical_bits,
+                canonical_bits,
+                0x3C00
+            ]
+        );
+        let canonical = coerce_array(&vectors, &vector_field, &options).unwrap();
+        assert_eq!(
+            bits(canonical.as_fixed_size_list().values()),
+            vec![canonical_bits, canonical_bits, canonical_bits, 0x3C00]
+        );
+    }
+
+    #[test]
+    fn test_max_string_bytes() {
+        let schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)]));
+        // "é" is 2 bytes, so the 4 bytes limit falls in the middle of the second one.
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from(vec![
+                Some("abc"),
+                None,
+                Some("aéé"),
+            ]))],
+        )
+        .unwrap();
+        let coerce = |options: CoercionOptions| {
+            let options = options.max_string_bytes(HashMap::from([("s".to_string(), 4)]));
+            let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+            coerce_schema_with_options(reader, schema.clone(), options)
+                .unwrap()
+                .collect::<std::result::Result<Vec<_>, _>>()
+        };
+
+        let err = coerce(CoercionOptions::new()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("String at row 2 of column s has 5 bytes, which exceeds the limit of 4"));
+
+        let batches =
+            coerce(CoercionOptions::new().on_oversized_strings(OversizedString::Truncate)).unwrap();
+        assert_eq!(
+            batches[0].column(0).as_string::<i32>(),
+            &StringArray::from(vec![Some("abc"), None, Some("aé")])
+        );
+
+        for (column, message) in [
+            ("missing", "Column missing has a string length limit"),
+            ("n", "Column n has type Int32"),
+        ] {
+            let target = Arc::new(Schema::new(vec![
+                Field::new("s", DataType::Utf8, true),
+                Field::new("n", DataType::Int32, true),
+            ]));
+            let options =
+                CoercionOptions::new().max_string_bytes(HashMap::from([(column.to_string(), 4)]));
+            let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+            let err = coerce_schema_with_options(reader, target, options)
+                .err()
+                .unwrap();
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_clean_strings() {
+        let schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)]));
+        let strings = vec![
+            Some("\u{feff}id"),
+            Some("  a b\t"),
+            None,
+            Some("\u{feff} x "),
+        ];
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(StringArray::from(strings))])
+                .unwrap();
+        let coerce = |options: CoercionOptions| {
+            let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+            let stream = coerce_schema_with_options(reader, schema.clone(), options).unwrap();
+            let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+            batches[0]
+                .column(0)
+                .as_string::<i32>()
+                .iter()
+                .map(|s| s.map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            coerce(CoercionOptions::new().strip_bom(true)),
+            vec![
+                Some("id".to_string()),
+                Some("  a b\t".to_string()),
+                None,
+                Some(" x ".to_string())
+            ]
+        );
+        assert_eq!(
+            coerce(CoercionOptions::new().trim_strings(true).strip_bom(true)),
+            vec![
+                Some("id".to_string()),
+                Some("a b".to_string()),
+                None,
+                Some("x".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_coerce_int_to_label() {
+        let labels = HashMap::from([(1, "cat".to_string()), (2, "dog".to_string())]);
+        let codes: Arc<dyn Array> = Arc::new(Int32Array::from(vec![Some(2), None, Some(1)]));
+        let field = Field::new("pet", DataType::Utf8, true);
+
+        let options = CoercionOptions::new().int_to_label(labels);
+        let coerced = coerce_array(&codes, &field, &options).unwrap();
+        assert_eq!(
+            coerced.as_string::<i32>(),
+            &StringArray::from(vec![Some("dog"), None, Some("cat")])
+        );
+
+        let unmapped: Arc<dyn Array> = Arc::new(Int32Array::from(vec![1, 3]));
+        let err = coerce_array(&unmapped, &field, &options).unwrap_err();
+        assert!(err.to_string().contains("no label for code 3 at row 1"));
+
+        let options = options.coerce_errors_to_null(true);
+        let coerced = coerce_array(&unmapped, &field, &options).unwrap();
+        assert_eq!(
+            coerced.as_string::<i32>(),
+            &StringArray::from(vec![Some("cat"), None])
+        );
+    }
+
+    #[test]
+    fn test_coerce_label_to_int() {
+        let labels: Arc<dyn Array> = Arc::new(StringArray::from(vec![
+            Some("dog"),
+            None,
+            Some("cat"),
+            Some("bird"),
+            Some("fish"),
+            Some("bird"),
+        ]));
+        let field = Field::new("code", DataType::Int32, true);
+        let codes = HashMap::from([(
+            "code".to_string(),
+            HashMap::from([("cat".to_string(), 1), ("dog".to_string(), 2)]),
+        )]);
+
+        let options = CoercionOptions::new().label_to_int(codes.clone());
+        let err = coerce_array(&labels, &field, &options).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("no code for label \"bird\" at row 3"));
+
+        let options = CoercionOptions::new()
+            .label_to_int(codes.clone())
+            .on_unknown_label(UnknownLabel::Null);
+        let coded = coerce_array(&labels, &field, &options).unwrap();
+        assert_eq!(
+            coded.as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![Some(2), None, Some(1), None, None, None])
+        );
+
+        let options = CoercionOptions::new()
+            .label_to_int(codes)
+            .on_unknown_label(UnknownLabel::AssignNew);
+        let coded = coerce_array(&labels, &field, &options).unwrap();
+        assert_eq!(
+            coded.as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![Some(2), None, Some(1), Some(3), Some(4), Some(3)])
+        );
+        let vocabulary = options.label_codes("code").unwrap();
+        assert_eq!(vocabulary.lock().unwrap().len(), 4);
+        assert_eq!(vocabulary.lock().unwrap()["fish"], 4);
+
+        // The codes of another column are not shared.
+        let other = Field::new("other", DataType::Int32, true);
+        let options = options.label_to_int(HashMap::from([
+            ("code".to_string(), HashMap::from([("cat".to_string(), 1)])),
+            ("other".to_string(), HashMap::from([("cat".to_string(), 7)])),
+        ]));
+        let coded = coerce_array(&labels.slice(2, 1), &other, &options).unwrap();
+        assert_eq!(coded.as_primitive::<Int32Type>().value(0), 7);
+        let coded = coerce_array(&labels.slice(2, 1), &field, &options).unwrap();
+        assert_eq!(coded.as_primitive::<Int32Type>().value(0), 1);
+
+        // Codes that do not fit the field are not silently replaced with null.
+        let narrow = Field::new("code", DataType::Int8, true);
+        let options = CoercionOptions::new().label_to_int(HashMap::from([(
+            "code".to_string(),
+            HashMap::from([("cat".to_string(), 1000)]),
+        )]));
+        let err = coerce_array(&labels.slice(2, 1), &narrow, &options).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Coercing the labels of field code"));
+
+        // No code is left after the largest one.
+        let options = CoercionOptions::new()
+            .label_to_int(HashMap::from([(
+                "code".to_string(),
+                HashMap::from([("cat".to_string(), i64::MAX)]),
+            )]))
+            .on_unknown_label(UnknownLabel::AssignNew);
+        let wide = Field::new("code", DataType::Int64, true);
+        let err = coerce_array(&labels, &wide, &options).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("no code left for label \"dog\" at row 0"));
+    }
+
+    #[test]
+    fn test_coerce_binary_to_string() {
+        let field = Field::new("s", DataType::Utf8, true);
+        let binary: Arc<dyn Array> = Arc::new(LargeBinaryArray::from(vec![
+            Some("héllo".as_bytes()),
+            None,
+            Some(b"world".as_ref()),
+        ]));
+        let coerced = coerce_array(&binary, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(
+            coerced.as_string::<i32>(),
+            &StringArray::from(vec![Some("héllo"), None, Some("world")])
+        );
+
+        let invalid: Arc<dyn Array> =
+            Arc::new(BinaryArray::from(vec![b"ok".as_ref(), &[0xff, 0xfe]]));
+        let err = coerce_array(&invalid, &field, &CoercionOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("invalid UTF-8 at row 1"));
+
+        let options = CoercionOptions::new().coerce_errors_to_null(true);
+        let coerced = coerce_array(&invalid, &field, &options).unwrap();
+        assert_eq!(
+            coerced.as_string::<i32>(),
+            &StringArray::from(vec![Some("ok"), None])
+        );
+    }
+
+    #[test]
+    fn test_coerce_json_to_struct() {
+        let field = Field::new(
+            "doc",
+            DataType::Struct(
+                vec![
+                    Field::new("a", DataType::Int64, true),
+                    Field::new("b", DataType::Utf8, true),
+                ]
+                .into(),
+            ),
+            true,
+        );
+        let json: Arc<dyn Array> = Arc::new(StringArray::from(vec![
+            Some(r#"{"a":1,"b":"x"}"#),
+            None,
+            Some(r#"{"a":2}"#),
+        ]));
+        assert!(coerce_array(&json, &field, &CoercionOptions::default()).is_err());
+
+        let options = CoercionOptions::new().parse_json_structs(true);
+        let coerced = coerce_array(&json, &field, &options).unwrap();
+        let coerced = coerced.as_struct();
+        assert_eq!(coerced.data_type(), field.data_type());
+        assert_eq!(coerced.logical_nulls().unwrap().null_count(), 1);
+        assert!(coerced.is_null(1));
+        assert_eq!(
+            coerced.column(0).as_primitive::<Int64Type>(),
+            &PrimitiveArray::<Int64Type>::from(vec![Some(1), None, Some(2)])
+        );
+        assert_eq!(coerced.column(1).as_string::<i32>().value(0), "x");
+        assert!(coerced.column(1).is_null(2));
+
+        let malformed: Arc<dyn Array> =
+            Arc::new(StringArray::from(vec![r#"{"a":1,"b":"x"}"#, r#"{"a":"#]));
+        let err = coerce_array(&malformed, &field, &options).unwrap_err();
+        assert!(err.to_string().contains("malformed JSON at row 1"));
+    }
+
+    #[test]
+    fn test_coerce_struct_to_json() {
+        let structs: Arc<dyn Array> = Arc::new(StructArray::new(
+            vec![
+                Field::new("b", DataType::Utf8, true),
+                Field::new("a", DataType::Int64, true),
+            ]
+            .into(),
+            vec![
+                Arc::new(StringArray::from(vec![Some("x"), None, Some("z")])),
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+            ],
+            Some(NullBuffer::from(vec![true, true, false])),
+        ));
+        let field = Field::new("doc", DataType::Utf8, true);
+
+        let json = coerce_array(&structs, &field, &CoercionOptions::default()).unwrap();
+        let json = json.as_string::<i32>();
+        assert_eq!(json.value(0), r#"{"b":"x","a":1}"#);
+        assert_eq!(json.value(1), r#"{"b":null,"a":2}"#);
+        assert!(json.is_null(2));
+
+        let options = CoercionOptions::new().json_key_order(JsonKeyOrder::Sorted);
+        let json = coerce_array(&structs, &field, &options).unwrap();
+        assert_eq!(json.as_string::<i32>().value(0), r#"{"a":1,"b":"x"}"#);
+
+        let nested: Arc<dyn Array> = Arc::new(StructArray::new(
+            vec![
+                Field::new("outer", structs.data_type().clone(), true),
+                Field::new("id", DataType::Int64, true),
+            ]
+            .into(),
+            vec![structs.clone(), Arc::new(Int64Array::from(vec![7, 8, 9]))],
+            None,
+        ));
+        let json = coerce_array(&nested, &field, &CoercionOptions::default()).unwrap();
+        let json = json.as_string::<i32>();
+        assert_eq!(json.value(0), r#"{"outer":{"b":"x","a":1},"id":7}"#);
+        assert_eq!(json.value(2), r#"{"outer":null,"id":9}"#);
+        let json = coerce_array(&nested, &field, &options).unwrap();
+        assert_eq!(
+            json.as_string::<i32>().value(0),
+            r#"{"id":7,"outer":{"a":1,"b":"x"}}"#
+        );
+    }
+
+    #[test]
+    fn test_sort_list_values() {
+        let tags_field = Field::new(
+            "tags",
+            DataType::List(Arc::new(Field::new("item", DataType::Int32, true))),
+            true,
+        );
+        let schema = Arc::new(Schema::new(vec![tags_field.clone()]));
+        let tags = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(3), None, Some(1), Some(2)]),
+            None,
+            Some(vec![]),
+            Some(vec![Some(5), Some(-1)]),
+        ]);
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(tags.slice(0, 4))]).unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+
+        let options = CoercionOptions::new().sort_list_values(vec!["tags".to_string()]);
+        let stream = coerce_schema_with_options(reader, schema, options).unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let expected = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), Some(2), Some(3), None]),
+            None,
+            Some(vec![]),
+            Some(vec![Some(-1), Some(5)]),
+        ]);
+        assert_eq!(batches[0].column(0).as_list::<i32>(), &expected);
+    }
+
+    #[test]
+    fn test_f16_rounding() {
+        let field = Field::new(
+            "vec",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float16, true)), 4),
+            true,
+        );
+        // 1 + 2^-12 lies a quarter of the way between two adjacent f16 values.
+        let x = 1.0 + 2.0_f32.powi(-12);
+        let (lo, hi) = (f16::from_f32(1.0), f16::from_f32(1.0 + 2.0_f32.powi(-10)));
+        let values = Float32Array::from_iter_values([x, -2.5, 0.1, 1000.3].repeat(256));
+        let vectors: Arc<dyn Array> =
+            Arc::new(FixedSizeListArray::try_new_from_values(values, 4).unwrap());
+        let coerce = |options: &CoercionOptions| {
+            coerce_array(&vectors, &field, options)
+                .unwrap()
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float16Type>()
+                .values()
+                .to_vec()
+        };
+
+        let nearest = coerce(&CoercionOptions::default());
+        assert_eq!(nearest, coerce(&CoercionOptions::default()));
+        assert!(nearest.iter().step_by(4).all(|v| *v == lo));
+
+        let options = CoercionOptions::new().f16_rounding(F16Rounding::Stochastic { seed: 42 });
+        let stochastic = coerce(&options);
+        assert_eq!(stochastic, coerce(&options));
+        assert_ne!(stochastic, nearest);
+        // Exactly representable values are not perturbed.
+        assert!(stochastic
+            .iter()
+            .skip(1)
+            .step_by(4)
+            .all(|v| v.to_f32() == -2.5));
+        let rounded = stochastic.iter().step_by(4).collect::<Vec<_>>();
+        assert!(rounded.iter().all(|v| **v == lo || **v == hi));
+        let ups = rounded.iter().filter(|v| ***v == hi).count();
+        assert!((32..=96).contains(&ups), "{} of 256 rounded up", ups);
+    }
+
+    #[test]
+    fn test_coerce_reordered_struct() {
+        let field = Field::new(
+            "s",
+            DataType::Struct(
+                vec![
+                    Field::new("a", DataType::Int64, true),
+                    Field::new("b", DataType::Utf8, true),
+                ]
+                .into(),
+            ),
+            true,
+        );
+        let reordered: Arc<dyn Array> = Arc::new(StructArray::from(vec![
+            (
+                Arc::new(Field::new("b", DataType::Utf8, true)),
+                Arc::new(StringArray::from(vec!["x", "y"])) as Arc<dyn Array>,
+            ),
+            (
+                Arc::new(Field::new("a", DataType::Int32, true)),
+                Arc::new(Int32Array::from(vec![1, 2])) as Arc<dyn Array>,
+            ),
+        ]));
+        let coerced = coerce_array(&reordered, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(coerced.data_type(), field.data_type());
+        let coerced = coerced.as_struct();
+        assert_eq!(
+            coerced.column(0).as_primitive::<Int64Type>().values(),
+            &[1, 2]
+        );
+        assert_eq!(
+            coerced.column(1).as_string::<i32>(),
+            &StringArray::from(vec!["x", "y"])
+        );
+
+        let missing: Arc<dyn Array> = Arc::new(StructArray::from(vec![(
+            Arc::new(Field::new("a", DataType::Int64, true)),
+            Arc::new(Int64Array::from(vec![1, 2])) as Arc<dyn Array>,
+        )]));
+        let err = coerce_array(&missing, &field, &CoercionOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("missing struct child b"));
+    }
+
+    #[test]
+    fn test_rescale_decimal() {
+        let decimals: Arc<dyn Array> = Arc::new(
+            Decimal128Array::from(vec![Some(12345), None, Some(-5)])
+                .with_precision_and_scale(10, 2)
+                .unwrap(),
+        );
+        let field = Field::new("d", DataType::Decimal128(12, 4), true);
+        let upscaled = coerce_array(&decimals, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(
+            upscaled.as_primitive::<Decimal128Type>(),
+            &Decimal128Array::from(vec![Some(1234500), None, Some(-500)])
+                .with_precision_and_scale(12, 4)
+                .unwrap()
+        );
+
+        let overflow = Field::new("d", DataType::Decimal128(5, 4), true);
+        let err = coerce_array(&decimals, &overflow, &CoercionOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("value at row 0 overflows"));
+
+        let lossy = Field::new("d", DataType::Decimal128(10, 1), true);
+        let options = CoercionOptions::new().integer_narrowing(NarrowingPolicy::Error);
+        assert!(coerce_array(&decimals, &lossy, &options).is_err());
+        let options = CoercionOptions::new().integer_narrowing(NarrowingPolicy::CheckValues);
+        let err = coerce_array(&decimals, &lossy, &options).unwrap_err();
+        assert!(err.to_string().contains("row 0 loses digits"), "{}", err);
+
+        let rounded = coerce_array(&decimals, &lossy, &CoercionOptions::default()).unwrap();
+        assert_eq!(
+            rounded.as_primitive::<Decimal128Type>(),
+            &Decimal128Array::from(vec![Some(1235), None, Some(-1)])
+                .with_precision_and_scale(10, 1)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_detect_drift() {
+        let vector_type = |dim| {
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim)
+        };
+        let baseline = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("price", DataType::Float64, true),
+            Field::new("vector", vector_type(128), true),
+            Field::new("location", vector_type(2), true),
+        ]);
+        // The points coerce to vectors, although arrow can not cast them.
+        let point_type = DataType::Struct(
+            vec![
+                Field::new("x", DataType::Float32, true),
+                Field::new("y", DataType::Float32, true),
+            ]
+            .into(),
+        );
+        let current = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("price", DataType::Utf8, true),
+            Field::new("vector", vector_type(256), true),
+            Field::new("location", point_type, true),
+            Field::new("source", DataType::Utf8, true),
+        ]);
+
+        let report = detect_drift(&current, &current);
+        assert!(report.is_empty());
+        assert_eq!(report.max_severity(), None);
+
+        let report = detect_drift(&current, &baseline);
+        let drifts = report
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.kind, f.severity))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            drifts,
+            vec![
+                ("price", DriftKind::Retyped, DriftSeverity::Warning),
+                (
+                    "vector",
+                    DriftKind::DimensionChanged,
+                    DriftSeverity::Critical
+                ),
+                ("location", DriftKind::Retyped, DriftSeverity::Warning),
+                ("source", DriftKind::Added, DriftSeverity::Info),
+            ]
+        );
+        assert_eq!(report.fields[1].baseline, Some(vector_type(128)));
+        assert_eq!(report.fields[1].current, Some(vector_type(256)));
+        assert_eq!(report.max_severity(), Some(DriftSeverity::Critical));
+    }
+
+    #[test]
+    fn test_plan_coercion() {
+        let source = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float64, true)), 8),
+                true,
+            ),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("legacy", DataType::Binary, true),
+        ]);
+        let target = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 8),
+                true,
+            ),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("score", DataType::Float32, true),
+        ]);
+
+        let plan = plan_coercion(&source, &target, &CoercionOptions::default());
+        let actions = plan
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.action))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![
+                ("id", FieldAction::Coerce),
+                ("vector", FieldAction::Coerce),
+                ("name", FieldAction::Keep),
+                ("score", FieldAction::Missing),
+                ("legacy", FieldAction::Drop),
+            ]
+        );
+        assert!(plan.has_errors());
+
+        let table = plan.to_table_string();
+        for name in ["id", "vector", "name", "score", "legacy"] {
+            assert!(table.contains(name), "{}", table);
+        }
+        for label in ["coerce", "keep", "missing", "drop"] {
+            assert!(table.contains(label), "{}", table);
+        }
+        assert!(table.contains("FixedSizeList<Float64, 8>"));
+        assert!(table.contains("might lose precision"));
+        // Every cell of a column starts at the same offset.
+        let lines = table.lines().collect::<Vec<_>>();
+        let offset = lines[0].find("action").unwrap();
+        assert!(lines[2..]
+            .iter()
+            .all(|l| l[offset..].starts_with(|c: char| c.is_alphabetic())));
+
+        let options = CoercionOptions::new()
+            .drop_extra_columns(false)
+            .integer_narrowing(NarrowingPolicy::Error);
+        let plan = plan_coercion(&source, &target, &options);
+        assert_eq!(plan.fields[0].action, FieldAction::Reject);
+        assert_eq!(plan.fields[4].action, FieldAction::Reject);
+    }
+
+    #[test]
+    fn test_map_duplicate_keys() {
+        let mut builder = MapBuilder::new(None, StringBuilder::new(), Int32Builder::new());
+        for (key, value) in [("a", 1), ("b", 2), ("a", 3)] {
+            builder.keys().append_value(key);
+            builder.values().append_value(value);
+        }
+        builder.append(true).unwrap();
+        builder.keys().append_value("c");
+        builder.values().append_value(4);
+        builder.append(true).unwrap();
+        let map: Arc<dyn Array> = Arc::new(builder.finish());
+        let field = Field::new("m", map.data_type().clone(), true);
+        let entries = |array: &Arc<dyn Array>, row: usize| {
+            let entries = array.as_map().value(row);
+            let keys = entries.column(0).as_string::<i32>().clone();
+            let values = entries.column(1).as_primitive::<Int32Type>().clone();
+            keys.iter()
+                .zip(values.iter())
+                .map(|(k, v)| (k.unwrap().to_string(), v.unwrap()))
+                .collect::<Vec<_>>()
+        };
+
+        let kept = coerce_array(&map, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(
+            entries(&kept, 0),
+            vec![("a".into(), 1), ("b".into(), 2), ("a".into(), 3)]
+        );
+
+        let options = CoercionOptions::new().map_duplicate_keys(MapDuplicateKeys::LastWins);
+        let deduped = coerce_array(&map, &field, &options).unwrap();
+        assert_eq!(entries(&deduped, 0), vec![("b".into(), 2), ("a".into(), 3)]);
+        assert_eq!(entries(&deduped, 1), vec![("c".into(), 4)]);
+
+        let options = CoercionOptions::new().map_duplicate_keys(MapDuplicateKeys::Error);
+        let err = coerce_array(&map, &field, &options).unwrap_err();
+        assert!(
+            err.to_string().contains("duplicate map key a at row 0"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_max_rows() {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let batches = (0..3)
+            .map(|_| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+                )
+            })
+            .collect::<Vec<_>>();
+        let reader = RecordBatchIterator::new(batches, schema.clone());
+
+        let options = CoercionOptions::new().max_rows(Some(5));
+        let mut stream = coerce_schema_with_options(reader, schema, options).unwrap();
+        assert_eq!(stream.next().unwrap().unwrap().num_rows(), 3);
+        let err = stream.next().unwrap().unwrap_err();
+        let ArrowError::ExternalError(err) = err else {
+            panic!("expected an external error, got {:?}", err);
+        };
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::RowLimitExceeded { limit: 5 })
+        ));
+    }
+
+    #[test]
+    fn test_normalize_stream() {
+        let target = Arc::new(Schema::new(vec![Field::new("x", DataType::Float32, true)]));
+        let double = Arc::new(Schema::new(vec![Field::new("x", DataType::Float64, true)]));
+        let big_endian = Arc::new(Schema::new(vec![Field::new("x", DataType::Float32, true)
+            .with_metadata(HashMap::from([(
+                BYTE_ORDER_METADATA_KEY.to_string(),
+                "big".to_string(),
+            )]))]));
+        let swapped = f32::from_bits(3.0f32.to_bits().swap_bytes());
+        let batches = vec![
+            RecordBatch::try_new(
+                target.clone(),
+                vec![Arc::new(Float32Array::from(vec![1.0]))],
+            ),
+            RecordBatch::try_new(double, vec![Arc::new(Float64Array::from(vec![2.0]))]),
+            RecordBatch::try_new(
+                big_endian,
+                vec![Arc::new(Float32Array::from(vec![swapped]))],
+            ),
+        ];
+        // The reader declares the target schema, but its batches do not all match it.
+        let reader = RecordBatchIterator::new(batches, target.clone());
+
+        let stream = normalize_stream(reader, target.clone(), CoercionOptions::default()).unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let values = batches
+            .iter()
+            .map(|b| {
+                assert_eq!(b.schema(), target);
+                b.column(0).as_primitive::<Float32Type>().value(0)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_require_stable_schema() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        let dropped = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let batches = vec![
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(vec![1, 2])),
+                    Arc::new(StringArray::from(vec!["x", "y"])),
+                ],
+            ),
+            RecordBatch::try_new(dropped, vec![Arc::new(Int32Array::from(vec![3]))]),
+        ];
+        let reader = RecordBatchIterator::new(batches, schema.clone());
+
+        let options = CoercionOptions::new().require_stable_schema(true);
+        let mut stream = coerce_schema_with_options(reader, schema, options).unwrap();
+        assert_eq!(stream.next().unwrap().unwrap().num_rows(), 2);
+        let err = stream.next().unwrap().unwrap_err();
+        assert!(matches!(err, ArrowError::SchemaError(_)));
+        assert!(err.to_string().contains("Batch 1"));
+    }
+
+    #[test]
+    fn test_coerce_integer_vector() {
+        let field = Field::new(
+            "vec",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::UInt8, true)), 4),
+            true,
+        );
+        let vectors = |values: Vec<f32>| -> Arc<dyn Array> {
+            Arc::new(
+                FixedSizeListArray::try_new_from_values(Float32Array::from(values), 4).unwrap(),
+            )
+        };
+
+        let in_range = vectors(vec![0.0, 1.0, 128.0, 255.0]);
+        let coerced = coerce_array(&in_range, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(coerced.data_type(), field.data_type());
+        assert_eq!(
+            coerced
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<UInt8Type>()
+                .values(),
+            &[0, 1, 128, 255]
+        );
+
+        let out_of_range = vectors(vec![0.0, 1.0, 256.0, -1.0]);
+        let options = CoercionOptions::new().integer_narrowing(NarrowingPolicy::CheckValues);
+        assert!(coerce_array(&out_of_range, &field, &options).is_err());
+        let coerced = coerce_array(&out_of_range, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(coerced.as_fixed_size_list().values().null_count(), 2);
+    }
+
+    #[test]
+    fn test_redact() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("ssn", DataType::Utf8, true),
+            Field::new("email", DataType::Utf8, true),
+            Field::new("age", DataType::Int32, true),
+            Field::new("id", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["123-45-6789", "987-65-4321"])),
+                Arc::new(StringArray::from(vec![Some("a@example.com"), None])),
+                Arc::new(Int32Array::from(vec![30, 40])),
+                Arc::new(Int32Array::from(vec![1, 2])),
+            ],
+        )
+        .unwrap();
+        let options = CoercionOptions::new().redact(HashMap::from([
+            ("ssn".to_string(), RedactPolicy::Null),
+            ("email".to_string(), RedactPolicy::Hash),
+            (
+                "age".to_string(),
+                RedactPolicy::Constant(Arc::new(Int64Array::from(vec![0]))),
+            ),
+        ]));
+        let coerce = || {
+            let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+            let stream = coerce_schema_with_options(reader, schema.clone(), options.clone());
+            stream.unwrap().next().unwrap().unwrap()
+        };
+
+        let redacted = coerce();
+        assert_eq!(redacted.column(0).null_count(), 2);
+        let digests = redacted.column(1).as_string::<i32>();
+        assert_eq!(
+            digests.value(0),
+            "08168cd80dfd534ab0f10af10f1303fe00af2d43ab5c1432360d137f8197e17a"
+        );
+        assert!(digests.is_null(1));
+        assert_eq!(
+            redacted.column(2).as_primitive::<Int32Type>().values(),
+            &[0, 0]
+        );
+        assert_eq!(
+            redacted.column(3).as_primitive::<Int32Type>().values(),
+            &[1, 2]
+        );
+        // The digests are stable.
+        assert_eq!(coerce(), redacted);
+    }
+
+    #[test]
+    fn test_decimals_as_scaled_int() {
+        let decimal_schema = Arc::new(Schema::new(vec![Field::new(
+            "price",
+            DataType::Decimal128(10, 2),
+            true,
+        )]));
+        let decimals = RecordBatch::try_new(
+            decimal_schema.clone(),
+            vec![Arc::new(
+                Decimal128Array::from(vec![Some(12345), None, Some(-5)])
+                    .with_precision_and_scale(10, 2)
+                    .unwrap(),
+            )],
+        )
+        .unwrap();
+        let int_schema = Arc::new(Schema::new(vec![Field::new(
+            "price",
+            DataType::Int64,
+            true,
+        )]));
+        let options = CoercionOptions::new().decimals_as_scaled_int(true);
+
+        let reader = RecordBatchIterator::new(vec![Ok(decimals.clone())], decimal_schema.clone());
+        let stream = coerce_schema_with_options(reader, int_schema, options).unwrap();
+        let stored_schema = stream.schema();
+        assert_eq!(
+            stored_schema.field(0).metadata()[DECIMAL_SCALE_METADATA_KEY],
+            "2"
+        );
+        let stored = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            stored[0].column(0).as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![Some(12345), None, Some(-5)])
+        );
+
+        let reader = RecordBatchIterator::new(stored.into_iter().map(Ok), stored_schema);
+        let restored = coerce_schema(reader, decimal_schema)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(restored, vec![decimals]);
+    }
+
+    #[test]
+    fn test_coerce_decimal_to_string() {
+        let decimals: Arc<dyn Array> = Arc::new(
+            Decimal128Array::from(vec![Some(12345), Some(-5), None, Some(0), Some(-12300)])
+                .with_precision_and_scale(10, 2)
+                .unwrap(),
+        );
+        let field = Field::new("d", DataType::Utf8, true);
+        let coerced = coerce_array(&decimals, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(
+            coerced.as_string::<i32>(),
+            &StringArray::from(vec![
+                Some("123.45"),
+                Some("-0.05"),
+                None,
+                Some("0.00"),
+                Some("-123.00")
+            ])
+        );
+
+        let hundreds: Arc<dyn Array> = Arc::new(
+            Decimal128Array::from(vec![12, -3, 0])
+                .with_precision_and_scale(5, -2)
+                .unwrap(),
+        );
+        let field = Field::new("d", DataType::LargeUtf8, true);
+        let coerced = coerce_array(&hundreds, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(
+            coerced.as_string::<i64>().iter().collect::<Vec<_>>(),
+            vec![Some("1200"), Some("-300"), Some("0")]
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp_strings() {
+        let field = Field::new(
+            "ts",
+            DataType::Timestamp(TimeUnit::Second, Some("+00:00".into())),
+            true,
+        );
+        let iso: Arc<dyn Array> = Arc::new(StringArray::from(vec![
+            Some("2023-01-02T03:04:05Z"),
+            None,
+            Some("2023-01-02T05:04:05+02:00"),
+        ]));
+        let options = CoercionOptions::new().parse_timestamp_strings(None);
+        let coerced = coerce_array(&iso, &field, &options).unwrap();
+        assert_eq!(coerced.data_type(), field.data_type());
+        assert_eq!(
+            coerced.as_primitive::<TimestampSecondType>(),
+            &TimestampSecondArray::from(vec![Some(1672628645), None, Some(1672628645)])
+                .with_timezone("+00:00")
+        );
+
+        let invalid: Arc<dyn Array> = Arc::new(StringArray::from(vec!["2023-01-02", "soon"]));
+        let err = coerce_array(&invalid, &field, &options).unwrap_err();
+        assert!(err.to_string().contains("\"soon\" at row 1"), "{}", err);
+
+        let field = Field::new("ts", DataType::Timestamp(TimeUnit::Millisecond, None), true);
+        let custom: Arc<dyn Array> = Arc::new(StringArray::from(vec![
+            "02/01/2023 03:04",
+            "31/12/1999 23:59",
+        ]));
+        let options =
+            CoercionOptions::new().parse_timestamp_strings(Some("%d/%m/%Y %H:%M".to_string()));
+        let coerced = coerce_array(&custom, &field, &options).unwrap();
+        assert_eq!(
+            coerced.as_primitive::<TimestampMillisecondType>().values(),
+            &[1672628640000, 946684740000]
+        );
+    }
+
+    #[test]
+    fn test_drop_all_null_columns() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, true),
+            Field::new("junk", DataType::Utf8, true),
+        ]));
+        let target = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Int64, true),
+        ]));
+        let batch = |junk: Vec<Option<&str>>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(vec![Some(1), None])),
+                    // Not parseable as integers, but never coerced as it is all null.
+                    Arc::new(StringArray::from(vec![None::<&str>, None])),
+                    Arc::new(StringArray::from(junk)),
+                ],
+            )
+            .unwrap()
+        };
+        let batches = vec![
+            Ok(batch(vec![None, None])),
+            Ok(batch(vec![Some("x"), None])),
+        ];
+        let reader = RecordBatchIterator::new(batches, schema.clone());
+        let options = CoercionOptions::new()
+            .drop_extra_columns(false)
+            .drop_all_null_columns(true);
+        let mut stream = coerce_schema_with_options(reader, target.clone(), options).unwrap();
+        assert_eq!(stream.schema(), target);
+
+        let coerced = stream.next().unwrap().unwrap();
+        assert_eq!(coerced.schema(), target);
+        assert_eq!(
+            coerced.column(0).as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![Some(1), None])
+        );
+        assert_eq!(coerced.column(1).null_count(), 2);
+        // The extra column is only partly null in the second batch.
+        assert!(stream.next().unwrap().is_err());
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch(vec![None, None]))], schema);
+        let options = CoercionOptions::new().drop_extra_columns(false);
+        assert!(coerce_schema_with_options(reader, target, options).is_err());
+    }
+
+    #[test]
+    fn test_vector_permutation() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vec",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 4),
+            true,
+        )]));
+        let vectors = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            vec![
+                Some(vec![Some(0.0), Some(1.0), Some(2.0), Some(3.0)]),
+                None,
+                Some(vec![Some(4.0), None, Some(6.0), Some(7.0)]),
+            ],
+            4,
+        );
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+        let coerce = |permutation: Vec<usize>| {
+            let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+            let options = CoercionOptions::new()
+                .vector_permutation(HashMap::from([("vec".to_string(), permutation)]));
+            coerce_schema_with_options(reader, schema.clone(), options)
+                .unwrap()
+                .collect::<std::result::Result<Vec<_>, _>>()
+        };
+
+        let batches = coerce(vec![2, 0, 3, 1]).unwrap();
+        let permuted = batches[0].column(0).as_fixed_size_list();
+        assert_eq!(
+            permuted.value(0).as_primitive::<Float32Type>(),
+            &Float32Array::from(vec![2.0, 0.0, 3.0, 1.0])
+        );
+        assert!(permuted.is_null(1));
+        assert_eq!(
+            permuted.value(2).as_primitive::<Float32Type>(),
+            &Float32Array::from(vec![Some(6.0), Some(4.0), Some(7.0), None])
+        );
+
+        assert!(coerce(vec![0, 1, 2]).is_err());
+        assert!(coerce(vec![0, 1, 1, 3]).is_err());
+        assert!(coerce(vec![0, 1, 2, 4]).is_err());
+    }
+
+    #[test]
+    fn test_require_unit_vectors() {
+        let vector_type =
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2);
+        let schema = Arc::new(Schema::new(vec![Field::new("vec", vector_type, true)]));
+        let batch = |values: Vec<f32>| {
+            let values = Float32Array::from(values);
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(
+                    FixedSizeListArray::try_new_from_values(values, 2).unwrap(),
+                )],
+            )
+            .unwrap()
+        };
+        let coerce = |batches: Vec<RecordBatch>, normalize: bool| {
+            let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+            let options = CoercionOptions::new()
+                .require_unit_vectors(0.01)
+                .normalize_unit_vectors(normalize);
+            coerce_schema_with_options(reader, schema.clone(), options)
+                .unwrap()
+                .collect::<std::result::Result<Vec<_>, _>>()
+        };
+
+        let within = batch(vec![1.0, 0.0, 0.6, 0.801]);
+        assert_eq!(
+            coerce(vec![within.clone()], false).unwrap(),
+            vec![within.clone()]
+        );
+
+        let outside = batch(vec![0.0, 1.0, 3.0, 4.0]);
+        let err = coerce(vec![within.clone(), outside.clone()], false).unwrap_err();
+        assert!(err.to_string().contains("row 3"), "{}", err);
+        assert!(err.to_string().contains("norm of 5"), "{}", err);
+
+        let normalized = coerce(vec![outside], true).unwrap();
+        assert_eq!(
+            normalized[0]
+                .column(0)
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float32Type>()
+                .values(),
+            &[0.0, 1.0, 0.6, 0.8]
+        );
+        assert!(coerce(vec![batch(vec![0.0, 0.0, 1.0, 0.0])], true).is_err());
+
+        // A NaN or infinite norm is never a unit norm, nor can it be normalized.
+        for invalid in [f32::NAN, f32::INFINITY] {
+            let err = coerce(vec![batch(vec![1.0, 0.0, invalid, 0.0])], false).unwrap_err();
+            assert!(err.to_string().contains("row 1"), "{}", err);
+            assert!(coerce(vec![batch(vec![1.0, 0.0, invalid, 0.0])], true).is_err());
+        }
+
+        // Float64 vectors are normalized in Float64, and the unit vectors are untouched.
+        let vector_type =
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float64, true)), 2);
+        let schema = Arc::new(Schema::new(vec![Field::new("vec", vector_type, true)]));
+        let within = 0.1_f64 + 1e-12;
+        let values = Float64Array::from(vec![within, (1.0 - within * within).sqrt(), 3.0, 4.0]);
+        let vectors = FixedSizeListArray::try_new_from_values(values, 2).unwrap();
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let options = CoercionOptions::new()
+            .require_unit_vectors(0.01)
+            .normalize_unit_vectors(true);
+        let batches = coerce_schema_with_options(reader, schema, options)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        let values = batches[0]
+            .column(0)
+            .as_fixed_size_list()
+            .values()
+            .as_primitive::<Float64Type>()
+            .values()
+            .to_vec();
+        assert_eq!(values[0], within);
+        assert_eq!(&values[2..], &[0.6, 0.8]);
+    }
+
+    #[test]
+    fn test_zero_norm_vectors() {
+        let vector_type =
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2);
+        let schema = Arc::new(Schema::new(vec![Field::new("vec", vector_type, true)]));
+        let vectors = FixedSizeListArray::try_new_from_values(
+            Float32Array::from(vec![3.0, 4.0, 0.0, 0.0, 1.0, 0.0]),
+            2,
+        )
+        .unwrap();
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let options = CoercionOptions::new()
+            .require_unit_vectors(0.01)
+            .normalize_unit_vectors(true)
+            .on_zero_norm_vectors(ZeroNormPolicy::Report);
+
+        let (stream, report) = coerce_schema_with_report(reader, schema, options).unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            batches[0]
+                .column(0)
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float32Type>()
+                .values(),
+            &[0.6, 0.8, 0.0, 0.0, 1.0, 0.0]
+        );
+        let report = report.lock().unwrap();
+        assert_eq!(report.columns["vec"].zero_norm_vectors, 1);
+        assert!(report.to_string().contains("1 zero-norm vectors"));
+    }
+
+    #[tokio::test]
+    async fn test_coerce_to_dataset_schema() {
+        let vector_type = |dim| {
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim)
+        };
+        let dataset_schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("vec", vector_type(2), true),
+        ]));
+        let batch = RecordBatch::try_new(
+            dataset_schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1])),
+                Arc::new(
+                    FixedSizeListArray::try_new_from_values(Float32Array::from(vec![1.0, 2.0]), 2)
+                        .unwrap(),
+                ),
+            ],
+        )
+        .unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], dataset_schema.clone());
+        let dataset = Dataset::write(batches, "memory://coerce", None)
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                "vec",
+                DataType::List(Arc::new(Field::new("item", DataType::Float64, true))),
+                true,
+            ),
+            Field::new("id", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(ListArray::from_iter_primitive::<Float64Type, _, _>(vec![
+                    Some(vec![Some(3.0), Some(4.0)]),
+                ])),
+                Arc::new(Int32Array::from(vec![2])),
             ],
         )
         .unwrap();
-        let reader =
-            RecordBatchIterator::new(vec![batch.clone()].into_iter().map(Ok), schema.clone());
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let stream = coerce_to_dataset_schema(reader, &dataset).unwrap();
+        assert_eq!(stream.schema(), dataset_schema);
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            batches[0].column(0).as_primitive::<Int64Type>().values(),
+            &[2]
+        );
 
-        let expected_schema = Arc::new(Schema::new(vec![
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("vec", vector_type(3), true),
+        ]));
+        let reader = RecordBatchIterator::new(vec![], schema);
+        assert!(coerce_to_dataset_schema(reader, &dataset).is_err());
+    }
+
+    #[test]
+    fn test_coerce_fixed_size_list_rewraps_values() {
+        let values = Float32Array::from_iter_values((0..6).map(|v| v as f32));
+        let vectors: Arc<dyn Array> = Arc::new(
+            FixedSizeListArray::try_new(
+                Arc::new(Field::new("values", DataType::Float32, false)),
+                3,
+                Arc::new(values),
+                None,
+            )
+            .unwrap(),
+        );
+        let field = Field::new(
+            "vec",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 3),
+            true,
+        );
+
+        let coerced = coerce_array(&vectors, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(coerced.data_type(), field.data_type());
+        let values_ptr = |array: &Arc<dyn Array>| {
+            array
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float32Type>()
+                .values()
+                .as_ptr()
+        };
+        assert_eq!(values_ptr(&coerced), values_ptr(&vectors));
+    }
+
+    #[test]
+    fn test_duration_as_unit() {
+        let durations: Arc<dyn Array> = Arc::new(DurationMillisecondArray::from(vec![
+            Some(1500),
+            None,
+            Some(-2000),
+        ]));
+        let options = CoercionOptions::new().duration_as_unit(TimeUnit::Second);
+
+        let field = Field::new("seconds", DataType::Int64, true);
+        let coerced = coerce_array(&durations, &field, &options).unwrap();
+        assert_eq!(
+            coerced.as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![Some(1), None, Some(-2)])
+        );
+
+        let field = Field::new("seconds", DataType::Float64, true);
+        let coerced = coerce_array(&durations, &field, &options).unwrap();
+        assert_eq!(
+            coerced.as_primitive::<Float64Type>(),
+            &Float64Array::from(vec![Some(1.5), None, Some(-2.0)])
+        );
+
+        let options = CoercionOptions::new().duration_as_unit(TimeUnit::Microsecond);
+        let field = Field::new("micros", DataType::Int64, true);
+        let coerced = coerce_array(&durations, &field, &options).unwrap();
+        assert_eq!(coerced.as_primitive::<Int64Type>().value(0), 1_500_000);
+    }
+
+    #[test]
+    fn test_run_end_encode() {
+        let field = Field::new(
+            "status",
+            DataType::RunEndEncoded(
+                Arc::new(Field::new("run_ends", DataType::Int32, false)),
+                Arc::new(Field::new("values", DataType::Utf8, true)),
+            ),
+            true,
+        );
+        let dictionary: Arc<dyn Array> = Arc::new(
+            vec![
+                Some("a"),
+                Some("a"),
+                Some("a"),
+                None,
+                None,
+                Some("b"),
+                Some("a"),
+            ]
+            .into_iter()
+            .collect::<DictionaryArray<Int8Type>>(),
+        );
+
+        let coerced = coerce_array(&dictionary, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(coerced.data_type(), field.data_type());
+        let runs = coerced
+            .as_any()
+            .downcast_ref::<RunArray<Int32Type>>()
+            .unwrap();
+        assert_eq!(runs.len(), 7);
+        assert_eq!(runs.run_ends().values(), &[3, 5, 6, 7]);
+        assert_eq!(
+            runs.values().as_string::<i32>(),
+            &StringArray::from(vec![Some("a"), None, Some("b"), Some("a")])
+        );
+
+        let narrow = Field::new(
+            "status",
+            DataType::RunEndEncoded(
+                Arc::new(Field::new("run_ends", DataType::Int16, false)),
+                Arc::new(Field::new("values", DataType::Int64, true)),
+            ),
+            true,
+        );
+        let long: Arc<dyn Array> = Arc::new(Int64Array::from_iter_values(0..40_000));
+        assert!(coerce_array(&long, &narrow, &CoercionOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_list_delimiter() {
+        let item = Arc::new(Field::new("item", DataType::Utf8, true));
+        let list_field = Field::new("tags", DataType::List(item.clone()), true);
+        let string_field = Field::new("tags", DataType::Utf8, true);
+        let options = CoercionOptions::new().list_delimiter(',');
+
+        let mut builder = ListBuilder::new(StringBuilder::new());
+        builder.append_value([Some("a"), Some("b")]);
+        builder.append_null();
+        builder.append_value([Some("c"), None]);
+        builder.append_value(Vec::<Option<&str>>::new());
+        let lists: Arc<dyn Array> = Arc::new(builder.finish());
+
+        let joined = coerce_array(&lists, &string_field, &options).unwrap();
+        assert_eq!(
+            joined.as_string::<i32>(),
+            &StringArray::from(vec![Some("a,b"), None, Some("c"), Some("")])
+        );
+
+        let strings: Arc<dyn Array> = Arc::new(StringArray::from(vec![
+            Some("a,b"),
+            None,
+            Some("c"),
+            Some(""),
+        ]));
+        let split = coerce_array(&strings, &list_field, &options).unwrap();
+        let split = split.as_list::<i32>();
+        assert_eq!(
+            split.value(0).as_string::<i32>(),
+            &StringArray::from(vec!["a", "b"])
+        );
+        assert!(split.is_null(1));
+        assert_eq!(
+            split.value(2).as_string::<i32>(),
+            &StringArray::from(vec!["c"])
+        );
+        assert_eq!(split.value(3).len(), 0);
+
+        // Without a delimiter, each string is a single-element list.
+        let wrapped = coerce_array(&strings, &list_field, &CoercionOptions::default()).unwrap();
+        assert_eq!(wrapped.as_list::<i32>().value(0).len(), 1);
+    }
+
+    #[test]
+    fn test_decode_geohash() {
+        let field = Field::new(
+            "location",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float64, true)), 2),
+            true,
+        );
+        let geohashes: Arc<dyn Array> = Arc::new(StringArray::from(vec![
+            Some("ezs42"),
+            None,
+            Some("u4pruydqqvj"),
+        ]));
+        let options = CoercionOptions::new().decode_geohash(true);
+
+        let coerced = coerce_array(&geohashes, &field, &options).unwrap();
+        let locations = coerced.as_fixed_size_list();
+        assert!(locations.is_null(1));
+        let expected = [(0, [42.605, -5.603]), (2, [57.64911, 10.40744])];
+        for (row, [lat, lon]) in expected {
+            let location = locations.value(row);
+            let location = location.as_primitive::<Float64Type>().values();
+            assert!((location[0] - lat).abs() < 1e-3, "{:?}", location);
+            assert!((location[1] - lon).abs() < 1e-3, "{:?}", location);
+        }
+
+        let invalid: Arc<dyn Array> = Arc::new(StringArray::from(vec!["u4pru", "ezs4a"]));
+        let err = coerce_array(&invalid, &field, &options).unwrap_err();
+        assert!(err.to_string().contains("row 1"), "{}", err);
+        assert!(coerce_array(&geohashes, &field, &CoercionOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_decode_base64_vectors() {
+        let bytes = [1.0f32, -2.5, 0.125, 3e8]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        let encoded = BASE64.encode(bytes);
+        let strings: Arc<dyn Array> =
+            Arc::new(StringArray::from(vec![Some(encoded.as_str()), None]));
+        let vector_field = |dim| {
             Field::new(
-                "fl",
-                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float16, true)), 64),
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
                 true,
+            )
+        };
+
+        let options = CoercionOptions::new()
+            .decode_base64_vectors(Some(4))
+            .endianness(Endianness::Little);
+        let vectors = coerce_array(&strings, &vector_field(4), &options).unwrap();
+        let vectors = vectors.as_fixed_size_list();
+        assert_eq!(
+            vectors.value(0).as_primitive::<Float32Type>().values(),
+            &[1.0, -2.5, 0.125, 3e8]
+        );
+        assert!(vectors.is_null(1));
+
+        assert!(coerce_array(&strings, &vector_field(2), &options).is_err());
+        let options = CoercionOptions::new().decode_base64_vectors(Some(2));
+        let err = coerce_array(&strings, &vector_field(2), &options).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected 8 bytes for dimension 2 but got 16 at row 0"));
+        let invalid: Arc<dyn Array> = Arc::new(StringArray::from(vec!["not base64!"]));
+        assert!(coerce_array(&invalid, &vector_field(2), &options).is_err());
+    }
+
+    #[test]
+    fn test_decode_bf16() {
+        // bf16 bits of 1.0, -2.0, 0.5 and 0.10009765625.
+        let bits = [0x3f80u16, 0xc000, 0x3f00, 0x3dcd];
+        let expected = [1.0f32, -2.0, 0.5, 0.1];
+        let vector_field = |dt| {
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", dt, true)), 2),
+                true,
+            )
+        };
+        let options = CoercionOptions::new()
+            .decode_bf16(true)
+            .endianness(Endianness::Little);
+
+        let lists: Arc<dyn Array> = Arc::new(
+            FixedSizeListArray::try_new_from_values(UInt16Array::from(bits.to_vec()), 2).unwrap(),
+        );
+        let vectors = coerce_array(&lists, &vector_field(DataType::Float32), &options).unwrap();
+        let values = vectors
+            .as_fixed_size_list()
+            .values()
+            .as_primitive::<Float32Type>();
+        for (v, e) in values.values().iter().zip(expected) {
+            assert!((v - e).abs() < 1e-3);
+        }
+
+        let bytes = bits
+            .iter()
+            .flat_map(|b| b.to_le_bytes())
+            .collect::<Vec<_>>();
+        let binary: Arc<dyn Array> =
+            Arc::new(FixedSizeBinaryArray::try_from_iter(bytes.chunks(4)).unwrap());
+        let vectors = coerce_array(&binary, &vector_field(DataType::Float16), &options).unwrap();
+        let values = vectors
+            .as_fixed_size_list()
+            .values()
+            .as_primitive::<Float16Type>();
+        for (v, e) in values.values().iter().zip(expected) {
+            assert!((v.to_f32() - e).abs() < 1e-3);
+        }
+        assert!(coerce_array(
+            &binary,
+            &vector_field(DataType::Float16),
+            &CoercionOptions::default()
+        )
+        .is_err());
+        assert!(coerce_array(
+            &binary,
+            &Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 4),
+                true
             ),
-            Field::new("s", DataType::Utf8, true),
-            Field::new("f", DataType::Float64, true),
-            Field::new("i", DataType::Int8, true),
+            &options
+        )
+        .is_err());
+
+        let scalars: Arc<dyn Array> = Arc::new(UInt16Array::from(vec![Some(0x3f80), None]));
+        let floats = coerce_array(
+            &scalars,
+            &Field::new("a", DataType::Float32, true),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(floats.as_primitive::<Float32Type>().value(0), 1.0);
+        assert!(floats.is_null(1));
+    }
+
+    #[test]
+    fn test_boolean_vector_round_trip() {
+        let vector_field = Field::new(
+            "flag",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::UInt8, true)), 1),
+            true,
+        );
+        let flags: Arc<dyn Array> =
+            Arc::new(BooleanArray::from(vec![Some(true), None, Some(false)]));
+        let options = CoercionOptions::default();
+
+        let vectors = coerce_array(&flags, &vector_field, &options).unwrap();
+        assert_eq!(vectors.data_type(), vector_field.data_type());
+        let list = vectors.as_fixed_size_list();
+        assert!(list.is_null(1));
+        assert_eq!(list.value(0).as_primitive::<UInt8Type>().values(), &[1]);
+        assert_eq!(list.value(2).as_primitive::<UInt8Type>().values(), &[0]);
+
+        let bool_field = Field::new("flag", DataType::Boolean, true);
+        let round_trip = coerce_array(&vectors, &bool_field, &options).unwrap();
+        assert_eq!(round_trip.as_boolean(), flags.as_boolean());
+
+        let invalid: Arc<dyn Array> = Arc::new(
+            FixedSizeListArray::try_new_from_values(UInt8Array::from(vec![1, 2]), 1).unwrap(),
+        );
+        assert!(coerce_array(&invalid, &bool_field, &options).is_err());
+    }
+
+    #[cfg(feature = "datafusion")]
+    #[test]
+    fn test_expressions() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("name", DataType::Utf8, true),
         ]));
-        let stream = coerce_schema(reader, expected_schema.clone()).unwrap();
-        let batches = stream.collect::<Vec<_>>();
-        assert_eq!(batches.len(), 1);
-        let batch = batches[0].as_ref().unwrap();
-        assert_eq!(batch.schema(), expected_schema);
+        let target = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("label", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec![Some("alice"), None, Some("Bob")])),
+            ],
+        )
+        .unwrap();
+        let expressions = HashMap::from([
+            ("name".to_string(), "upper(name)".to_string()),
+            ("label".to_string(), "concat('#', id)".to_string()),
+        ]);
+        let options = CoercionOptions::new().expressions(expressions);
 
-        let expected = RecordBatch::try_new(
-            expected_schema,
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let batches = coerce_schema_with_options(reader, target.clone(), options)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches[0].schema(), target);
+        assert_eq!(
+            batches[0].column(1).as_string::<i32>(),
+            &StringArray::from(vec![Some("ALICE"), None, Some("BOB")])
+        );
+        assert_eq!(
+            batches[0].column(2).as_string::<i32>(),
+            &StringArray::from(vec!["#1", "#2", "#3"])
+        );
+
+        // A computed copy of a column shares its buffers.
+        let options = CoercionOptions::new()
+            .expressions(HashMap::from([("label".to_string(), "name".to_string())]));
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let batches = coerce_schema_with_options(reader, target.clone(), options)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches[0].column(2), batches[0].column(1));
+
+        let options = CoercionOptions::new()
+            .expressions(HashMap::from([("name".to_string(), "nope(".to_string())]));
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let err = coerce_schema_with_options(reader, target, options)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Invalid expression nope("));
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_unicode_normalize() {
+        let schema = Arc::new(Schema::new(vec![Field::new("name", DataType::Utf8, true)]));
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        assert_ne!(composed, decomposed);
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from(vec![
+                Some(composed),
+                Some(decomposed),
+                None,
+            ]))],
+        )
+        .unwrap();
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let options = CoercionOptions::new().unicode_normalize(Some(NfForm::Nfc));
+        let batches = coerce_schema_with_options(reader, schema, options)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            batches[0].column(0).as_string::<i32>(),
+            &StringArray::from(vec![Some(composed), Some(composed), None])
+        );
+    }
+
+    #[test]
+    fn test_defaults() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, true)]));
+        let target = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("status", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![Some(1), None]))],
+        )
+        .unwrap();
+        let defaults = HashMap::from([
+            (
+                "status".to_string(),
+                Arc::new(StringArray::from(vec!["pending"])) as Arc<dyn Array>,
+            ),
+            (
+                "id".to_string(),
+                Arc::new(Int32Array::from(vec![-1])) as Arc<dyn Array>,
+            ),
+        ]);
+        let options = CoercionOptions::new()
+            .fill_non_nullable_with_default(Arc::new(Int32Array::from(vec![0])))
+            .defaults(defaults);
+        assert!(!plan_coercion(&schema, &target, &options).has_errors());
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let batches = coerce_schema_with_options(reader, target.clone(), options)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            batches[0].column(0).as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![1, -1])
+        );
+        assert_eq!(
+            batches[0].column(1).as_string::<i32>(),
+            &StringArray::from(vec!["pending", "pending"])
+        );
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let mut stream =
+            coerce_schema_with_options(reader, target, CoercionOptions::default()).unwrap();
+        assert!(stream.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_timestamp_as_epoch_unit() {
+        let timestamps: Arc<dyn Array> = Arc::new(
+            TimestampMicrosecondArray::from(vec![Some(1_700_000_000_123_456), None])
+                .with_timezone("UTC"),
+        );
+        let field = Field::new("epoch", DataType::Int64, true);
+
+        let options = CoercionOptions::new().timestamp_as_epoch_unit(TimeUnit::Millisecond);
+        let coerced = coerce_array(&timestamps, &field, &options).unwrap();
+        assert_eq!(
+            coerced.as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![Some(1_700_000_000_123), None])
+        );
+
+        let options = CoercionOptions::new().timestamp_as_epoch_unit(TimeUnit::Nanosecond);
+        let coerced = coerce_array(&timestamps, &field, &options).unwrap();
+        assert_eq!(
+            coerced.as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![Some(1_700_000_000_123_456_000), None])
+        );
+    }
+
+    #[test]
+    fn test_coerce_schema_with_rejects() {
+        let input = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new(
+                "vec",
+                DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+                true,
+            ),
+        ]));
+        let target = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                true,
+            ),
+        ]));
+        // Rows 1 and 3 have the wrong vector length.
+        let batch = RecordBatch::try_new(
+            input.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![0, 1, 2, 3, 4])),
+                Arc::new(ListArray::from_iter_primitive::<Float32Type, _, _>(vec![
+                    Some(vec![Some(0.0), Some(0.5)]),
+                    Some(vec![Some(1.0)]),
+                    Some(vec![Some(2.0), Some(2.5)]),
+                    Some(vec![Some(3.0), Some(3.5), Some(3.75)]),
+                    Some(vec![Some(4.0), Some(4.5)]),
+                ])),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], input.clone());
+
+        let (good, rejects) =
+            coerce_schema_with_rejects(reader, target.clone(), CoercionOptions::default()).unwrap();
+        assert_eq!(rejects.schema().fields().len(), 3);
+        assert_eq!(rejects.schema().field(2).name(), REJECT_REASON_COLUMN);
+        let good = good.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(good.len(), 1);
+        assert_eq!(good[0].schema(), target);
+        assert_eq!(
+            good[0].column(0).as_primitive::<Int64Type>().values(),
+            &[0, 2, 4]
+        );
+        assert_eq!(
+            good[0]
+                .column(1)
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float32Type>()
+                .values(),
+            &[0.0, 0.5, 2.0, 2.5, 4.0, 4.5]
+        );
+
+        let rejects = rejects.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rejects.len(), 1);
+        assert_eq!(
+            rejects[0].column(0).as_primitive::<Int32Type>().values(),
+            &[1, 3]
+        );
+        let reasons = rejects[0].column(2).as_string::<i32>();
+        assert!(reasons.value(0).contains("expected dimension 2 but got 1"));
+        assert!(reasons.value(1).contains("expected dimension 2 but got 3"));
+
+        // The labels of the rejected rows get no code.
+        let input = Arc::new(Schema::new(vec![
+            Field::new("label", DataType::Utf8, true),
+            input.field(1).clone(),
+        ]));
+        let target = Arc::new(Schema::new(vec![
+            Field::new("label", DataType::Int32, true),
+            target.field(1).clone(),
+        ]));
+        let batch = RecordBatch::try_new(
+            input.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+                Arc::new(ListArray::from_iter_primitive::<Float32Type, _, _>(vec![
+                    Some(vec![Some(0.0), Some(0.5)]),
+                    Some(vec![Some(1.0)]),
+                    Some(vec![Some(2.0), Some(2.5)]),
+                ])),
+            ],
+        )
+        .unwrap();
+        let options = CoercionOptions::new()
+            .label_to_int(HashMap::from([("label".to_string(), HashMap::new())]))
+            .on_unknown_label(UnknownLabel::AssignNew);
+        let codes = options.label_codes("label").unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], input.clone());
+        let (good, rejects) = coerce_schema_with_rejects(reader, target.clone(), options).unwrap();
+        let good = good.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            good[0].column(0).as_primitive::<Int32Type>().values(),
+            &[0, 1]
+        );
+        assert_eq!(rejects.count(), 1);
+        let codes = codes.lock().unwrap();
+        assert_eq!(
+            *codes,
+            HashMap::from([("a".to_string(), 0), ("c".to_string(), 1)])
+        );
+
+        // Errors that fail every row alike fail the stream, rather than rejecting the rows.
+        let options = CoercionOptions::new().max_rows(Some(2));
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], input.clone());
+        let (mut good, mut rejects) =
+            coerce_schema_with_rejects(reader, target.clone(), options).unwrap();
+        let err = good.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("more than 2 rows"));
+        assert!(rejects.next().is_none());
+
+        let mut fields = target.fields().to_vec();
+        fields.push(Arc::new(Field::new("missing", DataType::Int32, true)));
+        let target = Arc::new(Schema::new(fields));
+        let reader = RecordBatchIterator::new(vec![Ok(batch.slice(0, 0)), Ok(batch)], input);
+        let (good, rejects) =
+            coerce_schema_with_rejects(reader, target, CoercionOptions::default()).unwrap();
+        let good = good.collect::<Vec<_>>();
+        assert_eq!(good.len(), 2);
+        assert!(good[0].is_err());
+        let err = good[1].as_ref().unwrap_err();
+        assert!(err.to_string().contains("Column missing not found"));
+        assert_eq!(rejects.count(), 0);
+    }
+
+    #[test]
+    fn test_require_dim_divisible_by() {
+        let vector_schema = |dim| {
+            Arc::new(Schema::new(vec![Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
+                true,
+            )]))
+        };
+        let options = CoercionOptions::new().require_dim_divisible_by(Some(8));
+
+        let schema = vector_schema(768);
+        let reader = RecordBatchIterator::new(vec![], schema.clone());
+        assert!(coerce_schema_with_options(reader, schema, options.clone()).is_ok());
+
+        let schema = vector_schema(100);
+        let reader = RecordBatchIterator::new(vec![], schema.clone());
+        let Err(err) = coerce_schema_with_options(reader, schema, options) else {
+            panic!("expected dimension 100 to fail");
+        };
+        assert!(err
+            .to_string()
+            .contains("Vector column vec has dimension 100, which is not divisible by 8"));
+    }
+
+    #[test]
+    fn test_max_vector_dim() {
+        let vector_schema = |dim| {
+            Arc::new(Schema::new(vec![Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
+                true,
+            )]))
+        };
+        let options = CoercionOptions::new().max_vector_dim(Some(2048));
+
+        let schema = vector_schema(1024);
+        let reader = RecordBatchIterator::new(vec![], schema.clone());
+        assert!(coerce_schema_with_options(reader, schema, options.clone()).is_ok());
+
+        let schema = vector_schema(4096);
+        let reader = RecordBatchIterator::new(vec![], schema.clone());
+        let Err(err) = coerce_schema_with_options(reader, schema, options) else {
+            panic!("expected dimension 4096 to fail");
+        };
+        assert!(err
+            .to_string()
+            .contains("Vector column vec has dimension 4096, which exceeds the maximum of 2048"));
+    }
+
+    #[test]
+    fn test_reorder_dimensions() {
+        let vector_field = |order: &str| {
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 3),
+                true,
+            )
+            .with_metadata(HashMap::from([(
+                DIMENSION_ORDER_METADATA_KEY.to_string(),
+                order.to_string(),
+            )]))
+        };
+        let input = Arc::new(Schema::new(vec![vector_field("y,z,x")]));
+        let batch = RecordBatch::try_new(
+            input.clone(),
+            vec![Arc::new(
+                FixedSizeListArray::try_new_from_values(
+                    Float32Array::from(vec![2.0, 3.0, 1.0, 5.0, 6.0, 4.0]),
+                    3,
+                )
+                .unwrap(),
+            )],
+        )
+        .unwrap();
+        let coerce = |order: &str| {
+            let target = Arc::new(Schema::new(vec![vector_field(order)]));
+            let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], input.clone());
+            coerce_schema(reader, target)
+                .unwrap()
+                .collect::<std::result::Result<Vec<_>, _>>()
+        };
+
+        let batches = coerce("x, y, z").unwrap();
+        assert_eq!(
+            batches[0]
+                .column(0)
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float32Type>()
+                .values(),
+            &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]
+        );
+        let err = coerce("x,y,w").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Column vec has incompatible dimension orders: [y,z,x] and [x,y,w]"));
+    }
+
+    #[test]
+    fn test_check_declared_dimensions() {
+        let vector_field = |dim| {
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
+                true,
+            )
+            .with_metadata(HashMap::from([(
+                VECTOR_DIMENSION_METADATA_KEY.to_string(),
+                "768".to_string(),
+            )]))
+        };
+        let input = Arc::new(Schema::new(vec![Field::new(
+            "vec",
+            DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+            true,
+        )]));
+
+        let target = Arc::new(Schema::new(vec![vector_field(512)]));
+        let reader = RecordBatchIterator::new(vec![], input.clone());
+        let Err(err) = coerce_schema(reader, target) else {
+            panic!("expected the dimension mismatch to fail");
+        };
+        assert!(err.to_string().contains(
+            "Vector column vec declares dimension 768 in its lance:vector_dim metadata, but has dimension 512"
+        ));
+
+        let target = Arc::new(Schema::new(vec![vector_field(768)]));
+        let reader = RecordBatchIterator::new(vec![], input);
+        assert!(coerce_schema(reader, target).is_ok());
+    }
+
+    #[test]
+    fn test_pipeline_order() {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Float64, true)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Float64Array::from(vec![0.8, 0.25]))],
+        )
+        .unwrap();
+        let coerce = |options: CoercionOptions| {
+            let options = options
+                .clamp(HashMap::from([("a".to_string(), (0.0, 0.74))]))
+                .round_floats(HashMap::from([("a".to_string(), 1)]));
+            let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+            let batches = coerce_schema_with_options(reader, schema.clone(), options)
+                .unwrap()
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .unwrap();
+            batches[0]
+                .column(0)
+                .as_primitive::<Float64Type>()
+                .values()
+                .to_vec()
+        };
+
+        // Clamp, then round.
+        assert_eq!(coerce(CoercionOptions::new()), vec![0.7, 0.2]);
+        let options = CoercionOptions::new().pipeline_order(vec![PipelineStep::Round]);
+        assert_eq!(coerce(options), vec![0.74, 0.2]);
+    }
+
+    #[test]
+    fn test_round_floats() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("f", DataType::Float64, true),
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                true,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
             vec![
+                Arc::new(Float64Array::from(vec![
+                    Some(1.23456),
+                    Some(0.0625),
+                    Some(0.1875),
+                    Some(-0.0625),
+                    Some(1e300),
+                    None,
+                ])),
                 Arc::new(
-                    FixedSizeListArray::try_new_from_values(
-                        Float16Array::from_iter_values((0..256).map(|v| f16::from_f32(v as f32))),
-                        64,
-                    )
-                    .unwrap(),
+                    FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+                        (0..6).map(|_| Some(vec![Some(0.0625), Some(2.00049)])),
+                        2,
+                    ),
                 ),
-                Arc::new(StringArray::from(vec![
-                    Some("hello"),
-                    Some("world"),
-                    Some("from"),
-                    Some("lance"),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+
+        let options = CoercionOptions::new().round_floats(HashMap::from([
+            ("f".to_string(), 3),
+            ("vec".to_string(), 3),
+        ]));
+        let stream = coerce_schema_with_options(reader, schema, options).unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        // Exact ties are rounded half to even.
+        assert_eq!(
+            batches[0].column(0).as_primitive::<Float64Type>(),
+            &Float64Array::from(vec![
+                Some(1.235),
+                Some(0.062),
+                Some(0.188),
+                Some(-0.062),
+                Some(1e300),
+                None,
+            ])
+        );
+        assert_eq!(
+            batches[0]
+                .column(1)
+                .as_fixed_size_list()
+                .value(0)
+                .as_primitive::<Float32Type>()
+                .values(),
+            &[0.062, 2.0]
+        );
+
+        let array: Arc<dyn Array> = Arc::new(Int32Array::from(vec![1]));
+        let field = Field::new("i", DataType::Int32, true);
+        assert!(round_values(&array, &field, 3).is_err());
+    }
+
+    #[test]
+    fn test_audit_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let input = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let target = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, true)]));
+        let batch =
+            RecordBatch::try_new(input.clone(), vec![Arc::new(Int32Array::from(vec![1, 2]))])
+                .unwrap();
+        let options = CoercionOptions::new().audit_log(Some(path.clone()));
+
+        for _ in 0..2 {
+            let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], input.clone());
+            let stream =
+                coerce_schema_with_options(reader, target.clone(), options.clone()).unwrap();
+            stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        }
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let record: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert!(record["timestamp"].is_string());
+        assert_eq!(record["source_schema"][0]["type"], "Int32");
+        assert_eq!(record["target_schema"][0]["type"], "Int64");
+        assert_eq!(record["plan"][0]["name"], "a");
+        assert_eq!(record["plan"][0]["action"], "coerce");
+        assert_eq!(record["batches"], 1);
+        assert_eq!(record["rows"], 2);
+    }
+
+    #[test]
+    fn test_clamp() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("score", DataType::Float64, true),
+            Field::new("age", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Float64Array::from(vec![
+                    Some(-0.5),
+                    None,
+                    Some(0.25),
+                    Some(1.5),
                 ])),
-                Arc::new(Float64Array::from_iter_values((0..4).map(|v| v as f64))),
-                Arc::new(Int8Array::from_iter_values(0..4)),
+                Arc::new(Int32Array::from(vec![Some(-3), Some(42), None, Some(200)])),
             ],
         )
         .unwrap();
-        assert_eq!(batch, &expected);
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+
+        let options = CoercionOptions::new().clamp(HashMap::from([
+            ("score".to_string(), (0.0, 1.0)),
+            ("age".to_string(), (0.0, 130.0)),
+        ]));
+        let stream = coerce_schema_with_options(reader, schema, options).unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            batches[0].column(0).as_primitive::<Float64Type>(),
+            &Float64Array::from(vec![Some(0.0), None, Some(0.25), Some(1.0)])
+        );
+        assert_eq!(
+            batches[0].column(1).as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![Some(0), Some(42), None, Some(130)])
+        );
+    }
+
+    #[test]
+    fn test_fixed_size_binary_hex() {
+        let binary: Arc<dyn Array> = Arc::new(
+            FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+                vec![Some(vec![0xde, 0xad, 0xBE, 0x0f]), None].into_iter(),
+                4,
+            )
+            .unwrap(),
+        );
+        let hex_field = Field::new("id", DataType::Utf8, true);
+        let options = CoercionOptions::default();
+        let hex = coerce_array(&binary, &hex_field, &options).unwrap();
+        assert_eq!(
+            hex.as_string::<i32>().iter().collect::<Vec<_>>(),
+            vec![Some("deadbe0f"), None]
+        );
+
+        let binary_field = Field::new("id", DataType::FixedSizeBinary(4), true);
+        let round_trip = coerce_array(&hex, &binary_field, &options).unwrap();
+        assert_eq!(round_trip.as_ref(), binary.as_ref());
+
+        let invalid: Arc<dyn Array> = Arc::new(StringArray::from(vec!["deadbe0f", "deadbexf"]));
+        let err = coerce_array(&invalid, &binary_field, &options).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("invalid hex string \"deadbexf\" at row 1"));
+        let short: Arc<dyn Array> = Arc::new(StringArray::from(vec!["dead"]));
+        let err = coerce_array(&short, &binary_field, &options).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected 8 hex characters but got 4"));
+    }
+
+    #[test]
+    fn test_coerce_half_float_to_integer_narrowing() {
+        let arr: Arc<dyn Array> = Arc::new(Float16Array::from(vec![
+            f16::from_f32(1.5),
+            f16::NAN,
+            f16::from_f32(70000.0),
+        ]));
+        let field = Field::new("i", DataType::Int16, true);
+        let coerced = coerce_array(&arr, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(
+            coerced.as_primitive::<Int16Type>(),
+            &Int16Array::from(vec![Some(1), None, None])
+        );
+
+        let options = CoercionOptions::new().integer_narrowing(NarrowingPolicy::CheckValues);
+        assert!(coerce_array(&arr.slice(1, 1), &field, &options).is_err());
+        assert!(coerce_array(&arr.slice(2, 1), &field, &options).is_err());
+        assert!(coerce_array(&arr.slice(0, 1), &field, &options).is_ok());
+        let options = CoercionOptions::new().integer_narrowing(NarrowingPolicy::Error);
+        assert!(coerce_array(&arr, &field, &options).is_err());
+    }
+
+    #[test]
+    fn test_coerce_date_timestamp_out_of_range() {
+        // 2262-04-12 does not fit a nanosecond timestamp.
+        let dates: Arc<dyn Array> = Arc::new(Date32Array::from(vec![Some(0), None, Some(106_752)]));
+        let field = Field::new("ts", DataType::Timestamp(TimeUnit::Nanosecond, None), true);
+        let err = coerce_array(&dates, &field, &CoercionOptions::default()).unwrap_err();
+        assert!(
+            err.to_string().contains("value at row 2 is out of range"),
+            "{}",
+            err
+        );
+        let micros = Field::new("ts", DataType::Timestamp(TimeUnit::Microsecond, None), true);
+        assert!(coerce_array(&dates, &micros, &CoercionOptions::default()).is_ok());
+
+        let timestamps: Arc<dyn Array> = Arc::new(TimestampSecondArray::from(vec![
+            Some(0),
+            Some(i64::MAX / 2),
+        ]));
+        let date = Field::new("d", DataType::Date32, true);
+        let err = coerce_array(&timestamps, &date, &CoercionOptions::default()).unwrap_err();
+        assert!(
+            err.to_string().contains("value at row 1 is out of range"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_rescale_decimal_extremes() {
+        let nines = 10_i128.pow(38) - 1;
+        let decimals: Arc<dyn Array> = Arc::new(
+            Decimal128Array::from(vec![nines, -nines])
+                .with_precision_and_scale(38, 38)
+                .unwrap(),
+        );
+        let field = Field::new("d", DataType::Decimal128(38, 0), true);
+        let rounded = coerce_array(&decimals, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(rounded.as_primitive::<Decimal128Type>().values(), &[1, -1]);
+
+        let integers: Arc<dyn Array> = Arc::new(
+            Decimal128Array::from(vec![0, 10_i128.pow(37)])
+                .with_precision_and_scale(38, 0)
+                .unwrap(),
+        );
+        let field = Field::new("d", DataType::Decimal128(38, 38), true);
+        let err = coerce_array(&integers, &field, &CoercionOptions::default()).unwrap_err();
+        assert!(
+            err.to_string().contains("value at row 1 overflows"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_endianness_of_reinterpreted_bytes() {
+        let vector_field = |dim| {
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
+                true,
+            )
+        };
+        let bytes = [1.5f32, -2.0]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect::<Vec<_>>();
+        let strings: Arc<dyn Array> = Arc::new(StringArray::from(vec![BASE64.encode(bytes)]));
+        let options = CoercionOptions::new()
+            .decode_base64_vectors(Some(2))
+            .endianness(Endianness::Big);
+        let vectors = coerce_array(&strings, &vector_field(2), &options).unwrap();
+        assert_eq!(
+            vectors
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float32Type>()
+                .values(),
+            &[1.5, -2.0]
+        );
+
+        // bf16 bits of 1.0 and -2.0, most significant byte first.
+        let binary: Arc<dyn Array> = Arc::new(
+            FixedSizeBinaryArray::try_from_iter(vec![vec![0x3f, 0x80, 0xc0, 0x00]].into_iter())
+                .unwrap(),
+        );
+        let options = CoercionOptions::new()
+            .decode_bf16(true)
+            .endianness(Endianness::Big);
+        let vectors = coerce_array(&binary, &vector_field(2), &options).unwrap();
+        assert_eq!(
+            vectors
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float32Type>()
+                .values(),
+            &[1.0, -2.0]
+        );
+        let options = options.endianness(Endianness::Little);
+        let vectors = coerce_array(&binary, &vector_field(2), &options).unwrap();
+        assert_ne!(
+            vectors
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float32Type>()
+                .value(0),
+            1.0
+        );
     }
 }
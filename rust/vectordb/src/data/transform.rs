@@ -0,0 +1,3487 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Streaming transforms that reshape the columns of the input data.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
+
+use arrow::buffer::{NullBuffer, OffsetBuffer};
+use arrow::compute::{concat, interleave, partition, take};
+use arrow::row::{RowConverter, SortField};
+use arrow::util::display::array_value_to_string;
+use arrow_array::{
+    builder::FixedSizeBinaryBuilder,
+    cast::AsArray,
+    make_array, new_null_array,
+    types::{
+        Float16Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, UInt16Type,
+        UInt32Type, UInt64Type,
+    },
+    Array, ArrayRef, ArrowPrimitiveType, FixedSizeListArray, Float32Array, Float64Array, ListArray,
+    PrimitiveArray, RecordBatch, RecordBatchIterator, RecordBatchReader, StringArray, UInt32Array,
+    UInt64Array, UInt8Array,
+};
+use arrow_cast::cast;
+use arrow_schema::{ArrowError, DataType, Field, FieldRef, Schema, SchemaRef, TimeUnit};
+use chrono::{Datelike, NaiveDateTime};
+use half::f16;
+use lance::arrow::FixedSizeListArrayExt;
+use lance_linalg::distance::{DistanceFunc, MetricType};
+use sha2::{Digest, Sha256};
+
+use super::sanitize::{coerce_array, CoercionOptions};
+use crate::error::{Error, Result};
+
+/// Make sure all the `columns` exist in the `schema`.
+fn check_columns(schema: &Schema, columns: &[String]) -> Result<()> {
+    for name in columns {
+        if schema.field_with_name(name).is_err() {
+            return Err(Error::Schema {
+                message: format!("Column {} not found", name),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Make sure the `new_columns` collide neither with each other nor with the columns of
+/// the `schema` that are not `replaced`.
+fn check_new_columns<'a>(
+    schema: &Schema,
+    replaced: &[String],
+    new_columns: impl IntoIterator<Item = &'a str>,
+) -> Result<()> {
+    let mut seen = HashSet::new();
+    for name in new_columns {
+        let exists = schema.field_with_name(name).is_ok() && !replaced.iter().any(|c| c == name);
+        if exists || !seen.insert(name) {
+            return Err(Error::Schema {
+                message: format!("Column {} already exists", name),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Replace the `source_columns` of the schema with `field`, at the position of the
+/// first source column.
+fn replace_fields(schema: &Schema, source_columns: &[String], field: Field) -> Result<SchemaRef> {
+    if source_columns.is_empty() {
+        return Err(Error::Schema {
+            message: format!(
+                "At least one source column is required to build {}",
+                field.name()
+            ),
+        });
+    }
+    check_new_columns(schema, source_columns, [field.name().as_str()])?;
+    let mut field = Some(Arc::new(field));
+    let fields = schema
+        .fields()
+        .iter()
+        .filter_map(|f| {
+            if f.name() == source_columns[0].as_str() {
+                field.take()
+            } else if source_columns.contains(f.name()) {
+                None
+            } else {
+                Some(f.clone())
+            }
+        })
+        .collect::<Vec<FieldRef>>();
+    Ok(Arc::new(Schema::new_with_metadata(
+        fields,
+        schema.metadata().clone(),
+    )))
+}
+
+/// Replace the `source_columns` of the batch with `column`, at the position of the
+/// first source column.
+fn replace_columns(
+    batch: &RecordBatch,
+    source_columns: &[String],
+    column: Arc<dyn Array>,
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let mut column = Some(column);
+    let columns = batch
+        .schema()
+        .fields()
+        .iter()
+        .zip(batch.columns())
+        .filter_map(|(field, c)| {
+            if field.name() == source_columns[0].as_str() {
+                column.take()
+            } else if source_columns.contains(field.name()) {
+                None
+            } else {
+                Some(c.clone())
+            }
+        })
+        .collect::<Vec<_>>();
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Get the `source_columns` of the batch, making sure they have as many values as the
+/// batch has rows.
+fn source_arrays(
+    batch: &RecordBatch,
+    source_columns: &[String],
+) -> std::result::Result<Vec<Arc<dyn Array>>, ArrowError> {
+    source_columns
+        .iter()
+        .map(|name| {
+            let column = batch
+                .column_by_name(name)
+                .ok_or_else(|| ArrowError::SchemaError(format!("Column {} not found", name)))?;
+            // A per-column piece may also be stored as a single-element fixed size list.
+            let column = match column.data_type() {
+                DataType::FixedSizeList(_, 1) => {
+                    let list = column.as_fixed_size_list();
+                    let values = list
+                        .values()
+                        .slice(list.value_offset(0) as usize, list.len());
+                    // A null list nulls its element, whatever value is stored under it.
+                    let nulls = NullBuffer::union(values.nulls(), list.nulls());
+                    make_array(values.into_data().into_builder().nulls(nulls).build()?)
+                }
+                _ => column.clone(),
+            };
+            if column.len() != batch.num_rows() {
+                return Err(ArrowError::SchemaError(format!(
+                    "Column {} has {} values but the batch has {} rows",
+                    name,
+                    column.len(),
+                    batch.num_rows()
+                )));
+            }
+            Ok(column)
+        })
+        .collect()
+}
+
+fn transpose_batch(
+    batch: RecordBatch,
+    source_columns: &[String],
+    schema: SchemaRef,
+    dim: i32,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let item = Field::new("item", DataType::Float32, true);
+    let pieces = source_arrays(&batch, source_columns)?
+        .iter()
+        .map(|column| coerce_array(column, &item, &CoercionOptions::default()))
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    let pieces = pieces
+        .iter()
+        .map(|p| p.as_primitive::<Float32Type>())
+        .collect::<Vec<_>>();
+
+    let values = PrimitiveArray::<Float32Type>::from_iter((0..batch.num_rows()).flat_map(|row| {
+        pieces
+            .iter()
+            .map(move |p| p.is_valid(row).then(|| p.value(row)))
+    }));
+    let vectors = Arc::new(FixedSizeListArray::try_new_from_values(values, dim)?);
+    replace_columns(&batch, source_columns, vectors, schema)
+}
+
+/// Reassemble a row-major vector column from column-major storage.
+///
+/// Some producers store the vectors as `dim` separate columns, one per dimension.
+/// The `source_columns` are replaced by a single `target_column` of type
+/// `FixedSizeList<Float32, dim>`, at the position of the first source column.
+pub fn transpose_vector(
+    reader: impl RecordBatchReader + Send + 'static,
+    source_columns: Vec<String>,
+    target_column: &str,
+    dim: i32,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    if dim <= 0 {
+        return Err(Error::Schema {
+            message: format!("Vector dimension must be positive, got {}", dim),
+        });
+    }
+    if source_columns.len() != dim as usize {
+        return Err(Error::Schema {
+            message: format!(
+                "Expected {} source columns for dimension {}, got {}",
+                dim,
+                dim,
+                source_columns.len()
+            ),
+        });
+    }
+    check_columns(&reader.schema(), &source_columns)?;
+    let vector_field = Field::new(
+        target_column,
+        DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
+        true,
+    );
+    let output_schema = replace_fields(&reader.schema(), &source_columns, vector_field)?;
+
+    let s = output_schema.clone();
+    let batches = reader.map(move |batch| transpose_batch(batch?, &source_columns, s.clone(), dim));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+/// The source columns of [columns_to_vector].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VectorColumns {
+    /// The columns `{prefix}0` to `{prefix}{dim - 1}`, e.g., `f0` to `f7`.
+    Prefix(String),
+    /// The named columns, in order.
+    Names(Vec<String>),
+}
+
+/// Assemble a wide schema of float columns that form one vector, e.g., `f0` to `f7`,
+/// into a single vector column.
+///
+/// Like [transpose_vector], the source columns are replaced by a single `target_column`
+/// of type `FixedSizeList<Float32, dim>`, at the position of the first source column.
+///
+/// # Arguments
+///
+/// * `columns` - The [VectorColumns] to assemble, in component order.
+/// * `target_column` - The name of the vector column.
+/// * `dim` - The dimension of the vectors.
+pub fn columns_to_vector(
+    reader: impl RecordBatchReader + Send + 'static,
+    columns: VectorColumns,
+    target_column: &str,
+    dim: i32,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let source_columns = match columns {
+        VectorColumns::Prefix(prefix) => (0..dim).map(|i| format!("{}{}", prefix, i)).collect(),
+        VectorColumns::Names(names) => names,
+    };
+    transpose_vector(reader, source_columns, target_column, dim)
+}
+
+fn pack_batch(
+    batch: RecordBatch,
+    source_columns: &[String],
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let columns = source_arrays(&batch, source_columns)?;
+    let flags = columns
+        .iter()
+        .zip(source_columns)
+        .map(|(c, name)| {
+            c.as_boolean_opt().ok_or_else(|| {
+                ArrowError::SchemaError(format!(
+                    "Column {} is not boolean, got {:?}",
+                    name,
+                    c.data_type()
+                ))
+            })
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+
+    let num_bytes = flags.len().div_ceil(8);
+    let mut bytes = vec![0_u8; batch.num_rows() * num_bytes];
+    for (bit, flag) in flags.iter().enumerate() {
+        for row in 0..batch.num_rows() {
+            if flag.is_valid(row) && flag.value(row) {
+                bytes[row * num_bytes + bit / 8] |= 1 << (bit % 8);
+            }
+        }
+    }
+    let bitset =
+        FixedSizeListArray::try_new_from_values(UInt8Array::from(bytes), num_bytes as i32)?;
+    replace_columns(&batch, source_columns, Arc::new(bitset), schema)
+}
+
+/// Pack boolean feature columns into a bitset vector column.
+///
+/// The `source_columns` are replaced by a single `target_column` of type
+/// `FixedSizeList<UInt8, ceil(K / 8)>`, where `K` is the number of source columns.
+/// The bits are packed LSB-first: the `i`-th source column is stored in bit `i % 8`
+/// of byte `i / 8`. Null values are packed as unset bits.
+pub fn pack_booleans_to_bitset(
+    reader: impl RecordBatchReader + Send + 'static,
+    source_columns: Vec<String>,
+    target_column: &str,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    if source_columns.is_empty() {
+        return Err(Error::Schema {
+            message: "At least one boolean column is required to build a bitset".to_string(),
+        });
+    }
+    check_columns(&reader.schema(), &source_columns)?;
+    let bitset_field = Field::new(
+        target_column,
+        DataType::FixedSizeList(
+            Arc::new(Field::new("item", DataType::UInt8, true)),
+            source_columns.len().div_ceil(8) as i32,
+        ),
+        true,
+    );
+    let output_schema = replace_fields(&reader.schema(), &source_columns, bitset_field)?;
+
+    let s = output_schema.clone();
+    let batches = reader.map(move |batch| pack_batch(batch?, &source_columns, s.clone()));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+fn sparse_batch(
+    batch: RecordBatch,
+    source_columns: &[String],
+    schema: SchemaRef,
+    dim: i32,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let list_field = |dt| {
+        Field::new(
+            "list",
+            DataType::List(Arc::new(Field::new("item", dt, true))),
+            true,
+        )
+    };
+    let columns = source_arrays(&batch, source_columns)?;
+    let indices = coerce_array(
+        &columns[0],
+        &list_field(DataType::Int64),
+        &CoercionOptions::default(),
+    )?;
+    let values = coerce_array(
+        &columns[1],
+        &list_field(DataType::Float32),
+        &CoercionOptions::default(),
+    )?;
+    let (indices, values) = (indices.as_list::<i32>(), values.as_list::<i32>());
+
+    let dim = dim as usize;
+    let mut dense = vec![0.0_f32; batch.num_rows() * dim];
+    let mut validity = Vec::with_capacity(batch.num_rows());
+    for row in 0..batch.num_rows() {
+        if indices.is_null(row) || values.is_null(row) {
+            validity.push(false);
+            continue;
+        }
+        validity.push(true);
+        let (row_indices, row_values) = (indices.value(row), values.value(row));
+        if row_indices.len() != row_values.len() {
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "Row {} has {} indices but {} values",
+                row,
+                row_indices.len(),
+                row_values.len()
+            )));
+        }
+        let row_indices = row_indices.as_primitive::<Int64Type>();
+        let row_values = row_values.as_primitive::<Float32Type>();
+        for (index, value) in row_indices.iter().zip(row_values.iter()) {
+            let index = match index {
+                Some(i) if (0..dim as i64).contains(&i) => i as usize,
+                Some(i) => {
+                    return Err(ArrowError::InvalidArgumentError(format!(
+                        "Row {} has index {} out of range for dimension {}",
+                        row, i, dim
+                    )))
+                }
+                None => {
+                    return Err(ArrowError::InvalidArgumentError(format!(
+                        "Row {} has a null index",
+                        row
+                    )))
+                }
+            };
+            dense[row * dim + index] = value.unwrap_or_default();
+        }
+    }
+
+    let item = Arc::new(Field::new("item", DataType::Float32, true));
+    let vectors = FixedSizeListArray::try_new(
+        item,
+        dim as i32,
+        Arc::new(Float32Array::from(dense)),
+        Some(NullBuffer::from(validity)),
+    )?;
+    replace_columns(&batch, source_columns, Arc::new(vectors), schema)
+}
+
+/// Expand a sparse vector representation into a dense vector column.
+///
+/// Each row of the sparse vectors is stored in two parallel list columns: the
+/// `indices_column` of the non-zero positions, and the `values_column` of their values.
+/// Both are replaced by a single `target_column` of type `FixedSizeList<Float32, dim>`,
+/// at the position of the indices column. Unspecified positions are zero-filled, and a
+/// row that is null in either column is a null vector.
+pub fn sparse_to_dense_vector(
+    reader: impl RecordBatchReader + Send + 'static,
+    indices_column: &str,
+    values_column: &str,
+    dim: i32,
+    target_column: &str,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    if dim <= 0 {
+        return Err(Error::Schema {
+            message: format!("Invalid dimension {} for a dense vector", dim),
+        });
+    }
+    let source_columns = vec![indices_column.to_string(), values_column.to_string()];
+    check_columns(&reader.schema(), &source_columns)?;
+    let vector_field = Field::new(
+        target_column,
+        DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
+        true,
+    );
+    let output_schema = replace_fields(&reader.schema(), &source_columns, vector_field)?;
+
+    let s = output_schema.clone();
+    let batches = reader.map(move |batch| sparse_batch(batch?, &source_columns, s.clone(), dim));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+fn split_batch(
+    batch: RecordBatch,
+    source_column: &str,
+    splits: &[(String, i32)],
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let index = batch.schema().index_of(source_column)?;
+    let vectors = batch.column(index).as_fixed_size_list();
+    let dim = vectors.value_length();
+    let mut offset = 0;
+    let parts = splits
+        .iter()
+        .enumerate()
+        .map(|(i, (_, split_dim))| {
+            let DataType::FixedSizeList(item, _) = schema.field(index + i).data_type() else {
+                unreachable!()
+            };
+            // Components past the end of the source vector are padded with nulls.
+            let indices = (0..vectors.len())
+                .flat_map(|row| {
+                    let start = vectors.value_offset(row);
+                    (offset..offset + split_dim)
+                        .map(move |c| (c < dim).then_some((start + c) as u32))
+                })
+                .collect::<UInt32Array>();
+            offset += split_dim;
+            Ok(Arc::new(FixedSizeListArray::try_new(
+                item.clone(),
+                *split_dim,
+                take(vectors.values(), &indices, None)?,
+                vectors.nulls().cloned(),
+            )?) as ArrayRef)
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+
+    let mut columns = batch.columns().to_vec();
+    columns.splice(index..=index, parts);
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Split a vector column into sub-vector columns, e.g., a concatenation of a text and
+/// an image embedding.
+///
+/// The `source_column` is replaced by one `FixedSizeList` column per split, in order,
+/// with the same inner type. The dimensions of the splits must add up to the dimension
+/// of the source column. A null vector is null in every split.
+///
+/// # Arguments
+///
+/// * `source_column` - The name of the vector column to split.
+/// * `splits` - The name and dimension of each sub-vector column.
+pub fn split_vector(
+    reader: impl RecordBatchReader + Send + 'static,
+    source_column: &str,
+    splits: Vec<(String, i32)>,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    let source_field = schema
+        .field_with_name(source_column)
+        .map_err(|_| Error::Schema {
+            message: format!("Column {} not found", source_column),
+        })?;
+    let DataType::FixedSizeList(item, dim) = source_field.data_type() else {
+        return Err(Error::Schema {
+            message: format!(
+                "Column {} is not a vector column, got {:?}",
+                source_column,
+                source_field.data_type()
+            ),
+        });
+    };
+    if splits.iter().any(|(_, d)| *d <= 0) || splits.iter().map(|(_, d)| d).sum::<i32>() != *dim {
+        return Err(Error::Schema {
+            message: format!(
+                "The splits {:?} do not add up to the dimension {} of column {}",
+                splits.iter().map(|(_, d)| d).collect::<Vec<_>>(),
+                dim,
+                source_column
+            ),
+        });
+    }
+
+    check_new_columns(
+        &schema,
+        &[source_column.to_string()],
+        splits.iter().map(|(name, _)| name.as_str()),
+    )?;
+
+    let fields = schema
+        .fields()
+        .iter()
+        .flat_map(|f| {
+            if f.name() == source_column {
+                splits
+                    .iter()
+                    .map(|(name, dim)| {
+                        Arc::new(Field::new(
+                            name,
+                            DataType::FixedSizeList(item.clone(), *dim),
+                            f.is_nullable(),
+                        ))
+                    })
+                    .collect::<Vec<_>>()
+            } else {
+                vec![f.clone()]
+            }
+        })
+        .collect::<Vec<_>>();
+    let output_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let s = output_schema.clone();
+    let source_column = source_column.to_string();
+    let batches = reader.map(move |batch| split_batch(batch?, &source_column, &splits, s.clone()));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+/// Split a vector column into columns of `chunk_size` components each, e.g., to
+/// experiment with the columnar compression of high dimensional vectors.
+///
+/// The `column` is replaced by the `{column}_chunk_{i}` columns, in order. When the
+/// dimension is not a multiple of `chunk_size`, the last chunk is padded with nulls.
+/// A null vector is null in every chunk.
+///
+/// # Arguments
+///
+/// * `column` - The name of the vector column to chunk.
+/// * `chunk_size` - The number of components of each chunk.
+pub fn chunk_vector_column(
+    reader: impl RecordBatchReader + Send + 'static,
+    column: &str,
+    chunk_size: i32,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    let field = schema.field_with_name(column).map_err(|_| Error::Schema {
+        message: format!("Column {} not found", column),
+    })?;
+    let DataType::FixedSizeList(item, dim) = field.data_type() else {
+        return Err(Error::Schema {
+            message: format!(
+                "Column {} is not a vector column, got {:?}",
+                column,
+                field.data_type()
+            ),
+        });
+    };
+    if chunk_size <= 0 {
+        return Err(Error::Schema {
+            message: format!("Chunk size must be positive, got {}", chunk_size),
+        });
+    }
+
+    let chunks = (*dim as usize).div_ceil(chunk_size as usize) as i32;
+    let padded_item = Arc::new(item.as_ref().clone().with_nullable(true));
+    let chunk_fields = (0..chunks)
+        .map(|i| {
+            let padded = dim - i * chunk_size < chunk_size;
+            let item = if padded { &padded_item } else { item };
+            Arc::new(Field::new(
+                format!("{}_chunk_{}", column, i),
+                DataType::FixedSizeList(item.clone(), chunk_size),
+                field.is_nullable(),
+            ))
+        })
+        .collect::<Vec<_>>();
+    check_new_columns(
+        &schema,
+        &[column.to_string()],
+        chunk_fields.iter().map(|f| f.name().as_str()),
+    )?;
+    let splits = chunk_fields
+        .iter()
+        .map(|f| (f.name().clone(), chunk_size))
+        .collect::<Vec<_>>();
+    let fields = schema
+        .fields()
+        .iter()
+        .flat_map(|f| {
+            if f.name() == column {
+                chunk_fields.clone()
+            } else {
+                vec![f.clone()]
+            }
+        })
+        .collect::<Vec<_>>();
+    let output_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let s = output_schema.clone();
+    let column = column.to_string();
+    let batches = reader.map(move |batch| split_batch(batch?, &column, &splits, s.clone()));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+fn split_column_batch(
+    batch: RecordBatch,
+    source: &str,
+    delimiter: &str,
+    targets: &[Field],
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let index = batch.schema().index_of(source)?;
+    let strings = cast(batch.column(index), &DataType::Utf8)?;
+    let strings = strings.as_string::<i32>();
+    let mut parts = vec![Vec::with_capacity(strings.len()); targets.len()];
+    for (row, value) in strings.iter().enumerate() {
+        let Some(value) = value else {
+            parts.iter_mut().for_each(|p| p.push(None));
+            continue;
+        };
+        let values = value.split(delimiter).collect::<Vec<_>>();
+        if values.len() != targets.len() {
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "Column {} has {} parts at row {}, expected {}",
+                source,
+                values.len(),
+                row,
+                targets.len()
+            )));
+        }
+        for (part, value) in parts.iter_mut().zip(values) {
+            part.push(Some(value));
+        }
+    }
+    let columns = parts
+        .into_iter()
+        .zip(targets)
+        .enumerate()
+        .map(|(index, (part, field))| {
+            let part: ArrayRef = Arc::new(StringArray::from(part));
+            let coerced = coerce_array(&part, field, &CoercionOptions::default())?;
+            // The values that can not be coerced are null after the cast.
+            let failed = (0..part.len()).find(|&row| part.is_valid(row) && coerced.is_null(row));
+            match failed {
+                Some(row) => Err(ArrowError::CastError(format!(
+                    "Column {} has part {} {:?} at row {} that can not be coerced to {:?}",
+                    source,
+                    index,
+                    part.as_string::<i32>().value(row),
+                    row,
+                    field.data_type()
+                ))),
+                None => Ok(coerced),
+            }
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+
+    let mut all_columns = batch.columns().to_vec();
+    all_columns.splice(index..=index, columns);
+    RecordBatch::try_new(schema, all_columns)
+}
+
+/// Split a string column of composite values, e.g., `"42|2023|us"`, into one column per
+/// part.
+///
+/// The `source` column is replaced by the `targets` columns, in order, and each part is
+/// coerced to the type of its target field. Every non-null value must have exactly as
+/// many parts as there are targets, each of which must coerce to its target type. A null
+/// value is null in every target column.
+///
+/// # Arguments
+///
+/// * `source` - The name of the string column to split.
+/// * `delimiter` - The delimiter between the parts.
+/// * `targets` - The field of each part.
+pub fn split_column(
+    reader: impl RecordBatchReader + Send + 'static,
+    source: &str,
+    delimiter: &str,
+    targets: Vec<Field>,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    let field = schema.field_with_name(source).map_err(|_| Error::Schema {
+        message: format!("Column {} not found", source),
+    })?;
+    if !matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8) {
+        return Err(Error::Schema {
+            message: format!(
+                "Column {} is not a string column, got {:?}",
+                source,
+                field.data_type()
+            ),
+        });
+    }
+    if delimiter.is_empty() || targets.is_empty() {
+        return Err(Error::Schema {
+            message: format!(
+                "Column {} needs a delimiter and at least one target to be split",
+                source
+            ),
+        });
+    }
+
+    check_new_columns(
+        &schema,
+        &[source.to_string()],
+        targets.iter().map(|t| t.name().as_str()),
+    )?;
+
+    let fields = schema
+        .fields()
+        .iter()
+        .flat_map(|f| {
+            if f.name() == source {
+                targets
+                    .iter()
+                    .map(|t| Arc::new(t.clone()))
+                    .collect::<Vec<_>>()
+            } else {
+                vec![f.clone()]
+            }
+        })
+        .collect::<Vec<_>>();
+    let output_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let s = output_schema.clone();
+    let source = source.to_string();
+    let delimiter = delimiter.to_string();
+    let batches = reader
+        .map(move |batch| split_column_batch(batch?, &source, &delimiter, &targets, s.clone()));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+fn interleave_batch(
+    batch: RecordBatch,
+    source_columns: &[String],
+    schema: SchemaRef,
+    dim: i32,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let item = Field::new("item", DataType::Float32, true);
+    let parts = source_columns
+        .iter()
+        .map(|name| {
+            let vectors = batch
+                .column_by_name(name)
+                .ok_or_else(|| ArrowError::SchemaError(format!("Column {} not found", name)))?
+                .as_fixed_size_list();
+            let values = coerce_array(vectors.values(), &item, &CoercionOptions::default())?;
+            Ok((vectors, values))
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    let (real, real_values) = &parts[0];
+    let (imag, imag_values) = &parts[1];
+
+    let indices = (0..batch.num_rows())
+        .flat_map(|row| {
+            let real_start = real.value_offset(row) as usize;
+            let imag_start = imag.value_offset(row) as usize;
+            (0..dim as usize).flat_map(move |i| [(0, real_start + i), (1, imag_start + i)])
+        })
+        .collect::<Vec<_>>();
+    let values = interleave(&[real_values.as_ref(), imag_values.as_ref()], &indices)?;
+    let vectors = FixedSizeListArray::try_new(
+        Arc::new(item),
+        dim * 2,
+        values,
+        NullBuffer::union(real.nulls(), imag.nulls()),
+    )?;
+    replace_columns(&batch, source_columns, Arc::new(vectors), schema)
+}
+
+/// Combine the real and imaginary parts of complex vectors into one vector column.
+///
+/// The `real_column` and `imag_column` must be vector columns of the same dimension
+/// `N`. They are replaced by a single `target_column` of type `FixedSizeList<Float32, 2N>`,
+/// at the position of the real column, with the interleaved `[r0, i0, r1, i1, ...]`
+/// layout. A vector is null if either of its parts is null.
+///
+/// # Arguments
+///
+/// * `real_column` - The name of the vector column with the real parts.
+/// * `imag_column` - The name of the vector column with the imaginary parts.
+/// * `target_column` - The name of the interleaved vector column.
+pub fn interleave_complex(
+    reader: impl RecordBatchReader + Send + 'static,
+    real_column: &str,
+    imag_column: &str,
+    target_column: &str,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    let source_columns = vec![real_column.to_string(), imag_column.to_string()];
+    check_columns(&schema, &source_columns)?;
+    let dims = source_columns
+        .iter()
+        .map(|name| {
+            let field = schema.field_with_name(name).unwrap();
+            match field.data_type() {
+                DataType::FixedSizeList(item, dim) if item.data_type().is_floating() => Ok(*dim),
+                dt => Err(Error::Schema {
+                    message: format!("Column {} is not a vector column, got {:?}", name, dt),
+                }),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if dims[0] != dims[1] {
+        return Err(Error::Schema {
+            message: format!(
+                "Column {} has dimension {} but column {} has dimension {}",
+                real_column, dims[0], imag_column, dims[1]
+            ),
+        });
+    }
+    let dim = dims[0];
+    let vector_field = Field::new(
+        target_column,
+        DataType::FixedSizeList(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            dim * 2,
+        ),
+        true,
+    );
+    let output_schema = replace_fields(&schema, &source_columns, vector_field)?;
+
+    let s = output_schema.clone();
+    let batches =
+        reader.map(move |batch| interleave_batch(batch?, &source_columns, s.clone(), dim));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+/// How [concat_vectors] combines a null source vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConcatNulls {
+    /// The combined vector is null if any source vector is null.
+    #[default]
+    NullVector,
+    /// The components of a null source vector are null items of the combined vector,
+    /// which is only null if every source vector is null.
+    NullComponents,
+}
+
+fn concat_vectors_batch(
+    batch: RecordBatch,
+    source_columns: &[String],
+    schema: SchemaRef,
+    nulls: ConcatNulls,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let item = Field::new("item", DataType::Float32, true);
+    let parts = source_columns
+        .iter()
+        .map(|name| {
+            let vectors = batch
+                .column_by_name(name)
+                .ok_or_else(|| ArrowError::SchemaError(format!("Column {} not found", name)))?
+                .as_fixed_size_list();
+            let values = coerce_array(vectors.values(), &item, &CoercionOptions::default())?;
+            Ok((vectors, values))
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    let dim = parts.iter().map(|(v, _)| v.value_length()).sum();
+
+    // The last source holds the null item of the null components.
+    let null_item = new_null_array(&DataType::Float32, 1);
+    let null_source = parts.len();
+    let indices = (0..batch.num_rows())
+        .flat_map(|row| {
+            parts.iter().enumerate().flat_map(move |(k, (vectors, _))| {
+                let start = vectors.value_offset(row) as usize;
+                let valid = vectors.is_valid(row);
+                (0..vectors.value_length() as usize).map(move |i| {
+                    if valid {
+                        (k, start + i)
+                    } else {
+                        (null_source, 0)
+                    }
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut sources = parts.iter().map(|(_, v)| v.as_ref()).collect::<Vec<_>>();
+    sources.push(null_item.as_ref());
+    let values = interleave(&sources, &indices)?;
+
+    let validity = match nulls {
+        ConcatNulls::NullVector => parts.iter().fold(None, |acc, (v, _)| {
+            NullBuffer::union(acc.as_ref(), v.nulls())
+        }),
+        ConcatNulls::NullComponents => {
+            let valid = (0..batch.num_rows())
+                .map(|row| parts.iter().any(|(v, _)| v.is_valid(row)))
+                .collect::<Vec<_>>();
+            Some(NullBuffer::from(valid)).filter(|n| n.null_count() > 0)
+        }
+    };
+    let vectors = FixedSizeListArray::try_new(Arc::new(item), dim, values, validity)?;
+    replace_columns(&batch, source_columns, Arc::new(vectors), schema)
+}
+
+/// Concatenate vector columns into one vector column, e.g., to fuse the embeddings of
+/// several models.
+///
+/// The `columns` are replaced by a single `target` column of type
+/// `FixedSizeList<Float32, D>`, at the position of the first column, where `D` is the sum
+/// of their dimensions. The components are cast to `Float32`.
+///
+/// # Arguments
+///
+/// * `columns` - The names of the vector columns, in concatenation order.
+/// * `target` - The name of the concatenated vector column.
+/// * `validate_dims` - Whether the vector columns must all have the same dimension.
+/// * `nulls` - The [ConcatNulls] policy for null source vectors.
+pub fn concat_vectors(
+    reader: impl RecordBatchReader + Send + 'static,
+    columns: Vec<String>,
+    target: &str,
+    validate_dims: bool,
+    nulls: ConcatNulls,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    if columns.is_empty() {
+        return Err(Error::Schema {
+            message: "No vector columns to concatenate".to_string(),
+        });
+    }
+    check_columns(&schema, &columns)?;
+    let dims = columns
+        .iter()
+        .map(|name| {
+            let field = schema.field_with_name(name).unwrap();
+            match field.data_type() {
+                DataType::FixedSizeList(item, dim) if item.data_type().is_numeric() => Ok(*dim),
+                dt => Err(Error::Schema {
+                    message: format!("Column {} is not a vector column, got {:?}", name, dt),
+                }),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if validate_dims && dims.iter().any(|d| *d != dims[0]) {
+        return Err(Error::Schema {
+            message: format!("Columns {:?} have different dimensions {:?}", columns, dims),
+        });
+    }
+    let vector_field = Field::new(
+        target,
+        DataType::FixedSizeList(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            dims.iter().sum(),
+        ),
+        true,
+    );
+    let output_schema = replace_fields(&schema, &columns, vector_field)?;
+
+    let s = output_schema.clone();
+    let batches = reader.map(move |batch| concat_vectors_batch(batch?, &columns, s.clone(), nulls));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+fn swap_primitive<T: ArrowPrimitiveType>(
+    array: &dyn Array,
+    swap: impl Fn(T::Native) -> T::Native,
+) -> ArrayRef {
+    Arc::new(array.as_primitive::<T>().unary::<_, T>(swap))
+}
+
+/// Swap the byte order of the values of a numeric or vector array.
+pub(crate) fn byteswap_array(array: &ArrayRef) -> std::result::Result<ArrayRef, ArrowError> {
+    Ok(match array.data_type() {
+        DataType::Int8 | DataType::UInt8 => array.clone(),
+        DataType::Int16 => swap_primitive::<Int16Type>(array, i16::swap_bytes),
+        DataType::Int32 => swap_primitive::<Int32Type>(array, i32::swap_bytes),
+        DataType::Int64 => swap_primitive::<Int64Type>(array, i64::swap_bytes),
+        DataType::UInt16 => swap_primitive::<UInt16Type>(array, u16::swap_bytes),
+        DataType::UInt32 => swap_primitive::<UInt32Type>(array, u32::swap_bytes),
+        DataType::UInt64 => swap_primitive::<UInt64Type>(array, u64::swap_bytes),
+        DataType::Float16 => {
+            swap_primitive::<Float16Type>(array, |v| f16::from_bits(v.to_bits().swap_bytes()))
+        }
+        DataType::Float32 => {
+            swap_primitive::<Float32Type>(array, |v| f32::from_bits(v.to_bits().swap_bytes()))
+        }
+        DataType::Float64 => {
+            swap_primitive::<Float64Type>(array, |v| f64::from_bits(v.to_bits().swap_bytes()))
+        }
+        DataType::FixedSizeList(field, dim) => {
+            let vectors = array.as_fixed_size_list();
+            Arc::new(FixedSizeListArray::try_new(
+                field.clone(),
+                *dim,
+                byteswap_array(vectors.values())?,
+                vectors.nulls().cloned(),
+            )?)
+        }
+        dt => {
+            return Err(ArrowError::SchemaError(format!(
+                "Can not swap the byte order of {:?}",
+                dt
+            )))
+        }
+    })
+}
+
+/// Swap the byte order of a numeric or vector column, e.g., to fix big-endian data that
+/// was reinterpreted as little-endian.
+pub fn byteswap_column(
+    reader: impl RecordBatchReader + Send + 'static,
+    name: &str,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    let index = schema.index_of(name).map_err(|_| Error::Schema {
+        message: format!("Column {} not found", name),
+    })?;
+    let mut data_type = schema.field(index).data_type();
+    if let DataType::FixedSizeList(field, _) = data_type {
+        data_type = field.data_type();
+    }
+    if !(data_type.is_integer() || data_type.is_floating()) {
+        return Err(Error::Schema {
+            message: format!(
+                "Column {} is not numeric, got {:?}",
+                name,
+                schema.field(index).data_type()
+            ),
+        });
+    }
+
+    let batches = reader.map(move |batch| {
+        let batch = batch?;
+        let mut columns = batch.columns().to_vec();
+        columns[index] = byteswap_array(&columns[index])?;
+        RecordBatch::try_new(batch.schema(), columns)
+    });
+    Ok(Box::new(RecordBatchIterator::new(batches, schema)))
+}
+
+/// Append a `UInt64` row id column, numbering the rows of the whole stream from `start`.
+///
+/// The ids keep increasing across batches, so they are unique within the stream.
+pub fn add_rowid_column(
+    reader: impl RecordBatchReader + Send + 'static,
+    name: &str,
+    start: u64,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    if schema.field_with_name(name).is_ok() {
+        return Err(Error::Schema {
+            message: format!("Column {} already exists", name),
+        });
+    }
+    let mut fields = schema.fields().to_vec();
+    fields.push(Arc::new(Field::new(name, DataType::UInt64, false)));
+    let output_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let s = output_schema.clone();
+    let mut next_id = start;
+    let batches = reader.map(move |batch| {
+        let batch = batch?;
+        let ids = UInt64Array::from_iter_values(next_id..next_id + batch.num_rows() as u64);
+        next_id += batch.num_rows() as u64;
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(ids));
+        RecordBatch::try_new(s.clone(), columns)
+    });
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+fn pad_batch(
+    batch: RecordBatch,
+    min_rows: usize,
+    defaults: &HashMap<String, ArrayRef>,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    if batch.num_rows() >= min_rows {
+        return Ok(batch);
+    }
+    let padding = min_rows - batch.num_rows();
+    let columns = batch
+        .schema()
+        .fields()
+        .iter()
+        .zip(batch.columns())
+        .map(|(field, column)| {
+            let tail = match defaults.get(field.name()) {
+                Some(value) => take(value, &UInt32Array::from(vec![0; padding]), None)?,
+                None => new_null_array(field.data_type(), padding),
+            };
+            concat(&[column.as_ref(), tail.as_ref()])
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    RecordBatch::try_new(batch.schema(), columns)
+}
+
+/// Pad the batches smaller than `min_rows` with trailing rows, e.g., for consumers that
+/// process fixed-size windows.
+///
+/// This changes the number of rows of the stream: every padded batch gets exactly
+/// `min_rows` rows. The padding rows are null, or hold the default value of their column.
+/// Every non-nullable column needs a default.
+///
+/// # Arguments
+///
+/// * `min_rows` - The minimum number of rows of each batch.
+/// * `defaults` - The padding value of some columns, each held by a single-element array
+///   of the column type.
+pub fn pad_batches(
+    reader: impl RecordBatchReader + Send + 'static,
+    min_rows: usize,
+    defaults: HashMap<String, ArrayRef>,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    for (name, value) in &defaults {
+        let field = schema.field_with_name(name).map_err(|_| Error::Schema {
+            message: format!("Column {} not found", name),
+        })?;
+        if value.len() != 1 || value.data_type() != field.data_type() {
+            return Err(Error::Schema {
+                message: format!(
+                    "The default of column {} must be a single {:?} value",
+                    name,
+                    field.data_type()
+                ),
+            });
+        }
+    }
+    if let Some(field) = schema
+        .fields()
+        .iter()
+        .find(|f| !f.is_nullable() && !defaults.contains_key(f.name()))
+    {
+        return Err(Error::Schema {
+            message: format!(
+                "Column {} is not nullable, and has no default to pad it with",
+                field.name()
+            ),
+        });
+    }
+
+    let batches = reader.map(move |batch| pad_batch(batch?, min_rows, &defaults));
+    Ok(Box::new(RecordBatchIterator::new(batches, schema)))
+}
+
+/// The hash computed by [add_row_fingerprint], and how it is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FingerprintAlgorithm {
+    /// SHA-256, stored as a `FixedSizeBinary(32)` column.
+    #[default]
+    Sha256,
+    /// SHA-256, stored as a `Utf8` column of lowercase hex digests.
+    Sha256Hex,
+}
+
+impl FingerprintAlgorithm {
+    fn data_type(&self) -> DataType {
+        match self {
+            Self::Sha256 => DataType::FixedSizeBinary(32),
+            Self::Sha256Hex => DataType::Utf8,
+        }
+    }
+}
+
+fn fingerprint_batch(
+    batch: RecordBatch,
+    columns: &[String],
+    algo: FingerprintAlgorithm,
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let sources = columns
+        .iter()
+        .map(|name| {
+            batch
+                .column_by_name(name)
+                .ok_or_else(|| ArrowError::SchemaError(format!("Column {} not found", name)))
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+
+    let digests = (0..batch.num_rows())
+        .map(|row| {
+            let mut hasher = Sha256::new();
+            for column in &sources {
+                // Tag the nulls, and prefix the values with their type and length, so that
+                // distinct rows, e.g., `1` and `"1"`, can not serialize to the same bytes.
+                if column.is_null(row) {
+                    hasher.update([0]);
+                } else {
+                    let data_type = column.data_type().to_string();
+                    let value = array_value_to_string(column, row)?;
+                    hasher.update([1]);
+                    hasher.update((data_type.len() as u64).to_le_bytes());
+                    hasher.update(data_type.as_bytes());
+                    hasher.update((value.len() as u64).to_le_bytes());
+                    hasher.update(value.as_bytes());
+                }
+            }
+            Ok(hasher.finalize())
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    let fingerprints: ArrayRef = match algo {
+        FingerprintAlgorithm::Sha256 => {
+            let mut builder = FixedSizeBinaryBuilder::with_capacity(digests.len(), 32);
+            for digest in &digests {
+                builder.append_value(digest)?;
+            }
+            Arc::new(builder.finish())
+        }
+        FingerprintAlgorithm::Sha256Hex => Arc::new(StringArray::from_iter_values(
+            digests.iter().map(|d| format!("{:x}", d)),
+        )),
+    };
+
+    let mut columns = batch.columns().to_vec();
+    columns.push(fingerprints);
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Append a `target_name` column with a fingerprint of the values of `columns` in each
+/// row, e.g., for change detection or deduplication.
+///
+/// The fingerprint hashes the type and the display form of each value, so it is stable
+/// across runs, and rows with the same values in the chosen columns get the same
+/// fingerprint. Nulls hash the same wherever they are, but differently from any value.
+///
+/// # Arguments
+///
+/// * `columns` - The names of the hashed columns, in hashing order.
+/// * `target_name` - The name of the fingerprint column.
+/// * `algo` - The [FingerprintAlgorithm], which decides the type of the column.
+pub fn add_row_fingerprint(
+    reader: impl RecordBatchReader + Send + 'static,
+    columns: Vec<String>,
+    target_name: &str,
+    algo: FingerprintAlgorithm,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    check_columns(&schema, &columns)?;
+    if schema.field_with_name(target_name).is_ok() {
+        return Err(Error::Schema {
+            message: format!("Column {} already exists", target_name),
+        });
+    }
+    let mut fields = schema.fields().to_vec();
+    fields.push(Arc::new(Field::new(target_name, algo.data_type(), false)));
+    let output_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let s = output_schema.clone();
+    let batches = reader.map(move |batch| fingerprint_batch(batch?, &columns, algo, s.clone()));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+/// The number of most recently kept vectors that [dedup_near_vectors] compares against.
+pub const NEAR_DUPLICATE_WINDOW: usize = 1024;
+
+fn dedup_batch(
+    batch: RecordBatch,
+    column: &str,
+    distance: DistanceFunc,
+    threshold: f32,
+    window: &mut VecDeque<Vec<f32>>,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let item = Field::new("item", DataType::Float32, true);
+    let vectors = batch
+        .column_by_name(column)
+        .ok_or_else(|| ArrowError::SchemaError(format!("Column {} not found", column)))?
+        .as_fixed_size_list();
+    let values = coerce_array(vectors.values(), &item, &CoercionOptions::default())?;
+    let values = values.as_primitive::<Float32Type>().values();
+    let dim = vectors.value_length() as usize;
+
+    let mut kept = vec![];
+    for row in 0..vectors.len() {
+        if vectors.is_null(row) {
+            kept.push(row as u32);
+            continue;
+        }
+        let start = vectors.value_offset(row) as usize;
+        let vector = &values[start..start + dim];
+        if window.iter().any(|v| distance(v, vector) <= threshold) {
+            continue;
+        }
+        kept.push(row as u32);
+        window.push_back(vector.to_vec());
+        if window.len() > NEAR_DUPLICATE_WINDOW {
+            window.pop_front();
+        }
+    }
+    if kept.len() == batch.num_rows() {
+        return Ok(batch);
+    }
+    let indices = UInt32Array::from(kept);
+    let columns = batch
+        .columns()
+        .iter()
+        .map(|c| take(c, &indices, None))
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    RecordBatch::try_new(batch.schema(), columns)
+}
+
+/// Drop the rows whose vector is within `threshold` of the vector of an earlier kept row,
+/// e.g., to remove near-duplicate embeddings.
+///
+/// To stay streaming, each vector is only compared to the last [NEAR_DUPLICATE_WINDOW]
+/// kept vectors, so near duplicates further apart are kept: the deduplication is
+/// approximate. Rows with a null vector are always kept.
+///
+/// # Arguments
+///
+/// * `column` - The name of the vector column.
+/// * `metric` - The [MetricType] of the distance. [MetricType::L2] is the squared
+///   euclidean distance.
+/// * `threshold` - The largest distance of a near duplicate.
+pub fn dedup_near_vectors(
+    reader: impl RecordBatchReader + Send + 'static,
+    column: &str,
+    metric: MetricType,
+    threshold: f32,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    match schema.field_with_name(column).map(|f| f.data_type()) {
+        Ok(DataType::FixedSizeList(item, _)) if item.data_type().is_numeric() => {}
+        _ => {
+            return Err(Error::Schema {
+                message: format!("Column {} is not a vector column", column),
+            })
+        }
+    }
+
+    let column = column.to_string();
+    let distance = metric.func();
+    let mut window = VecDeque::new();
+    let batches =
+        reader.map(move |batch| dedup_batch(batch?, &column, distance, threshold, &mut window));
+    Ok(Box::new(RecordBatchIterator::new(batches, schema)))
+}
+
+fn centroid_batch(
+    batch: RecordBatch,
+    group_key: &str,
+    vector_column: &str,
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let column = |name: &str| {
+        batch
+            .column_by_name(name)
+            .ok_or_else(|| ArrowError::SchemaError(format!("Column {} not found", name)))
+    };
+    let keys = column(group_key)?;
+    let vectors = column(vector_column)?.as_fixed_size_list();
+    let item = Field::new("item", DataType::Float32, true);
+    let values = coerce_array(vectors.values(), &item, &CoercionOptions::default())?;
+    let values = values.as_primitive::<Float32Type>();
+    let dim = vectors.value_length() as usize;
+
+    let converter = RowConverter::new(vec![SortField::new(keys.data_type().clone())])?;
+    let rows = converter.convert_columns(std::slice::from_ref(keys))?;
+    let mut group_ids = HashMap::new();
+    let groups = rows
+        .iter()
+        .map(|row| {
+            let next = group_ids.len();
+            *group_ids.entry(row).or_insert(next)
+        })
+        .collect::<Vec<_>>();
+
+    // Sum and count every component of each group, skipping null vectors and components.
+    let mut sums = vec![0.0f64; group_ids.len() * dim];
+    let mut counts = vec![0usize; group_ids.len() * dim];
+    for (row, group) in groups.iter().enumerate() {
+        if vectors.is_null(row) {
+            continue;
+        }
+        let start = vectors.value_offset(row) as usize;
+        for i in 0..dim {
+            if values.is_valid(start + i) {
+                sums[group * dim + i] += values.value(start + i) as f64;
+                counts[group * dim + i] += 1;
+            }
+        }
+    }
+    let centroid = |group: usize| group * dim..(group + 1) * dim;
+    let means = groups
+        .iter()
+        .flat_map(|&group| {
+            centroid(group).map(|i| (counts[i] > 0).then(|| (sums[i] / counts[i] as f64) as f32))
+        })
+        .collect::<Float32Array>();
+    let nulls = NullBuffer::from(
+        groups
+            .iter()
+            .map(|&group| centroid(group).any(|i| counts[i] > 0))
+            .collect::<Vec<_>>(),
+    );
+    let DataType::FixedSizeList(item, _) = schema.fields().last().unwrap().data_type() else {
+        unreachable!("The centroid column is a fixed size list");
+    };
+
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(FixedSizeListArray::try_new(
+        item.clone(),
+        dim as i32,
+        Arc::new(means),
+        Some(nulls),
+    )?));
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Append a `target_column` with the centroid, the mean vector, of the `group_key` group
+/// of each row, e.g., to store the parent vector of hierarchical vectors with each child.
+///
+/// The groups are formed within each batch only: rows of the same group in different
+/// batches get different centroids, unless the input is buffered into a single batch.
+/// Null vectors and null components are excluded from the mean, and a group without any
+/// vector has a null centroid. The centroids are `Float32` vectors.
+///
+/// # Arguments
+///
+/// * `group_key` - The name of the column to group the rows by.
+/// * `vector_column` - The name of the vector column.
+/// * `target_column` - The name of the appended centroid column.
+pub fn add_group_centroid(
+    reader: impl RecordBatchReader + Send + 'static,
+    group_key: &str,
+    vector_column: &str,
+    target_column: &str,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    check_columns(&schema, &[group_key.to_string()])?;
+    let dim = match schema.field_with_name(vector_column).map(|f| f.data_type()) {
+        Ok(DataType::FixedSizeList(item, dim)) if item.data_type().is_numeric() => *dim,
+        _ => {
+            return Err(Error::Schema {
+                message: format!("Column {} is not a vector column", vector_column),
+            })
+        }
+    };
+    if schema.field_with_name(target_column).is_ok() {
+        return Err(Error::Schema {
+            message: format!("Column {} already exists", target_column),
+        });
+    }
+    let mut fields = schema.fields().to_vec();
+    fields.push(Arc::new(Field::new(
+        target_column,
+        DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim),
+        true,
+    )));
+    let output_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let s = output_schema.clone();
+    let group_key = group_key.to_string();
+    let vector_column = vector_column.to_string();
+    let batches =
+        reader.map(move |batch| centroid_batch(batch?, &group_key, &vector_column, s.clone()));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+fn group_vectors_batch(
+    batch: RecordBatch,
+    doc_key: &str,
+    vector_column: &str,
+    target_column: &str,
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let column = |name: &str| {
+        batch
+            .column_by_name(name)
+            .ok_or_else(|| ArrowError::SchemaError(format!("Column {} not found", name)))
+    };
+    let keys = column(doc_key)?;
+    let ranges = partition(std::slice::from_ref(keys))?.ranges();
+
+    let DataType::List(vector_field) = schema.field_with_name(target_column)?.data_type() else {
+        unreachable!("The grouped column is a list");
+    };
+    let vectors = coerce_array(
+        column(vector_column)?,
+        vector_field,
+        &CoercionOptions::default(),
+    )?;
+    let lists = ListArray::try_new(
+        vector_field.clone(),
+        OffsetBuffer::from_lengths(ranges.iter().map(|r| r.len())),
+        vectors,
+        None,
+    )?;
+
+    let first_rows = ranges
+        .iter()
+        .map(|r| r.start as u32)
+        .collect::<UInt32Array>();
+    let columns = batch
+        .columns()
+        .iter()
+        .map(|c| take(c, &first_rows, None))
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    let firsts = RecordBatch::try_new(batch.schema(), columns)?;
+    replace_columns(
+        &firsts,
+        &[vector_column.to_string()],
+        Arc::new(lists),
+        schema,
+    )
+}
+
+/// Group the consecutive rows with the same `doc_key` into one row, collecting their
+/// vectors into a list of vectors, e.g., to store the multiple vectors of a document for
+/// multi-vector (ColBERT-style) retrieval.
+///
+/// The groups are formed within each batch only, and the other columns take the values
+/// of the first row of each group. The `vector_column` is replaced by the
+/// `target_column`, a `List<FixedSizeList<Float32, dim>>`; the values of every vector are
+/// checked to be consistent with the dimension of the vector column.
+///
+/// # Arguments
+///
+/// * `doc_key` - The name of the column identifying the document of each row.
+/// * `vector_column` - The name of the vector column.
+/// * `target_column` - The name of the list of vectors column.
+pub fn group_vectors_to_list(
+    reader: impl RecordBatchReader + Send + 'static,
+    doc_key: &str,
+    vector_column: &str,
+    target_column: &str,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    check_columns(&schema, &[doc_key.to_string()])?;
+    let dim = match schema.field_with_name(vector_column).map(|f| f.data_type()) {
+        Ok(DataType::FixedSizeList(item, dim)) if item.data_type().is_numeric() => *dim,
+        _ => {
+            return Err(Error::Schema {
+                message: format!("Column {} is not a vector column", vector_column),
+            })
+        }
+    };
+    if target_column != vector_column && schema.field_with_name(target_column).is_ok() {
+        return Err(Error::Schema {
+            message: format!("Column {} already exists", target_column),
+        });
+    }
+    let vector_type =
+        DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim);
+    let field = Field::new(
+        target_column,
+        DataType::List(Arc::new(Field::new("item", vector_type, true))),
+        true,
+    );
+    let output_schema = replace_fields(&schema, &[vector_column.to_string()], field)?;
+
+    let s = output_schema.clone();
+    let doc_key = doc_key.to_string();
+    let vector_column = vector_column.to_string();
+    let target_column = target_column.to_string();
+    let batches = reader.map(move |batch| {
+        group_vectors_batch(batch?, &doc_key, &vector_column, &target_column, s.clone())
+    });
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+/// What [ungroup_vectors] does with an empty or null list of vectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyVectorList {
+    /// Produce no row.
+    #[default]
+    Skip,
+    /// Produce one row with a null vector.
+    NullRow,
+}
+
+fn ungroup_vectors_batch(
+    batch: RecordBatch,
+    list_column: &str,
+    empty: EmptyVectorList,
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let lists = batch
+        .column_by_name(list_column)
+        .ok_or_else(|| ArrowError::SchemaError(format!("Column {} not found", list_column)))?
+        .as_list::<i32>();
+
+    let mut rows = vec![];
+    let mut vectors = vec![];
+    for (row, offsets) in lists.value_offsets().windows(2).enumerate() {
+        let (start, end) = (offsets[0] as u32, offsets[1] as u32);
+        if lists.is_valid(row) && start < end {
+            rows.extend(std::iter::repeat_n(row as u32, (end - start) as usize));
+            vectors.extend((start..end).map(|i| (0, i as usize)));
+        } else if empty == EmptyVectorList::NullRow {
+            rows.push(row as u32);
+            vectors.push((1, 0));
+        }
+    }
+    let rows = UInt32Array::from(rows);
+    // The null vectors are taken from an extra single null vector source.
+    let null_vector = new_null_array(&lists.value_type(), 1);
+
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            if field.name() == list_column {
+                return interleave(&[lists.values().as_ref(), null_vector.as_ref()], &vectors);
+            }
+            let column = batch.column_by_name(field.name()).ok_or_else(|| {
+                ArrowError::SchemaError(format!("Column {} not found", field.name()))
+            })?;
+            take(column, &rows, None)
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Flatten a list of vectors column into one row per vector, the inverse of
+/// [group_vectors_to_list].
+///
+/// The `doc_key_columns` are repeated for every vector of their row, and the other
+/// columns are dropped. The `list_column` is replaced by a vector column of the same
+/// name.
+///
+/// # Arguments
+///
+/// * `list_column` - The name of the `List<FixedSizeList>` column.
+/// * `doc_key_columns` - The names of the columns to repeat for every vector.
+/// * `empty` - What to do with the rows whose list is empty or null.
+pub fn ungroup_vectors(
+    reader: impl RecordBatchReader + Send + 'static,
+    list_column: &str,
+    doc_key_columns: &[String],
+    empty: EmptyVectorList,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    check_columns(&schema, doc_key_columns)?;
+    let vector_field = match schema.field_with_name(list_column).map(|f| f.data_type()) {
+        Ok(DataType::List(item)) if matches!(item.data_type(), DataType::FixedSizeList(_, _)) => {
+            Field::new(list_column, item.data_type().clone(), true)
+        }
+        _ => {
+            return Err(Error::Schema {
+                message: format!("Column {} is not a list of vectors column", list_column),
+            })
+        }
+    };
+    let fields = schema
+        .fields()
+        .iter()
+        .filter_map(|f| {
+            if f.name() == list_column {
+                Some(Arc::new(vector_field.clone()))
+            } else if doc_key_columns.contains(f.name()) {
+                Some(f.clone())
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    let output_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let s = output_schema.clone();
+    let list_column = list_column.to_string();
+    let batches =
+        reader.map(move |batch| ungroup_vectors_batch(batch?, &list_column, empty, s.clone()));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+/// The range of each column scaled by [minmax_scale_columns], to invert the scaling.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScalingParams {
+    /// The `(min, max)` of each scaled column that has at least one value.
+    pub ranges: HashMap<String, (f64, f64)>,
+}
+
+impl ScalingParams {
+    /// Map a scaled value of `column` back to its original value.
+    pub fn inverse(&self, column: &str, value: f64) -> Option<f64> {
+        self.ranges
+            .get(column)
+            .map(|(min, max)| min + value * (max - min))
+    }
+}
+
+fn minmax_scale_batch(
+    batch: RecordBatch,
+    params: &ScalingParams,
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let columns = schema
+        .fields()
+        .iter()
+        .zip(batch.columns())
+        .map(|(field, column)| {
+            let Some((min, max)) = params.ranges.get(field.name()) else {
+                return Ok(column.clone());
+            };
+            let values = cast(column, &DataType::Float64)?;
+            let range = max - min;
+            let scaled: Float64Array = values.as_primitive::<Float64Type>().unary(|v| {
+                if range > 0.0 {
+                    (v - min) / range
+                } else {
+                    0.0
+                }
+            });
+            Ok(Arc::new(scaled) as ArrayRef)
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Scale numeric columns to `[0, 1]` with their minimum and maximum across the whole
+/// input, e.g., to preprocess features for classic machine learning.
+///
+/// The range is only known once the input is read, so the input is buffered in memory:
+/// the batches are read first to find the ranges, and then scaled as the returned reader
+/// is read. Re-reading the source may be cheaper for inputs that do not fit in memory.
+///
+/// The scaled columns become `Float64`, and a column with a single distinct value is
+/// scaled to `0`. The returned [ScalingParams] invert the scaling.
+///
+/// # Arguments
+///
+/// * `columns` - The names of the numeric columns to scale.
+pub fn minmax_scale_columns(
+    reader: impl RecordBatchReader + Send + 'static,
+    columns: Vec<String>,
+) -> Result<(Box<dyn RecordBatchReader + Send>, ScalingParams)> {
+    let schema = reader.schema();
+    check_columns(&schema, &columns)?;
+    for name in &columns {
+        let field = schema.field_with_name(name)?;
+        if !field.data_type().is_numeric() {
+            return Err(Error::Schema {
+                message: format!(
+                    "Column {} is not numeric, got {:?}",
+                    name,
+                    field.data_type()
+                ),
+            });
+        }
+    }
+
+    let batches = reader.collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    let mut params = ScalingParams::default();
+    for batch in &batches {
+        for name in &columns {
+            let column = batch.column_by_name(name).ok_or(Error::Schema {
+                message: format!("Column {} not found", name),
+            })?;
+            let values = cast(column, &DataType::Float64)?;
+            for v in values.as_primitive::<Float64Type>().iter().flatten() {
+                if v.is_nan() {
+                    continue;
+                }
+                let range = params.ranges.entry(name.clone()).or_insert((v, v));
+                *range = (range.0.min(v), range.1.max(v));
+            }
+        }
+    }
+
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|f| {
+            if columns.contains(f.name()) {
+                Arc::new(Field::new(f.name(), DataType::Float64, f.is_nullable()))
+            } else {
+                f.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+    let output_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let s = output_schema.clone();
+    let p = params.clone();
+    let batches = batches
+        .into_iter()
+        .map(move |batch| minmax_scale_batch(batch, &p, s.clone()));
+    Ok((
+        Box::new(RecordBatchIterator::new(batches, output_schema)),
+        params,
+    ))
+}
+
+/// Computes a column from the columns of a batch, see [with_computed_column].
+pub type ComputeColumn = Arc<dyn Fn(&RecordBatch) -> Result<ArrayRef> + Send + Sync>;
+
+fn compute_batch(
+    batch: RecordBatch,
+    field: &Field,
+    compute: &ComputeColumn,
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let column = compute(&batch).map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
+    let column = coerce_array(&column, field, &CoercionOptions::default())?;
+    let mut columns = batch.columns().to_vec();
+    match batch.schema().index_of(field.name()) {
+        Ok(i) => columns[i] = column,
+        Err(_) => columns.push(column),
+    }
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Add a column computed from the other columns of each batch.
+///
+/// The computed column is coerced to `field`, and replaces the column of the same name
+/// in place if there is one, or is appended otherwise.
+///
+/// # Arguments
+///
+/// * `field` - The field of the computed column.
+/// * `compute` - Computes the column of a batch.
+pub fn with_computed_column(
+    reader: impl RecordBatchReader + Send + 'static,
+    field: Field,
+    compute: ComputeColumn,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    let mut fields = schema.fields().to_vec();
+    match schema.index_of(field.name()) {
+        Ok(i) => fields[i] = Arc::new(field.clone()),
+        Err(_) => fields.push(Arc::new(field.clone())),
+    }
+    let output_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let s = output_schema.clone();
+    let batches = reader.map(move |batch| compute_batch(batch?, &field, &compute, s.clone()));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+/// The norm computed by [add_vector_norm_column].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VectorNorm {
+    /// The euclidean norm, `sqrt(sum(x^2))`.
+    #[default]
+    L2,
+    /// The manhattan norm, `sum(|x|)`.
+    L1,
+    /// The maximum norm, `max(|x|)`.
+    Linf,
+}
+
+impl VectorNorm {
+    fn compute(&self, values: impl Iterator<Item = f32>) -> f32 {
+        match self {
+            Self::L2 => values.map(|v| v * v).sum::<f32>().sqrt(),
+            Self::L1 => values.map(f32::abs).sum(),
+            Self::Linf => values.map(f32::abs).fold(0.0, f32::max),
+        }
+    }
+}
+
+/// Compute the norm of each vector, a null vector having a null norm.
+pub(crate) fn vector_norms(
+    vectors: &FixedSizeListArray,
+    norm: VectorNorm,
+) -> std::result::Result<Float32Array, ArrowError> {
+    let item = Field::new("item", DataType::Float32, true);
+    let values = coerce_array(vectors.values(), &item, &CoercionOptions::default())?;
+    let values = values.as_primitive::<Float32Type>();
+    let dim = vectors.value_length() as usize;
+
+    Ok((0..vectors.len())
+        .map(|row| {
+            vectors.is_valid(row).then(|| {
+                let start = vectors.value_offset(row) as usize;
+                norm.compute(
+                    (start..start + dim)
+                        .filter(|&i| values.is_valid(i))
+                        .map(|i| values.value(i)),
+                )
+            })
+        })
+        .collect::<Float32Array>())
+}
+
+fn norm_batch(
+    batch: RecordBatch,
+    vector_column: &str,
+    norm: VectorNorm,
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let vectors = batch
+        .column_by_name(vector_column)
+        .ok_or_else(|| ArrowError::SchemaError(format!("Column {} not found", vector_column)))?
+        .as_fixed_size_list_opt()
+        .ok_or_else(|| {
+            ArrowError::SchemaError(format!("Column {} is not a fixed size list", vector_column))
+        })?;
+    let norms = vector_norms(vectors, norm)?;
+
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(norms));
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Append a `norm_column_name` column with the per-row norm of the `vector_column`.
+///
+/// The norms are stored as `Float32`; a null vector has a null norm. Null elements
+/// of a vector do not contribute to its norm.
+///
+/// # Arguments
+///
+/// * `vector_column` - The name of the fixed size list vector column.
+/// * `norm_column_name` - The name of the appended norm column.
+/// * `norm` - The [VectorNorm] to compute.
+pub fn add_vector_norm_column(
+    reader: impl RecordBatchReader + Send + 'static,
+    vector_column: &str,
+    norm_column_name: &str,
+    norm: VectorNorm,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    let vector_field = schema
+        .field_with_name(vector_column)
+        .map_err(|_| Error::Schema {
+            message: format!("Column {} not found", vector_column),
+        })?;
+    if !matches!(vector_field.data_type(), DataType::FixedSizeList(_, _)) {
+        return Err(Error::Schema {
+            message: format!(
+                "Column {} is not a vector column, got {:?}",
+                vector_column,
+                vector_field.data_type()
+            ),
+        });
+    }
+    if schema.field_with_name(norm_column_name).is_ok() {
+        return Err(Error::Schema {
+            message: format!("Column {} already exists", norm_column_name),
+        });
+    }
+    let mut fields = schema.fields().to_vec();
+    fields.push(Arc::new(Field::new(
+        norm_column_name,
+        DataType::Float32,
+        true,
+    )));
+    let output_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let s = output_schema.clone();
+    let vector_column = vector_column.to_string();
+    let batches = reader.map(move |batch| norm_batch(batch?, &vector_column, norm, s.clone()));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+/// How [one_hot_encode] encodes the values that are not among the categories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownCategory {
+    /// Fail the batch.
+    #[default]
+    Error,
+    /// Encode them as null vectors.
+    Null,
+    /// Encode them as vectors of zeros.
+    Zeros,
+}
+
+fn one_hot_batch(
+    batch: RecordBatch,
+    source: &str,
+    categories: &HashMap<String, usize>,
+    on_unknown: UnknownCategory,
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let column = batch
+        .column_by_name(source)
+        .ok_or_else(|| ArrowError::SchemaError(format!("Column {} not found", source)))?;
+    let dim = categories.len();
+    let mut values = vec![0.0f32; column.len() * dim];
+    let mut validity = Vec::with_capacity(column.len());
+    for row in 0..column.len() {
+        if column.is_null(row) {
+            validity.push(false);
+            continue;
+        }
+        let value = array_value_to_string(column, row)?;
+        match (categories.get(&value), on_unknown) {
+            (Some(index), _) => values[row * dim + index] = 1.0,
+            (None, UnknownCategory::Error) => {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "Value {} at row {} of column {} is not a known category",
+                    value, row, source
+                )))
+            }
+            (None, UnknownCategory::Null) => {
+                validity.push(false);
+                continue;
+            }
+            (None, UnknownCategory::Zeros) => {}
+        }
+        validity.push(true);
+    }
+    let DataType::FixedSizeList(item, _) = schema.fields().last().unwrap().data_type() else {
+        unreachable!("The one-hot column is a fixed size list");
+    };
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(FixedSizeListArray::try_new(
+        item.clone(),
+        dim as i32,
+        Arc::new(Float32Array::from(values)),
+        Some(NullBuffer::from(validity)),
+    )?));
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Append a `target_column` with the one-hot encoding of the `source` column: a vector
+/// with a 1 at the index of the category of each row, and 0 elsewhere.
+///
+/// The values are matched to the categories by their string form, so any column type
+/// can be encoded. A null value is a null vector.
+///
+/// # Arguments
+///
+/// * `source` - The name of the categorical column.
+/// * `categories` - The categories, in the order of the vector components.
+/// * `target_column` - The name of the appended `FixedSizeList<Float32, K>` column.
+/// * `on_unknown` - How to encode the values that are not among the categories.
+pub fn one_hot_encode(
+    reader: impl RecordBatchReader + Send + 'static,
+    source: &str,
+    categories: &[String],
+    target_column: &str,
+    on_unknown: UnknownCategory,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    check_columns(&schema, &[source.to_string()])?;
+    let indices = categories
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.clone(), i))
+        .collect::<HashMap<_, _>>();
+    if categories.is_empty() || indices.len() != categories.len() {
+        return Err(Error::Schema {
+            message: format!(
+                "The categories of column {} must be distinct and not empty",
+                source
+            ),
+        });
+    }
+    if schema.field_with_name(target_column).is_ok() {
+        return Err(Error::Schema {
+            message: format!("Column {} already exists", target_column),
+        });
+    }
+    let mut fields = schema.fields().to_vec();
+    fields.push(Arc::new(Field::new(
+        target_column,
+        DataType::FixedSizeList(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            categories.len() as i32,
+        ),
+        true,
+    )));
+    let output_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let s = output_schema.clone();
+    let source = source.to_string();
+    let batches =
+        reader.map(move |batch| one_hot_batch(batch?, &source, &indices, on_unknown, s.clone()));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+/// A cycle of time encoded by [encode_timestamp_cyclical].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CyclicalComponent {
+    /// The time of the day, continuous over the hours.
+    HourOfDay,
+    /// The day of the week, continuous over the days, starting on Monday.
+    DayOfWeek,
+    /// The month of the year, starting in January.
+    MonthOfYear,
+}
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+impl CyclicalComponent {
+    /// The position in the cycle of a timestamp in milliseconds, between 0 and 1.
+    fn position(&self, millis: i64) -> f64 {
+        let days = millis.div_euclid(MILLIS_PER_DAY);
+        let day_fraction = millis.rem_euclid(MILLIS_PER_DAY) as f64 / MILLIS_PER_DAY as f64;
+        match self {
+            Self::HourOfDay => day_fraction,
+            // The epoch is a Thursday.
+            Self::DayOfWeek => ((days + 3).rem_euclid(7) as f64 + day_fraction) / 7.0,
+            Self::MonthOfYear => NaiveDateTime::from_timestamp_millis(millis)
+                .map(|t| t.month0() as f64 / 12.0)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn cyclical_batch(
+    batch: RecordBatch,
+    ts_column: &str,
+    components: &[CyclicalComponent],
+    schema: SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let timestamps = batch
+        .column_by_name(ts_column)
+        .ok_or_else(|| ArrowError::SchemaError(format!("Column {} not found", ts_column)))?;
+    let DataType::Timestamp(_, tz) = timestamps.data_type() else {
+        return Err(ArrowError::SchemaError(format!(
+            "Column {} is not a timestamp column",
+            ts_column
+        )));
+    };
+    // Keep the timezone, so the values stay in UTC.
+    let millis = cast(
+        timestamps,
+        &DataType::Timestamp(TimeUnit::Millisecond, tz.clone()),
+    )?;
+    let millis = cast(&millis, &DataType::Int64)?;
+    let millis = millis.as_primitive::<Int64Type>();
+
+    let mut values = Vec::with_capacity(millis.len() * components.len() * 2);
+    for row in 0..millis.len() {
+        for component in components {
+            let angle = 2.0 * std::f64::consts::PI * component.position(millis.value(row));
+            values.push(angle.sin() as f32);
+            values.push(angle.cos() as f32);
+        }
+    }
+    let DataType::FixedSizeList(item, dim) = schema.fields().last().unwrap().data_type() else {
+        unreachable!("The encoded column is a fixed size list");
+    };
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(FixedSizeListArray::try_new(
+        item.clone(),
+        *dim,
+        Arc::new(Float32Array::from(values)),
+        millis.nulls().cloned(),
+    )?));
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Append a `target_column` encoding the `ts_column` timestamps as cyclical features,
+/// e.g., for time-aware models that should see 23:00 and 01:00 as close.
+///
+/// Each [CyclicalComponent] is encoded as the sine and the cosine of its position in its
+/// cycle, so the target column is a `FixedSizeList<Float32, 2 * components.len()>`.
+/// The timestamps are read in UTC, and a null timestamp is a null vector.
+///
+/// # Arguments
+///
+/// * `ts_column` - The name of the timestamp column.
+/// * `target_column` - The name of the appended vector column.
+/// * `components` - The cycles to encode, in order.
+pub fn encode_timestamp_cyclical(
+    reader: impl RecordBatchReader + Send + 'static,
+    ts_column: &str,
+    target_column: &str,
+    components: Vec<CyclicalComponent>,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let schema = reader.schema();
+    match schema.field_with_name(ts_column).map(|f| f.data_type()) {
+        Ok(DataType::Timestamp(_, _)) => {}
+        _ => {
+            return Err(Error::Schema {
+                message: format!("Column {} is not a timestamp column", ts_column),
+            })
+        }
+    }
+    if components.is_empty() {
+        return Err(Error::Schema {
+            message: "No cyclical component to encode".to_string(),
+        });
+    }
+    if schema.field_with_name(target_column).is_ok() {
+        return Err(Error::Schema {
+            message: format!("Column {} already exists", target_column),
+        });
+    }
+    let mut fields = schema.fields().to_vec();
+    fields.push(Arc::new(Field::new(
+        target_column,
+        DataType::FixedSizeList(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            2 * components.len() as i32,
+        ),
+        true,
+    )));
+    let output_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+
+    let s = output_schema.clone();
+    let ts_column = ts_column.to_string();
+    let batches =
+        reader.map(move |batch| cyclical_batch(batch?, &ts_column, &components, s.clone()));
+    Ok(Box::new(RecordBatchIterator::new(batches, output_schema)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{
+        types::{UInt64Type, UInt8Type},
+        BooleanArray, Float16Array, Float64Array, Int32Array, Int64Array, ListArray, StringArray,
+        TimestampSecondArray,
+    };
+
+    #[test]
+    fn test_transpose_vector() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("x", DataType::Float32, true),
+            Field::new("y", DataType::Float64, true),
+            Field::new("z", DataType::Float32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..2)),
+                Arc::new(Float32Array::from(vec![1.0, 4.0])),
+                Arc::new(Float64Array::from(vec![2.0, 5.0])),
+                Arc::new(Float32Array::from(vec![3.0, 6.0])),
+            ],
+        )
+        .unwrap();
+        let columns = vec!["x".to_string(), "y".to_string(), "z".to_string()];
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        assert!(transpose_vector(reader, columns.clone(), "vector", 2).is_err());
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let stream = transpose_vector(reader, columns, "vector", 3).unwrap();
+        let expected_schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 3),
+                true,
+            ),
+        ]));
+        assert_eq!(stream.schema(), expected_schema);
+
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let expected = RecordBatch::try_new(
+            expected_schema,
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..2)),
+                Arc::new(
+                    FixedSizeListArray::try_new_from_values(
+                        Float32Array::from_iter_values((1..=6).map(|v| v as f32)),
+                        3,
+                    )
+                    .unwrap(),
+                ),
+            ],
+        )
+        .unwrap();
+        assert_eq!(batches, vec![expected]);
+
+        // The pieces stored as single-element lists keep their nulls.
+        let piece_type =
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 1);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("x", piece_type.clone(), true),
+            Field::new("y", piece_type, true),
+        ]));
+        let piece = |values: Vec<f32>, nulls: Option<NullBuffer>| {
+            Arc::new(FixedSizeListArray::new(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                1,
+                Arc::new(Float32Array::from(values)),
+                nulls,
+            )) as ArrayRef
+        };
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                piece(
+                    vec![0.0, 1.0, 2.0],
+                    Some(NullBuffer::from(vec![true, false, true])),
+                )
+                .slice(1, 2),
+                piece(vec![9.0, 3.0, 4.0], None).slice(1, 2),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let columns = vec!["x".to_string(), "y".to_string()];
+        let batches = transpose_vector(reader, columns, "vector", 2)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        let vectors = batches[0].column(0).as_fixed_size_list();
+        assert_eq!(
+            vectors
+                .values()
+                .as_primitive::<Float32Type>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![None, Some(3.0), Some(2.0), Some(4.0)]
+        );
+    }
+
+    #[test]
+    fn test_pack_booleans_to_bitset() {
+        let names = (0..10).map(|i| format!("f{}", i)).collect::<Vec<_>>();
+        let schema = Arc::new(Schema::new(
+            names
+                .iter()
+                .map(|n| Field::new(n, DataType::Boolean, true))
+                .collect::<Vec<_>>(),
+        ));
+        // Row 0 sets features 0, 2 and 9; row 1 sets every feature but 0, and has a null.
+        let columns = (0..10)
+            .map(|i| {
+                let row1 = match i {
+                    0 => Some(false),
+                    5 => None,
+                    _ => Some(true),
+                };
+                Arc::new(BooleanArray::from(vec![Some([0, 2, 9].contains(&i)), row1]))
+                    as Arc<dyn Array>
+            })
+            .collect::<Vec<_>>();
+        let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+
+        let stream = pack_booleans_to_bitset(reader, names, "bits").unwrap();
+        assert_eq!(
+            stream.schema().field(0).data_type(),
+            &DataType::FixedSizeList(Arc::new(Field::new("item", DataType::UInt8, true)), 2)
+        );
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches[0].num_columns(), 1);
+        let bits = batches[0].column(0).as_fixed_size_list();
+        assert_eq!(
+            bits.values().as_primitive::<UInt8Type>(),
+            &UInt8Array::from(vec![0b0000_0101, 0b0000_0010, 0b1101_1110, 0b0000_0011])
+        );
+    }
+
+    #[test]
+    fn test_add_vector_norm_column() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vector",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+            true,
+        )]));
+        let vectors = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            vec![
+                Some(vec![Some(3.0), Some(4.0)]),
+                None,
+                Some(vec![Some(-6.0), Some(8.0)]),
+                Some(vec![Some(0.0), Some(0.0)]),
+            ],
+            2,
+        );
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        assert!(add_vector_norm_column(reader, "vector", "vector", VectorNorm::L2).is_err());
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let stream = add_vector_norm_column(reader, "vector", "norm", VectorNorm::L2).unwrap();
+        assert_eq!(
+            stream.schema().field(1),
+            &Field::new("norm", DataType::Float32, true)
+        );
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches[0].num_columns(), 2);
+        assert_eq!(
+            batches[0].column(1).as_primitive::<Float32Type>(),
+            &Float32Array::from(vec![Some(5.0), None, Some(10.0), Some(0.0)])
+        );
+    }
+
+    #[test]
+    fn test_sparse_to_dense_vector() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                "idx",
+                DataType::List(Arc::new(Field::new("item", DataType::Int32, true))),
+                true,
+            ),
+            Field::new(
+                "val",
+                DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+                true,
+            ),
+        ]));
+        let batch = |indices: Vec<Option<Vec<Option<i32>>>>| {
+            let values = indices
+                .iter()
+                .map(|r| {
+                    r.as_ref()
+                        .map(|r| (1..=r.len()).map(|v| Some(v as f32)).collect::<Vec<_>>())
+                })
+                .collect::<Vec<_>>();
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(indices)),
+                    Arc::new(ListArray::from_iter_primitive::<Float32Type, _, _>(values)),
+                ],
+            )
+            .unwrap()
+        };
+
+        let sparse = batch(vec![Some(vec![Some(0), Some(2)]), None, Some(vec![])]);
+        let reader = RecordBatchIterator::new(vec![Ok(sparse)], schema.clone());
+        let stream = sparse_to_dense_vector(reader, "idx", "val", 3, "vector").unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches[0].num_columns(), 1);
+        assert_eq!(batches[0].schema().field(0).name(), "vector");
+        let vectors = batches[0].column(0).as_fixed_size_list();
+        assert_eq!(
+            vectors.values().as_primitive::<Float32Type>().values(),
+            &[1.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+        );
+        assert!(vectors.is_null(1));
+        assert!(vectors.is_valid(2));
+
+        let out_of_range = batch(vec![Some(vec![Some(3)])]);
+        let reader = RecordBatchIterator::new(vec![Ok(out_of_range)], schema);
+        let mut stream = sparse_to_dense_vector(reader, "idx", "val", 3, "vector").unwrap();
+        let err = stream.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("index 3 out of range"), "{}", err);
+    }
+
+    #[test]
+    fn test_with_computed_column() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![Some(1), Some(2), None])),
+                Arc::new(Int32Array::from(vec![Some(10), Some(20), Some(30)])),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+
+        let sum: ComputeColumn = Arc::new(|batch: &RecordBatch| {
+            Ok(arrow::compute::kernels::numeric::add(
+                batch.column(0),
+                batch.column(1),
+            )?)
+        });
+        let stream =
+            with_computed_column(reader, Field::new("sum", DataType::Int64, true), sum).unwrap();
+        assert_eq!(
+            stream.schema().field(2),
+            &Field::new("sum", DataType::Int64, true)
+        );
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            batches[0].column(2).as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![Some(11), Some(22), None])
+        );
+    }
+
+    #[test]
+    fn test_byteswap_column() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int32, true),
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                true,
+            ),
+        ]));
+        // Big-endian values, read as little-endian.
+        let int = i32::from_le_bytes(258_i32.to_be_bytes());
+        let floats = [1.5_f32, -2.0].map(|v| f32::from_le_bytes(v.to_be_bytes()));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![Some(int), None])),
+                Arc::new(
+                    FixedSizeListArray::try_new_from_values(
+                        Float32Array::from(floats.into_iter().chain(floats).collect::<Vec<_>>()),
+                        2,
+                    )
+                    .unwrap(),
+                ),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let reader = byteswap_column(reader, "i").unwrap();
+        let stream = byteswap_column(reader, "vec").unwrap();
+        assert_eq!(stream.schema(), schema);
+
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            batches[0].column(0).as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![Some(258), None])
+        );
+        assert_eq!(
+            batches[0]
+                .column(1)
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float32Type>()
+                .values(),
+            &[1.5, -2.0, 1.5, -2.0]
+        );
+    }
+
+    #[test]
+    fn test_columns_to_vector() {
+        let mut fields = vec![Field::new("id", DataType::Int32, false)];
+        fields.extend((0..8).map(|i| Field::new(format!("f{}", i), DataType::Float64, true)));
+        let schema = Arc::new(Schema::new(fields));
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(Int32Array::from(vec![1, 2]))];
+        columns.extend(
+            (0..8).map(|i| Arc::new(Float64Array::from(vec![i as f64, -(i as f64)])) as ArrayRef),
+        );
+        let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let prefix = VectorColumns::Prefix("f".to_string());
+        assert!(columns_to_vector(reader, prefix.clone(), "vector", 9).is_err());
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let stream = columns_to_vector(reader, prefix, "vector", 8).unwrap();
+        assert_eq!(stream.schema().fields().len(), 2);
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let vectors = batches[0].column(1).as_fixed_size_list();
+        assert_eq!(vectors.value_length(), 8);
+        assert_eq!(
+            vectors.value(0).as_primitive::<Float32Type>().values(),
+            &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]
+        );
+        assert_eq!(
+            vectors.value(1).as_primitive::<Float32Type>().values(),
+            &[0.0, -1.0, -2.0, -3.0, -4.0, -5.0, -6.0, -7.0]
+        );
+
+        // Named columns are assembled in the given order.
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let names = VectorColumns::Names((0..8).rev().map(|i| format!("f{}", i)).collect());
+        let stream = columns_to_vector(reader, names, "vector", 8).unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            batches[0]
+                .column(1)
+                .as_fixed_size_list()
+                .value(0)
+                .as_primitive::<Float32Type>()
+                .values(),
+            &[7.0, 6.0, 5.0, 4.0, 3.0, 2.0, 1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_split_vector() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 5),
+                true,
+            ),
+        ]));
+        let vectors = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            vec![
+                Some((0..5).map(|v| Some(v as f32)).collect::<Vec<_>>()),
+                None,
+                Some((5..10).map(|v| Some(v as f32)).collect::<Vec<_>>()),
+            ],
+            5,
+        );
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3])), Arc::new(vectors)],
+        )
+        .unwrap();
+        let splits = vec![("text".to_string(), 2), ("image".to_string(), 3)];
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let bad_splits = vec![("text".to_string(), 2), ("image".to_string(), 2)];
+        assert!(split_vector(reader, "vec", bad_splits).is_err());
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let stream = split_vector(reader, "vec", splits).unwrap();
+        let names = stream
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["id", "text", "image"]);
+
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let text = batches[0].column(1).as_fixed_size_list();
+        let image = batches[0].column(2).as_fixed_size_list();
+        assert_eq!(text.value_length(), 2);
+        assert_eq!(image.value_length(), 3);
+        assert_eq!(
+            text.value(2).as_primitive::<Float32Type>().values(),
+            &[5.0, 6.0]
+        );
+        assert_eq!(
+            image.value(0).as_primitive::<Float32Type>().values(),
+            &[2.0, 3.0, 4.0]
+        );
+        assert!(text.is_null(1) && image.is_null(1));
+    }
+
+    #[test]
+    fn test_chunk_vector_column() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, false)), 10),
+                true,
+            ),
+        ]));
+        let vectors = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            vec![
+                Some((0..10).map(|v| Some(v as f32)).collect::<Vec<_>>()),
+                None,
+            ],
+            10,
+        );
+        let vectors = FixedSizeListArray::try_new(
+            Arc::new(Field::new("item", DataType::Float32, false)),
+            10,
+            vectors.values().clone(),
+            vectors.nulls().cloned(),
+        )
+        .unwrap();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2])), Arc::new(vectors)],
+        )
+        .unwrap();
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        assert!(chunk_vector_column(reader, "vec", 0).is_err());
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let stream = chunk_vector_column(reader, "vec", 4).unwrap();
+        let names = stream
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["id", "vec_chunk_0", "vec_chunk_1", "vec_chunk_2"]
+        );
+
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let chunks = (1..4)
+            .map(|i| batches[0].column(i).as_fixed_size_list())
+            .collect::<Vec<_>>();
+        assert!(chunks.iter().all(|c| c.value_length() == 4 && c.is_null(1)));
+        assert_eq!(
+            chunks[1].value(0).as_primitive::<Float32Type>().values(),
+            &[4.0, 5.0, 6.0, 7.0]
+        );
+        let last = chunks[2].value(0);
+        let last = last.as_primitive::<Float32Type>();
+        assert_eq!(
+            last.iter().collect::<Vec<_>>(),
+            vec![Some(8.0), Some(9.0), None, None]
+        );
+
+        // A chunk larger than the vector pads it.
+        let reader = RecordBatchIterator::new(vec![], schema);
+        let stream = chunk_vector_column(reader, "vec", i32::MAX).unwrap();
+        assert_eq!(
+            stream.schema().field(1).data_type(),
+            &DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                i32::MAX
+            )
+        );
+        assert_eq!(stream.schema().fields().len(), 2);
+    }
+
+    #[test]
+    fn test_dedup_near_vectors() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                true,
+            ),
+        ]));
+        let batch = |ids: Vec<i32>, vectors: Vec<Option<[f32; 2]>>| {
+            let vectors = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+                vectors
+                    .into_iter()
+                    .map(|v| v.map(|v| v.into_iter().map(Some).collect::<Vec<_>>())),
+                2,
+            );
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from(ids)), Arc::new(vectors)],
+            )
+            .unwrap()
+        };
+        // Row 2 is a near duplicate of row 1, and row 4 of row 1 across batches.
+        let batches = vec![
+            batch(
+                vec![1, 2, 3],
+                vec![Some([1.0, 1.0]), Some([1.0, 1.01]), Some([5.0, 5.0])],
+            ),
+            batch(vec![4, 5], vec![Some([0.99, 1.0]), None]),
+        ];
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+
+        let stream = dedup_near_vectors(reader, "vec", MetricType::L2, 0.01).unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let ids = batches
+            .iter()
+            .flat_map(|b| b.column(0).as_primitive::<Int32Type>().values().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 3, 5]);
+
+        let reader = RecordBatchIterator::new(vec![], schema);
+        assert!(dedup_near_vectors(reader, "id", MetricType::L2, 0.01).is_err());
+    }
+
+    #[test]
+    fn test_add_group_centroid() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("doc", DataType::Utf8, true),
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                true,
+            ),
+        ]));
+        let vectors = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            vec![
+                Some(vec![Some(1.0), Some(2.0)]),
+                Some(vec![Some(10.0), Some(20.0)]),
+                Some(vec![Some(3.0), Some(4.0)]),
+                None,
+                Some(vec![Some(30.0), None]),
+            ],
+            2,
+        );
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "a", "a", "b"])),
+                Arc::new(vectors),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+
+        let stream = add_group_centroid(reader, "doc", "vec", "centroid").unwrap();
+        assert_eq!(stream.schema().field(2).name(), "centroid");
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let centroids = batches[0].column(2).as_fixed_size_list();
+        let centroid = |row| {
+            centroids
+                .value(row)
+                .as_primitive::<Float32Type>()
+                .values()
+                .to_vec()
+        };
+        for row in [0, 2, 3] {
+            assert_eq!(centroid(row), vec![2.0, 3.0]);
+        }
+        for row in [1, 4] {
+            assert_eq!(centroid(row), vec![20.0, 20.0]);
+        }
+
+        let reader = RecordBatchIterator::new(vec![], schema.clone());
+        assert!(add_group_centroid(reader, "doc", "doc", "centroid").is_err());
+        let reader = RecordBatchIterator::new(vec![], schema);
+        assert!(add_group_centroid(reader, "doc", "vec", "vec").is_err());
+    }
+
+    #[test]
+    fn test_group_vectors_to_list() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("doc", DataType::Int32, false),
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float16, true)), 2),
+                true,
+            ),
+        ]));
+        let vectors = FixedSizeListArray::try_new_from_values(
+            Float16Array::from_iter_values(
+                [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0].map(f16::from_f32),
+            ),
+            2,
+        )
+        .unwrap();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 1, 2])),
+                Arc::new(vectors),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+
+        let stream = group_vectors_to_list(reader, "doc", "vec", "vectors").unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let batch = &batches[0];
+        assert_eq!(batch.schema().field(1).name(), "vectors");
+        assert_eq!(
+            batch.column(0).as_primitive::<Int32Type>().values(),
+            &[1, 2]
+        );
+        let lists = batch.column(1).as_list::<i32>();
+        assert_eq!(lists.value_length(0), 3);
+        assert_eq!(lists.value_length(1), 1);
+        let doc = lists.value(0);
+        assert_eq!(
+            doc.as_fixed_size_list()
+                .values()
+                .as_primitive::<Float32Type>()
+                .values(),
+            &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]
+        );
+
+        let reader = RecordBatchIterator::new(vec![], schema);
+        assert!(group_vectors_to_list(reader, "doc", "doc", "vectors").is_err());
+    }
+
+    #[test]
+    fn test_ungroup_vectors() {
+        let vector_type =
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("doc", DataType::Int32, false),
+            Field::new("title", DataType::Utf8, true),
+            Field::new(
+                "vectors",
+                DataType::List(Arc::new(Field::new("item", vector_type.clone(), true))),
+                true,
+            ),
+        ]));
+        let vectors = FixedSizeListArray::try_new_from_values(
+            Float32Array::from(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+            2,
+        )
+        .unwrap();
+        let lists = ListArray::try_new(
+            Arc::new(Field::new("item", vector_type.clone(), true)),
+            OffsetBuffer::from_lengths([3, 0]),
+            Arc::new(vectors),
+            None,
+        )
+        .unwrap();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(lists),
+            ],
+        )
+        .unwrap();
+        let doc_keys = vec!["doc".to_string()];
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let stream = ungroup_vectors(reader, "vectors", &doc_keys, EmptyVectorList::Skip).unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let batch_out = &batches[0];
+        assert_eq!(batch_out.num_columns(), 2);
+        assert_eq!(batch_out.schema().field(1).data_type(), &vector_type);
+        assert_eq!(
+            batch_out.column(0).as_primitive::<Int32Type>().values(),
+            &[1, 1, 1]
+        );
+        let vectors = batch_out.column(1).as_fixed_size_list();
+        assert_eq!(
+            vectors.value(2).as_primitive::<Float32Type>().values(),
+            &[5.0, 6.0]
+        );
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let stream =
+            ungroup_vectors(reader, "vectors", &doc_keys, EmptyVectorList::NullRow).unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            batches[0].column(0).as_primitive::<Int32Type>().values(),
+            &[1, 1, 1, 2]
+        );
+        assert!(batches[0].column(1).is_null(3));
+
+        let reader = RecordBatchIterator::new(vec![], schema);
+        assert!(ungroup_vectors(reader, "title", &doc_keys, EmptyVectorList::Skip).is_err());
+    }
+
+    #[test]
+    fn test_minmax_scale_columns() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("x", DataType::Int32, true),
+            Field::new("c", DataType::Float32, true),
+        ]));
+        let batch = |ids: Vec<i32>, x: Vec<Option<i32>>| {
+            let c = Float32Array::from(vec![7.0; ids.len()]);
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(ids)),
+                    Arc::new(Int32Array::from(x)),
+                    Arc::new(c),
+                ],
+            )
+            .unwrap()
+        };
+        let batches = vec![
+            batch(vec![1, 2], vec![Some(10), None]),
+            batch(vec![3, 4], vec![Some(-10), Some(5)]),
+        ];
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+
+        let columns = vec!["x".to_string(), "c".to_string()];
+        let (stream, params) = minmax_scale_columns(reader, columns).unwrap();
+        assert_eq!(params.ranges["x"], (-10.0, 10.0));
+        assert_eq!(params.ranges["c"], (7.0, 7.0));
+        assert_eq!(params.inverse("x", 0.75), Some(5.0));
+        assert_eq!(params.inverse("id", 0.5), None);
+        assert_eq!(stream.schema().field(1).data_type(), &DataType::Float64);
+
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let x = batches
+            .iter()
+            .flat_map(|b| {
+                b.column(1)
+                    .as_primitive::<Float64Type>()
+                    .iter()
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(x, vec![Some(1.0), None, Some(0.0), Some(0.75)]);
+        assert_eq!(
+            batches[0].column(2).as_primitive::<Float64Type>().values(),
+            &[0.0, 0.0]
+        );
+        assert_eq!(
+            batches[1].column(0).as_primitive::<Int32Type>().values(),
+            &[3, 4]
+        );
+
+        let reader = RecordBatchIterator::new(vec![], schema);
+        assert!(minmax_scale_columns(reader, vec!["missing".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_concat_vectors() {
+        let vector_type =
+            |dt, dim| DataType::FixedSizeList(Arc::new(Field::new("item", dt, true)), dim);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("text", vector_type(DataType::Float32, 2), true),
+            Field::new("image", vector_type(DataType::Float64, 3), true),
+        ]));
+        let text = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            vec![
+                Some(vec![Some(1.0), Some(2.0)]),
+                None,
+                Some(vec![Some(5.0), Some(6.0)]),
+            ],
+            2,
+        );
+        let image = FixedSizeListArray::from_iter_primitive::<Float64Type, _, _>(
+            vec![
+                Some(vec![Some(1.5), Some(2.5), Some(3.5)]),
+                Some(vec![Some(4.5), Some(5.5), Some(6.5)]),
+                Some(vec![Some(7.5), Some(8.5), Some(9.5)]),
+            ],
+            3,
+        );
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(text),
+                Arc::new(image),
+            ],
+        )
+        .unwrap();
+        let columns = vec!["text".to_string(), "image".to_string()];
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        assert!(concat_vectors(
+            reader,
+            columns.clone(),
+            "fused",
+            true,
+            ConcatNulls::NullVector
+        )
+        .is_err());
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let stream = concat_vectors(
+            reader,
+            columns.clone(),
+            "fused",
+            false,
+            ConcatNulls::NullVector,
+        )
+        .unwrap();
+        assert_eq!(stream.schema().fields().len(), 2);
+        assert_eq!(
+            stream.schema().field(1).data_type(),
+            &vector_type(DataType::Float32, 5)
+        );
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let fused = batches[0].column(1).as_fixed_size_list();
+        assert_eq!(
+            fused.value(0).as_primitive::<Float32Type>().values(),
+            &[1.0, 2.0, 1.5, 2.5, 3.5]
+        );
+        assert!(fused.is_null(1));
+        assert_eq!(
+            fused.value(2).as_primitive::<Float32Type>().values(),
+            &[5.0, 6.0, 7.5, 8.5, 9.5]
+        );
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let stream =
+            concat_vectors(reader, columns, "fused", false, ConcatNulls::NullComponents).unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let fused = batches[0].column(1).as_fixed_size_list();
+        assert!(fused.is_valid(1));
+        assert_eq!(
+            fused
+                .value(1)
+                .as_primitive::<Float32Type>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![None, None, Some(4.5), Some(5.5), Some(6.5)]
+        );
+    }
+
+    #[test]
+    fn test_split_column() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("key", DataType::Utf8, true),
+            Field::new("score", DataType::Float32, false),
+        ]));
+        let batch = |keys: Vec<Option<&str>>| {
+            let scores = Float32Array::from(vec![1.0; keys.len()]);
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(StringArray::from(keys)), Arc::new(scores)],
+            )
+            .unwrap()
+        };
+        let targets = vec![
+            Field::new("user_id", DataType::Int64, true),
+            Field::new("year", DataType::Int32, true),
+            Field::new("region", DataType::Utf8, true),
+        ];
+
+        let batches = vec![batch(vec![Some("42|2023|us"), None, Some("7|2021|eu")])];
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+        let stream = split_column(reader, "key", "|", targets.clone()).unwrap();
+        let names = stream
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["user_id", "year", "region", "score"]);
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            batches[0].column(0).as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![Some(42), None, Some(7)])
+        );
+        assert_eq!(
+            batches[0].column(1).as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![Some(2023), None, Some(2021)])
+        );
+        assert_eq!(
+            batches[0].column(2).as_string::<i32>(),
+            &StringArray::from(vec![Some("us"), None, Some("eu")])
+        );
+
+        let batches = vec![batch(vec![Some("42|2023")])];
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+        let mut stream = split_column(reader, "key", "|", targets.clone()).unwrap();
+        let err = stream.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("has 2 parts at row 0, expected 3"));
+
+        let batches = vec![batch(vec![Some("42|2023|us"), Some("7|x|eu")])];
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+        let mut stream = split_column(reader, "key", "|", targets.clone()).unwrap();
+        let err = stream.next().unwrap().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("has part 1 \"x\" at row 1 that can not be coerced to Int32"));
+
+        let reader = RecordBatchIterator::new(vec![], schema);
+        assert!(split_column(reader, "score", "|", targets).is_err());
+    }
+
+    #[test]
+    fn test_interleave_complex() {
+        let vector_type = |dim| {
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim)
+        };
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("real", vector_type(2), true),
+            Field::new("imag", vector_type(2), true),
+            Field::new("other", vector_type(3), true),
+        ]));
+        let vectors = |values: Vec<f32>, dim| {
+            Arc::new(
+                FixedSizeListArray::try_new_from_values(Float32Array::from(values), dim).unwrap(),
+            )
+        };
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                vectors(vec![1.0, 2.0, 3.0, 4.0], 2),
+                vectors(vec![10.0, 20.0, 30.0, 40.0], 2),
+                vectors(vec![0.0; 6], 3),
+            ],
+        )
+        .unwrap();
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        assert!(interleave_complex(reader, "real", "other", "complex").is_err());
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let stream = interleave_complex(reader, "real", "imag", "complex").unwrap();
+        let names = stream
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["id", "complex", "other"]);
+
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let complex = batches[0].column(1).as_fixed_size_list();
+        assert_eq!(complex.value_length(), 4);
+        assert_eq!(
+            complex.values().as_primitive::<Float32Type>().values(),
+            &[1.0, 10.0, 2.0, 20.0, 3.0, 30.0, 4.0, 40.0]
+        );
+    }
+
+    #[test]
+    fn test_pad_batches() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec![Some("a"), None, Some("c")])),
+            ],
+        )
+        .unwrap();
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        assert!(pad_batches(reader, 8, HashMap::new()).is_err());
+
+        let defaults = HashMap::from([(
+            "id".to_string(),
+            Arc::new(Int32Array::from(vec![-1])) as ArrayRef,
+        )]);
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let batches = pad_batches(reader, 8, defaults)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches[0].num_rows(), 8);
+        assert_eq!(
+            batches[0].column(0).as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![1, 2, 3, -1, -1, -1, -1, -1])
+        );
+        let names = batches[0].column(1).as_string::<i32>();
+        assert_eq!(names.value(2), "c");
+        assert_eq!(names.null_count(), 6);
+        assert!((3..8).all(|i| names.is_null(i)));
+    }
+
+    #[test]
+    fn test_add_row_fingerprint() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("score", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4])),
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    Some("a"),
+                    Some("b"),
+                    None,
+                ])),
+                Arc::new(Int32Array::from(vec![None, None, None, None])),
+            ],
+        )
+        .unwrap();
+        let fingerprint = |algo| {
+            let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+            let columns = vec!["name".to_string(), "score".to_string()];
+            let batches = add_row_fingerprint(reader, columns, "fingerprint", algo)
+                .unwrap()
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .unwrap();
+            batches[0].column(3).clone()
+        };
+
+        let fingerprints = fingerprint(FingerprintAlgorithm::Sha256);
+        let fingerprints = fingerprints.as_fixed_size_binary();
+        assert_eq!(fingerprints.value_length(), 32);
+        assert_eq!(fingerprints.value(0), fingerprints.value(1));
+        assert_ne!(fingerprints.value(0), fingerprints.value(2));
+        assert_ne!(fingerprints.value(2), fingerprints.value(3));
+
+        let hex = fingerprint(FingerprintAlgorithm::Sha256Hex);
+        let hex = hex.as_string::<i32>();
+        assert_eq!(hex.value(0).len(), 64);
+        assert_eq!(hex.value(0), hex.value(1));
+        assert_ne!(hex.value(0), hex.value(2));
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.slice(0, 0))], schema.clone());
+        let columns = vec!["name".to_string()];
+        let batches = add_row_fingerprint(reader, columns, "fp", FingerprintAlgorithm::Sha256)
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches[0].num_rows(), 0);
+        assert_eq!(
+            batches[0].column(3).data_type(),
+            &DataType::FixedSizeBinary(32)
+        );
+
+        // The same display form in columns of different types.
+        let mixed_schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Utf8, false),
+            Field::new("c", DataType::Float64, false),
+            Field::new("d", DataType::Utf8, false),
+        ]));
+        let mixed = RecordBatch::try_new(
+            mixed_schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1])),
+                Arc::new(StringArray::from(vec!["1"])),
+                Arc::new(Float64Array::from(vec![1.5])),
+                Arc::new(StringArray::from(vec!["1.5"])),
+            ],
+        )
+        .unwrap();
+        let digests = ["a", "b", "c", "d"]
+            .iter()
+            .map(|column| {
+                let reader =
+                    RecordBatchIterator::new(vec![Ok(mixed.clone())], mixed_schema.clone());
+                let batches = add_row_fingerprint(
+                    reader,
+                    vec![column.to_string()],
+                    "fp",
+                    FingerprintAlgorithm::Sha256Hex,
+                )
+                .unwrap()
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .unwrap();
+                batches[0].column(4).as_string::<i32>().value(0).to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_ne!(digests[0], digests[1]);
+        assert_ne!(digests[2], digests[3]);
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let columns = vec!["missing".to_string()];
+        assert!(add_row_fingerprint(reader, columns, "fp", FingerprintAlgorithm::Sha256).is_err());
+    }
+
+    #[test]
+    fn test_add_rowid_column() {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let batches = [vec![1, 2, 3], vec![4, 5]]
+            .into_iter()
+            .map(|values| {
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))])
+            })
+            .collect::<Vec<_>>();
+        let reader = RecordBatchIterator::new(batches, schema.clone());
+
+        let stream = add_rowid_column(reader, "_rowid", 100).unwrap();
+        assert_eq!(
+            stream.schema().field(1),
+            &Field::new("_rowid", DataType::UInt64, false)
+        );
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let ids = batches
+            .iter()
+            .flat_map(|b| b.column(1).as_primitive::<UInt64Type>().values().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![100, 101, 102, 103, 104]);
+
+        let reader = RecordBatchIterator::new(vec![], schema);
+        assert!(add_rowid_column(reader, "a", 0).is_err());
+    }
+
+    #[test]
+    fn test_encode_timestamp_cyclical() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "ts",
+            DataType::Timestamp(TimeUnit::Second, None),
+            true,
+        )]));
+        // 2024-03-15T18:00:00Z, a Friday.
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(TimestampSecondArray::from(vec![
+                Some(1_710_525_600),
+                None,
+            ]))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let components = vec![
+            CyclicalComponent::HourOfDay,
+            CyclicalComponent::DayOfWeek,
+            CyclicalComponent::MonthOfYear,
+        ];
+
+        let stream = encode_timestamp_cyclical(reader, "ts", "time", components).unwrap();
+        let batches = stream.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let encoded = batches[0].column(1).as_fixed_size_list();
+        assert_eq!(encoded.value_length(), 6);
+        assert!(encoded.is_null(1));
+        let angle = |fraction: f64| 2.0 * std::f64::consts::PI * fraction;
+        let expected = [angle(0.75), angle((4.0 + 0.75) / 7.0), angle(2.0 / 12.0)]
+            .iter()
+            .flat_map(|a| [a.sin() as f32, a.cos() as f32])
+            .collect::<Vec<_>>();
+        let values = encoded.value(0);
+        for (v, e) in values
+            .as_primitive::<Float32Type>()
+            .values()
+            .iter()
+            .zip(expected)
+        {
+            assert!((v - e).abs() < 1e-6, "{} != {}", v, e);
+        }
+        // 18:00 is three quarters of the day.
+        assert!((values.as_primitive::<Float32Type>().value(0) + 1.0).abs() < 1e-6);
+
+        let reader = RecordBatchIterator::new(vec![], schema);
+        assert!(encode_timestamp_cyclical(reader, "ts", "time", vec![]).is_err());
+    }
+
+    #[test]
+    fn test_one_hot_encode() {
+        let schema = Arc::new(Schema::new(vec![Field::new("color", DataType::Utf8, true)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from(vec![
+                Some("green"),
+                None,
+                Some("red"),
+                Some("blue"),
+                Some("pink"),
+            ]))],
+        )
+        .unwrap();
+        let categories = ["red", "green", "blue"].map(String::from);
+        let encode = |on_unknown| {
+            let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+            one_hot_encode(reader, "color", &categories, "onehot", on_unknown)
+                .unwrap()
+                .collect::<std::result::Result<Vec<_>, _>>()
+        };
+
+        let err = encode(UnknownCategory::Error).unwrap_err();
+        assert!(err.to_string().contains("Value pink at row 4"), "{}", err);
+
+        let batches = encode(UnknownCategory::Zeros).unwrap();
+        let vectors = batches[0].column(1).as_fixed_size_list();
+        assert_eq!(vectors.value_length(), 3);
+        assert!(vectors.is_null(1));
+        assert_eq!(
+            vectors.values().as_primitive::<Float32Type>().values(),
+            &[
+                0.0, 1.0, 0.0, // green
+                0.0, 0.0, 0.0, // null
+                1.0, 0.0, 0.0, // red
+                0.0, 0.0, 1.0, // blue
+                0.0, 0.0, 0.0, // pink
+            ]
+        );
+        assert!(vectors.is_valid(4));
+
+        let batches = encode(UnknownCategory::Null).unwrap();
+        assert!(batches[0].column(1).is_null(4));
+    }
+
+    #[test]
+    fn test_reject_invalid_replaced_columns() {
+        let vector_type =
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 4);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("x", DataType::Float32, true),
+            Field::new("y", DataType::Float32, true),
+            Field::new("vec", vector_type, true),
+        ]));
+        let reader = || RecordBatchIterator::new(vec![], schema.clone());
+        let xy = vec!["x".to_string(), "y".to_string()];
+
+        let err = transpose_vector(reader(), vec![], "vector", 0)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("must be positive"), "{}", err);
+        let prefix = VectorColumns::Prefix("f".to_string());
+        assert!(columns_to_vector(reader(), prefix, "vector", 0).is_err());
+        let err = pack_booleans_to_bitset(reader(), vec![], "bits")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("At least one"), "{}", err);
+
+        let err = transpose_vector(reader(), xy.clone(), "id", 2)
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("Column id already exists"),
+            "{}",
+            err
+        );
+        // The target may reuse the name of a replaced column.
+        assert!(transpose_vector(reader(), xy, "x", 2).is_ok());
+
+        let splits = vec![("id".to_string(), 2), ("b".to_string(), 2)];
+        let err = split_vector(reader(), "vec", splits).err().unwrap();
+        assert!(
+            err.to_string().contains("Column id already exists"),
+            "{}",
+            err
+        );
+        let splits = vec![("a".to_string(), 2), ("a".to_string(), 2)];
+        assert!(split_vector(reader(), "vec", splits).is_err());
+        let targets = vec![Field::new("y", DataType::Utf8, true)];
+        assert!(split_column(reader(), "x", "|", targets).is_err());
+    }
+}
@@ -35,6 +35,17 @@ pub enum Error {
     Lance { message: String },
     #[snafu(display("LanceDB Schema Error: {message}"))]
     Schema { message: String },
+    #[snafu(display(
+        "LanceDB Schema Error: columns have different lengths: {}",
+        lengths
+            .iter()
+            .map(|(name, len)| format!("{name}={len}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))]
+    ColumnLengthMismatch { lengths: Vec<(String, usize)> },
+    #[snafu(display("LanceDB Schema Error: the data has more than {limit} rows"))]
+    RowLimitExceeded { limit: usize },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
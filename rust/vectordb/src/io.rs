@@ -1 +1,3 @@
 pub mod object_store;
+#[cfg(feature = "parquet")]
+pub mod parquet;
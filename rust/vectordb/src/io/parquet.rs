@@ -0,0 +1,249 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read Parquet files, coerced to a target schema.
+
+use std::{fs::File, path::Path, sync::Arc};
+
+use arrow_array::{
+    FixedSizeBinaryArray, RecordBatchIterator, RecordBatchReader, TimestampNanosecondArray,
+};
+use arrow_schema::{ArrowError, SchemaRef};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+use crate::data::sanitize::{coerce_schema_with_options, CoercionOptions};
+use crate::error::{Error, Result};
+
+/// Open one or more Parquet files as a single reader, coerced to the `target_schema`.
+///
+/// Each file is coerced on its own, so the files may have different schemas, as long as
+/// they all coerce to the target. Vector columns stored as Parquet lists are coerced to
+/// `FixedSizeList` columns, if the target schema says so. The files are read in order.
+///
+/// # Arguments
+///
+/// * `paths` - The paths of the Parquet files.
+/// * `target_schema` - The schema of the returned reader.
+/// * `options` - The [CoercionOptions] applied to every file.
+pub fn parquet_reader(
+    paths: &[impl AsRef<Path>],
+    target_schema: SchemaRef,
+    options: CoercionOptions,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    if paths.is_empty() {
+        return Err(Error::Store {
+            message: "No Parquet file to read".to_string(),
+        });
+    }
+    let readers = paths
+        .iter()
+        .map(|path| {
+            let path = path.as_ref();
+            let store_error = |e: &dyn std::fmt::Display| Error::Store {
+                message: format!("Unable to read Parquet file {}: {}", path.display(), e),
+            };
+            let file = File::open(path).map_err(|e| store_error(&e))?;
+            let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+                .and_then(|builder| builder.build())
+                .map_err(|e| store_error(&e))?;
+            coerce_schema_with_options(reader, target_schema.clone(), options.clone())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let schema = readers[0].schema();
+    Ok(Box::new(RecordBatchIterator::new(
+        readers.into_iter().flatten(),
+        schema,
+    )))
+}
+
+/// The Julian day of the Unix epoch.
+const JULIAN_DAY_OF_EPOCH: i64 = 2_440_588;
+
+const NANOS_PER_DAY: i64 = 86_400_000_000_000;
+
+/// Decode legacy Parquet `Int96` timestamps, carried as `FixedSizeBinary(12)` values,
+/// into nanosecond timestamps in the timezone `tz`.
+///
+/// The arrow Parquet reader already decodes `Int96` columns as `Timestamp(Nanosecond)`,
+/// so [parquet_reader] never needs this. The raw values reach us as `FixedSizeBinary(12)`
+/// when another system copied the physical bytes of the column, e.g., an export that
+/// does not know the legacy type.
+///
+/// Each value holds the nanoseconds within the day in its first 8 bytes, and the Julian
+/// day in its last 4 bytes, both little-endian. Nanoseconds outside of a day are rejected.
+pub(crate) fn int96_to_timestamp(
+    array: &FixedSizeBinaryArray,
+    tz: Option<Arc<str>>,
+) -> std::result::Result<TimestampNanosecondArray, ArrowError> {
+    if array.value_length() != 12 {
+        return Err(ArrowError::CastError(format!(
+            "Int96 timestamps have 12 bytes, got {}",
+            array.value_length()
+        )));
+    }
+    let timestamps = array
+        .iter()
+        .map(|value| {
+            let Some(value) = value else {
+                return Ok(None);
+            };
+            let nanos = i64::from_le_bytes(value[..8].try_into().unwrap());
+            let day = i32::from_le_bytes(value[8..].try_into().unwrap()) as i64;
+            if !(0..NANOS_PER_DAY).contains(&nanos) {
+                return Err(ArrowError::CastError(format!(
+                    "Int96 timestamp of Julian day {} has {} nanoseconds, outside of a day",
+                    day, nanos
+                )));
+            }
+            (day - JULIAN_DAY_OF_EPOCH)
+                .checked_mul(NANOS_PER_DAY)
+                .and_then(|n| n.checked_add(nanos))
+                .map(Some)
+                .ok_or_else(|| {
+                    ArrowError::CastError(format!(
+                        "Int96 timestamp of Julian day {} overflows nanoseconds",
+                        day
+                    ))
+                })
+        })
+        .collect::<std::result::Result<Vec<_>, ArrowError>>()?;
+    Ok(TimestampNanosecondArray::from(timestamps).with_timezone_opt(tz))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::data::sanitize::coerce_array;
+
+    use arrow_array::{
+        cast::AsArray,
+        types::{Float32Type, Int64Type, TimestampMicrosecondType, TimestampNanosecondType},
+        Array, Int32Array, ListArray, RecordBatch,
+    };
+    use arrow_schema::{DataType, Field, Schema, TimeUnit};
+    use parquet::arrow::ArrowWriter;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parquet_reader() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new(
+                "vec",
+                DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+                true,
+            ),
+        ]));
+        let dir = tempdir().unwrap();
+        let paths = (0..2)
+            .map(|i| {
+                let batch = RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from(vec![i])),
+                        Arc::new(ListArray::from_iter_primitive::<Float32Type, _, _>(vec![
+                            Some(vec![Some(i as f32), Some(1.0)]),
+                        ])),
+                    ],
+                )
+                .unwrap();
+                let path = dir.path().join(format!("{}.parquet", i));
+                let mut writer =
+                    ArrowWriter::try_new(File::create(&path).unwrap(), schema.clone(), None)
+                        .unwrap();
+                writer.write(&batch).unwrap();
+                writer.close().unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+
+        let target = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2),
+                true,
+            ),
+        ]));
+        let reader = parquet_reader(&paths, target.clone(), CoercionOptions::default()).unwrap();
+        assert_eq!(reader.schema(), target);
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(
+            batches[1].column(0).as_primitive::<Int64Type>().values(),
+            &[1]
+        );
+        assert_eq!(
+            batches[1]
+                .column(1)
+                .as_fixed_size_list()
+                .values()
+                .as_primitive::<Float32Type>()
+                .values(),
+            &[1.0, 1.0]
+        );
+
+        let missing = [dir.path().join("missing.parquet")];
+        assert!(parquet_reader(&missing, target, CoercionOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_int96_to_timestamp() {
+        // 2023-01-01T01:00:00.000000123Z is 1 hour and 123 nanoseconds into Julian day
+        // 2459946.
+        let mut int96 = (3_600_000_000_000i64 + 123).to_le_bytes().to_vec();
+        int96.extend(2_459_946i32.to_le_bytes());
+        let array: Arc<dyn Array> = Arc::new(
+            FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+                vec![Some(int96), None].into_iter(),
+                12,
+            )
+            .unwrap(),
+        );
+
+        let field = Field::new(
+            "ts",
+            DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+            true,
+        );
+        let timestamps = coerce_array(&array, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(timestamps.data_type(), field.data_type());
+        let timestamps = timestamps.as_primitive::<TimestampNanosecondType>();
+        assert_eq!(timestamps.value(0), 1_672_534_800_000_000_123);
+        assert!(timestamps.is_null(1));
+
+        let field = Field::new("ts", DataType::Timestamp(TimeUnit::Microsecond, None), true);
+        let timestamps = coerce_array(&array, &field, &CoercionOptions::default()).unwrap();
+        assert_eq!(
+            timestamps
+                .as_primitive::<TimestampMicrosecondType>()
+                .value(0),
+            1_672_534_800_000_000
+        );
+
+        for nanos in [-1, NANOS_PER_DAY] {
+            let mut int96 = nanos.to_le_bytes().to_vec();
+            int96.extend(2_459_946i32.to_le_bytes());
+            let array = FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+                vec![Some(int96)].into_iter(),
+                12,
+            )
+            .unwrap();
+            let err = int96_to_timestamp(&array, None).unwrap_err();
+            assert!(err.to_string().contains("outside of a day"));
+        }
+    }
+}